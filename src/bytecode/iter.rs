@@ -0,0 +1,63 @@
+//! # Iterators
+//!
+//! ⌭ iterators hand out values one at a time, on demand: the elements of a
+//! collection, the integers of a range, the values a generator function
+//! yields, or those of another iterator passed through ⧉ or ⧊. An iterator
+//! is stateful and shared, so every copy of it advances together.
+
+use super::opcode::Value;
+use crate::vm::context::SuspendedFrame;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Where an iterator's values come from
+#[derive(Debug)]
+pub enum IterSource {
+    /// Values of a list in order: array elements, map keys or graphemes
+    Items { items: Rc<Vec<Value>>, position: usize },
+    /// Integers from `next` up to (not including) `end`, `step` apart
+    Range { next: i64, end: i64, step: i64 },
+    /// Values yielded by a call to a generator function
+    Generator(GeneratorState),
+    /// Values of another iterator passed through a callable (⧉)
+    Mapped { source: IterHandle, callee: Value },
+    /// Values of another iterator a predicate holds for (⧊)
+    Filtered { source: IterHandle, callee: Value },
+}
+
+/// Progress of a generator
+#[derive(Debug)]
+pub enum GeneratorState {
+    /// Waiting to run up to its next ⌭, with its frame put aside
+    Suspended(Box<SuspendedFrame>),
+    /// Running between a resume and its next ⌭ or ⤶
+    Running,
+    /// Returned, or failed with an exception
+    Done,
+}
+
+/// Shared handle to an iterator
+#[derive(Debug, Clone)]
+pub struct IterHandle(Rc<RefCell<IterSource>>);
+
+impl IterHandle {
+    pub fn new(source: IterSource) -> Self {
+        Self(Rc::new(RefCell::new(source)))
+    }
+
+    /// Iterator over a list of values
+    pub fn items(items: Vec<Value>) -> Self {
+        Self::new(IterSource::Items { items: Rc::new(items), position: 0 })
+    }
+
+    pub fn source(&self) -> &RefCell<IterSource> {
+        &self.0
+    }
+}
+
+/// Iterators are equal only to themselves
+impl PartialEq for IterHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
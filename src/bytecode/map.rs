@@ -0,0 +1,213 @@
+//! # Ordered Map
+//!
+//! The representation behind `Value::Map`: entries are kept in insertion
+//! order (so `MapKeys`/`MapValues` and serialization are deterministic)
+//! and indexed by a hash of their key for constant-time lookup.
+
+use super::opcode::Value;
+use rustc_hash::FxHashMap;
+use std::fmt;
+
+/// The hashable form of a map key
+///
+/// Reals hash by their bit pattern, with `-0.0` folded into `0.0` and every
+/// NaN folded into one canonical NaN, so each real key can be found again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum MapKey {
+    Integer(i64),
+    Real(u64),
+    String(String),
+    Boolean(bool),
+    Rune(char),
+    Null,
+}
+
+impl MapKey {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(i) => Some(MapKey::Integer(*i)),
+            Value::Real(r) => Some(MapKey::Real(real_bits(*r))),
+            Value::String(s) => Some(MapKey::String(s.clone())),
+            Value::Boolean(b) => Some(MapKey::Boolean(*b)),
+            Value::Rune(c) => Some(MapKey::Rune(*c)),
+            Value::Null => Some(MapKey::Null),
+            _ => None,
+        }
+    }
+}
+
+fn real_bits(r: f64) -> u64 {
+    if r.is_nan() {
+        f64::NAN.to_bits()
+    } else if r == 0.0 {
+        0.0f64.to_bits()
+    } else {
+        r.to_bits()
+    }
+}
+
+/// A key that cannot be hashed (arrays, maps, functions, ...)
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnhashableKey(pub Value);
+
+/// An insertion-ordered, hash-indexed map of values
+#[derive(Clone, Default)]
+pub struct OrderedMap {
+    entries: Vec<(Value, Value)>,
+    index: FxHashMap<MapKey, usize>,
+}
+
+impl OrderedMap {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty map with room for `capacity` entries
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+            index: FxHashMap::with_capacity_and_hasher(capacity, Default::default()),
+        }
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the map has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Look up the value stored under a key
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        let slot = *self.index.get(&MapKey::from_value(key)?)?;
+        Some(&self.entries[slot].1)
+    }
+
+    /// Whether a key is present
+    pub fn contains_key(&self, key: &Value) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Insert or update an entry, returning the previous value.
+    ///
+    /// Updating keeps the entry's original position.
+    pub fn insert(&mut self, key: Value, value: Value) -> Result<Option<Value>, UnhashableKey> {
+        let hashed = match MapKey::from_value(&key) {
+            Some(hashed) => hashed,
+            None => return Err(UnhashableKey(key)),
+        };
+
+        match self.index.get(&hashed) {
+            Some(&slot) => Ok(Some(std::mem::replace(&mut self.entries[slot].1, value))),
+            None => {
+                self.index.insert(hashed, self.entries.len());
+                self.entries.push((key, value));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Remove an entry, preserving the order of the others
+    pub fn remove(&mut self, key: &Value) -> Option<Value> {
+        let slot = self.index.remove(&MapKey::from_value(key)?)?;
+        let (_, value) = self.entries.remove(slot);
+
+        // Entries after the removed one moved down by one
+        for position in self.index.values_mut() {
+            if *position > slot {
+                *position -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    /// The entry at a position in insertion order
+    pub fn get_index(&self, position: usize) -> Option<(&Value, &Value)> {
+        self.entries.get(position).map(|(k, v)| (k, v))
+    }
+
+    /// Iterate over entries in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = (&Value, &Value)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    /// Keys in insertion order
+    pub fn keys(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    /// Values in insertion order
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+}
+
+impl IntoIterator for OrderedMap {
+    type Item = (Value, Value);
+    type IntoIter = std::vec::IntoIter<(Value, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+/// Maps are equal when they hold the same entries in the same order
+impl PartialEq for OrderedMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl fmt::Debug for OrderedMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(text: &str) -> Value {
+        Value::String(text.to_string())
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut map = OrderedMap::new();
+        map.insert(s("c"), Value::Integer(1)).unwrap();
+        map.insert(s("a"), Value::Integer(2)).unwrap();
+        map.insert(s("b"), Value::Integer(3)).unwrap();
+        assert_eq!(map.insert(s("a"), Value::Integer(20)).unwrap(), Some(Value::Integer(2)));
+
+        let keys: Vec<&Value> = map.keys().collect();
+        assert_eq!(keys, vec![&s("c"), &s("a"), &s("b")]);
+
+        assert_eq!(map.remove(&s("c")), Some(Value::Integer(1)));
+        assert_eq!(map.get(&s("a")), Some(&Value::Integer(20)));
+        assert_eq!(map.get(&s("b")), Some(&Value::Integer(3)));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_real_and_rune_keys() {
+        let mut map = OrderedMap::new();
+        map.insert(Value::Real(0.0), s("zero")).unwrap();
+        map.insert(Value::Real(f64::NAN), s("nan")).unwrap();
+        map.insert(Value::Rune('ᚱ'), s("rune")).unwrap();
+        map.insert(Value::Integer(0), s("int")).unwrap();
+
+        assert_eq!(map.get(&Value::Real(-0.0)), Some(&s("zero")));
+        assert_eq!(map.get(&Value::Real(f64::NAN)), Some(&s("nan")));
+        assert_eq!(map.get(&Value::Rune('ᚱ')), Some(&s("rune")));
+        assert_eq!(map.get(&Value::Integer(0)), Some(&s("int")));
+        assert_eq!(map.len(), 4);
+
+        let key = Value::array(vec![]);
+        assert_eq!(map.insert(key.clone(), Value::Null), Err(UnhashableKey(key)));
+    }
+}
@@ -3,5 +3,11 @@
 //! Bytecode representation for the Obfusku VM.
 
 pub mod opcode;
+pub mod map;
+pub mod types;
+pub mod iter;
 
-pub use opcode::{Chunk, Closure, FunctionInfo, OpCode, Value, ValueType};
+pub use map::{OrderedMap, UnhashableKey};
+pub use iter::{GeneratorState, IterHandle, IterSource};
+pub use types::{Field, FieldType, MethodTable, StructValue, TypeDef, TypeKind, VariantDef, VariantValue};
+pub use opcode::{Chunk, Closure, Composition, FunctionInfo, OpCode, Upvalue, UpvalueCell, Value, ValueType};
@@ -12,7 +12,12 @@
 //! - **Compact**: Minimal memory footprint
 //! - **Extensible**: Easy to add new operations
 
+use super::map::OrderedMap;
+use super::types::{FieldType, MethodTable, StructValue, TypeDef, TypeKind, VariantValue};
+use super::iter::IterHandle;
 use std::fmt;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 /// Bytecode opcodes for the Obfusku VM
 ///
@@ -42,6 +47,21 @@ pub enum OpCode {
     /// Rotate top three values (a b c -> c a b)
     Rot = 0x08,
 
+    // ═══════════════════════════════════════════════════════════════
+    // SPELL STACK OPERATIONS
+    // ═══════════════════════════════════════════════════════════════
+
+    /// Move the top of the stack onto the spell stack (⇑)
+    SpellPush = 0x09,
+    /// Move the top of the spell stack onto the stack (⇓)
+    SpellPop = 0x0A,
+    /// Duplicate the top of the spell stack (⇕)
+    SpellDup = 0x0B,
+    /// Swap the top two spell stack values (⇆)
+    SpellSwap = 0x0C,
+    /// Rotate the top three spell stack values (↻)
+    SpellRot = 0x0D,
+
     // ═══════════════════════════════════════════════════════════════
     // VARIABLE OPERATIONS
     // ═══════════════════════════════════════════════════════════════
@@ -127,6 +147,23 @@ pub enum OpCode {
     /// Continue to next iteration
     Continue = 0x55,
 
+    // ═══════════════════════════════════════════════════════════════
+    // ITERATION
+    // ═══════════════════════════════════════════════════════════════
+
+    /// Replace the collection on top of the stack with an iteration state
+    IterBegin = 0x56,
+    /// Push the next element (or key and value) of the iteration state, or
+    /// jump when it is exhausted (8-bit binding count + 16-bit offset follow)
+    IterNext = 0x57,
+    /// Replace start, end and step on the stack with a range state
+    RangeBegin = 0x58,
+    /// Push the next integer of the range state, or jump when it is
+    /// exhausted (16-bit offset follows)
+    RangeNext = 0x59,
+    /// Bind the top of the stack to a loop variable (name index follows)
+    BindLoopVar = 0x5A,
+
     // ═══════════════════════════════════════════════════════════════
     // FUNCTION OPERATIONS
     // ═══════════════════════════════════════════════════════════════
@@ -141,6 +178,8 @@ pub enum OpCode {
     LoadFunc = 0x63,
     /// Call a closure value (arity follows, closure on stack)
     CallClosure = 0x5E,
+    /// Call a builtin from the standard library (builtin index + arity follows)
+    CallNative = 0x5F,
 
     // ═══════════════════════════════════════════════════════════════
     // ARRAY OPERATIONS
@@ -176,6 +215,18 @@ pub enum OpCode {
     /// Get map values as array
     MapValues = 0x6F,
 
+    // ═══════════════════════════════════════════════════════════════
+    // IN-PLACE COLLECTION OPERATIONS
+    // ═══════════════════════════════════════════════════════════════
+
+    /// Get an element of an array, map or string (container and index on stack)
+    Index = 0x74,
+    /// Append to the array held by a variable, in place (name index follows)
+    AppendVar = 0x75,
+    /// Store into the collection held by a variable, in place (name index follows,
+    /// index and value on stack)
+    SetIndexVar = 0x76,
+
     // ═══════════════════════════════════════════════════════════════
     // CLOSURE OPERATIONS (v0.3.0)
     // ═══════════════════════════════════════════════════════════════
@@ -199,8 +250,19 @@ pub enum OpCode {
     MatchEnd = 0xA5,
     /// Wildcard pattern match (always succeeds)
     MatchWildcard = 0xA6,
-    /// Bind matched value to variable
+    /// Pop the matched value and bind it to a variable (name index follows)
     MatchBind = 0xA7,
+    /// Push whether the value on top has the given type (8-bit type follows)
+    MatchType = 0xAB,
+    /// Pop a value and two bounds, push whether the value is an integer in lo‥hi
+    MatchRange = 0xAC,
+    /// Push whether the value on top is an array of the given length
+    /// (8-bit length + 8-bit rest flag follow; with rest it is a minimum)
+    MatchArray = 0xAD,
+    /// Pop a key and a map, push the entry's value and whether it exists
+    MatchEntry = 0xAE,
+    /// Pop an array and push its elements from a start index (8-bit index follows)
+    MatchRest = 0xAF,
 
     // ═══════════════════════════════════════════════════════════════
     // MODULE OPERATIONS (v0.3.0)
@@ -217,16 +279,80 @@ pub enum OpCode {
     // EXCEPTION HANDLING (v0.3.0)
     // ═══════════════════════════════════════════════════════════════
 
-    /// Begin try block (handler offset follows)
+    /// Begin try block (☊ and ☋ offsets follow, 0 when absent)
     TryBegin = 0xB0,
-    /// End try block
+    /// End try block, running its ☋ block before carrying on
     TryEnd = 0xB1,
     /// Throw exception (value on stack)
     Throw = 0xB2,
-    /// Catch exception (binds to variable)
+    /// Bind the exception being handled to a variable (name index follows)
     Catch = 0xB3,
     /// Finally block marker
     Finally = 0xB4,
+    /// End of a finally block: resume what was interrupted by it
+    EndFinally = 0xB5,
+    /// Push the exception being handled
+    LoadException = 0xB6,
+    /// Replace an exception value with its kind (a string, or null)
+    ExceptionKind = 0xB7,
+    /// Throw the exception being handled again, keeping its origin
+    Rethrow = 0xB8,
+
+    // ═══════════════════════════════════════════════════════════════
+    // USER TYPES
+    // ═══════════════════════════════════════════════════════════════
+
+    /// Pop the field values and build a struct (16-bit type index follows)
+    MakeStruct = 0xC0,
+    /// Pop the payload and build an enum variant (16-bit type index + 8-bit tag follow)
+    MakeVariant = 0xC1,
+    /// Replace a struct or variant with one of its fields (16-bit name index follows)
+    GetField = 0xC2,
+    /// Pop a value into a field of a variable's struct in place
+    /// (16-bit variable name + 16-bit field name follow)
+    SetFieldVar = 0xC3,
+    /// Push whether the value on top is of a user type (16-bit type index +
+    /// 8-bit tag follow; a tag of 0xFF accepts any variant)
+    MatchUserType = 0xC4,
+    /// Replace a struct or variant with its field at a position (8-bit index follows)
+    FieldAt = 0xC5,
+    /// Call a trait method on the receiver below the arguments, found through
+    /// the receiver type's method table (16-bit method name + 8-bit arity follow)
+    Invoke = 0xC6,
+
+    // ═══════════════════════════════════════════════════════════════
+    // HIGHER-ORDER OPERATIONS
+    // ═══════════════════════════════════════════════════════════════
+
+    /// Replace a collection and a callable with the callable applied to each element (⧉)
+    MapWith = 0xC7,
+    /// Replace a collection and a predicate with the elements it holds for (⧊)
+    FilterWith = 0xC8,
+    /// Fold a collection with a callable (8-bit seed flag follows; the seed
+    /// sits below the callable when set) (⧋)
+    ReduceWith = 0xC9,
+    /// Replace a value and a callable with the callable applied to the value (⋈)
+    Pipe = 0xCA,
+    /// Replace two callables with the callable running one after the other (⋉)
+    Compose = 0xCB,
+    /// Pop arguments and bind them to the leading parameters of the callable
+    /// below them (8-bit argument count follows) (⋊)
+    BindArgs = 0xCC,
+    /// Call a function with a ⌖ of named arguments above the positional ones
+    /// (16-bit function index + 8-bit positional count follow)
+    CallNamed = 0xCD,
+    /// Call the closure on top with a ⌖ of named arguments below it and the
+    /// positional ones below that (8-bit positional count follows)
+    CallClosureNamed = 0xCE,
+    /// Call a function in place of the current one, whose result it returns
+    /// (16-bit function index + 8-bit arity follow)
+    TailCall = 0xCF,
+    /// Call the closure on top in place of the current function (8-bit
+    /// arity follows)
+    TailCallClosure = 0xD0,
+    /// Suspend the running generator, handing the value on top to whoever
+    /// resumed it (⌭)
+    Yield = 0xD1,
 
     // ═══════════════════════════════════════════════════════════════
     // INPUT/OUTPUT
@@ -253,6 +379,14 @@ pub enum OpCode {
     IncAcc = 0x82,
     /// Decrement accumulator
     DecAcc = 0x83,
+    /// Open a loop's accumulator frame, seeded with the enclosing ✹
+    EnterLoop = 0x84,
+    /// Close the innermost loop's accumulator frame
+    ExitLoop = 0x85,
+    /// Load the accumulator of an enclosing loop (8-bit depth follows)
+    LoadOuterAcc = 0x86,
+    /// Store to the accumulator of an enclosing loop (8-bit depth follows)
+    StoreOuterAcc = 0x87,
 
     /// String concatenation
     Concat = 0x90,
@@ -261,6 +395,8 @@ pub enum OpCode {
     /// String interpolation (string index + var count follows)
     Interpolate = 0x92,
 
+    /// Halt execution with the exit status on top of the stack
+    Exit = 0xFD,
     /// No operation
     Nop = 0xFE,
     /// Halt execution
@@ -275,18 +411,25 @@ impl OpCode {
             OpCode::DeclareVar => 3,   // 16-bit name + 8-bit type
             OpCode::LoadVar => 2,      // 16-bit name index
             OpCode::StoreVar => 2,     // 16-bit name index
+            OpCode::AppendVar => 2,    // 16-bit name index
+            OpCode::SetIndexVar => 2,  // 16-bit name index
             OpCode::LoadGlobal => 2,   // 16-bit name index
             OpCode::StoreGlobal => 2,  // 16-bit name index
             OpCode::Jump => 2,         // 16-bit offset
             OpCode::JumpIfFalse => 2,  // 16-bit offset
             OpCode::JumpIfTrue => 2,   // 16-bit offset
             OpCode::Loop => 2,         // 16-bit offset (negative)
+            OpCode::IterNext => 3,     // 8-bit binding count + 16-bit offset
+            OpCode::RangeNext => 2,    // 16-bit offset
+            OpCode::BindLoopVar => 2,  // 16-bit name index
             OpCode::Call => 3,         // 16-bit function index + 8-bit arity
             OpCode::DefineFunc => 5,   // 16-bit name + 8-bit arity + 16-bit body length
             OpCode::LoadFunc => 2,     // 16-bit function index
             OpCode::PrintLit => 2,     // 16-bit string index
             OpCode::Input => 3,        // 16-bit name + 8-bit type
             OpCode::Convert => 1,      // 8-bit target type
+            OpCode::LoadOuterAcc => 1, // 8-bit loop depth
+            OpCode::StoreOuterAcc => 1, // 8-bit loop depth
             OpCode::MakeArray => 2,    // 16-bit element count
             OpCode::Interpolate => 3,  // 16-bit string index + 8-bit var count
             // v0.3.0 opcodes
@@ -295,14 +438,31 @@ impl OpCode {
             OpCode::LoadCapture => 2,  // 16-bit capture index
             OpCode::StoreCapture => 2, // 16-bit capture index
             OpCode::CallClosure => 1,  // 8-bit arity (closure on stack)
+            OpCode::CallNative => 3,   // 16-bit builtin index + 8-bit arity
             OpCode::MatchBegin => 1,   // 8-bit arm count
             OpCode::MatchArm => 2,     // 16-bit jump offset
             OpCode::MatchBind => 2,    // 16-bit name index
+            OpCode::MatchType => 1,    // 8-bit value type
+            OpCode::MatchArray => 2,   // 8-bit length + 8-bit rest flag
+            OpCode::MatchRest => 1,    // 8-bit start index
             OpCode::Import => 2,       // 16-bit module name index
             OpCode::Export => 2,       // 16-bit symbol name index
             OpCode::LoadModule => 4,   // 16-bit module + 16-bit symbol
-            OpCode::TryBegin => 2,     // 16-bit handler offset
+            OpCode::TryBegin => 4,     // 16-bit catch + 16-bit finally offset
             OpCode::Catch => 2,        // 16-bit variable name index
+            OpCode::MakeStruct => 2,   // 16-bit type index
+            OpCode::MakeVariant => 3,  // 16-bit type index + 8-bit tag
+            OpCode::GetField => 2,     // 16-bit field name index
+            OpCode::SetFieldVar => 4,  // 16-bit variable + 16-bit field name
+            OpCode::MatchUserType => 3, // 16-bit type index + 8-bit tag
+            OpCode::FieldAt => 1,      // 8-bit field position
+            OpCode::Invoke => 3,       // 16-bit method name + 8-bit arity
+            OpCode::ReduceWith => 1,   // 8-bit seed flag
+            OpCode::BindArgs => 1,     // 8-bit argument count
+            OpCode::CallNamed => 3,    // 16-bit function index + 8-bit positional count
+            OpCode::CallClosureNamed => 1, // 8-bit positional count
+            OpCode::TailCall => 3,     // 16-bit function index + 8-bit arity
+            OpCode::TailCallClosure => 1, // 8-bit arity (closure on stack)
             _ => 0,
         }
     }
@@ -329,6 +489,10 @@ pub enum ValueType {
     Function = 8,
     Closure = 9,
     Module = 10,
+    FileHandle = 11,
+    Struct = 12,
+    Enum = 13,
+    Iterator = 14,
 }
 
 impl fmt::Display for ValueType {
@@ -345,26 +509,59 @@ impl fmt::Display for ValueType {
             ValueType::Function => write!(f, "λ"),
             ValueType::Closure => write!(f, "λ⊃"),
             ValueType::Module => write!(f, "📦"),
+            ValueType::FileHandle => write!(f, "📂"),
+            ValueType::Struct => write!(f, "⌻"),
+            ValueType::Enum => write!(f, "⎊"),
+            ValueType::Iterator => write!(f, "⌭"),
         }
     }
 }
 
-/// Closure captures environment snapshot
+/// A variable captured by a closure
+///
+/// While the scope declaring the variable is alive the upvalue refers to it
+/// there, so the closure and the scope see each other's updates. When the
+/// scope ends the upvalue is closed: the value moves into it, still shared
+/// by every closure that captured the variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Upvalue {
+    /// Variable `name` of the call frame at `depth`
+    Open { depth: usize, name: String },
+    /// The variable's value after its scope ended
+    Closed(Value),
+}
+
+/// Shared handle to an upvalue
+pub type UpvalueCell = Rc<RefCell<Upvalue>>;
+
+/// Function together with the variables it captured
 #[derive(Debug, Clone, PartialEq)]
 pub struct Closure {
     /// Index into function table
     pub function_index: usize,
-    /// Captured values from enclosing scope
-    pub captures: Vec<Value>,
+    /// Captured variables, in the order of the function's capture names
+    pub captures: Vec<UpvalueCell>,
+    /// Leading arguments bound with ⋊, passed before those of each call
+    pub bound: Vec<Value>,
 }
 
 impl Closure {
-    pub fn new(function_index: usize, captures: Vec<Value>) -> Self {
-        Self { function_index, captures }
+    pub fn new(function_index: usize, captures: Vec<UpvalueCell>) -> Self {
+        Self { function_index, captures, bound: Vec::new() }
     }
 }
 
+/// Two callables joined with ⋉: the result of `first` is handed to `then`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Composition {
+    pub first: Value,
+    pub then: Value,
+}
+
 /// A runtime value in the VM
+///
+/// Arrays and maps are shared behind `Rc`: cloning a value is cheap, and a
+/// collection is only copied when it is mutated while shared (copy-on-write).
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Integer(i64),
@@ -372,9 +569,9 @@ pub enum Value {
     String(String),
     Boolean(bool),
     Rune(char),
-    Array(Vec<Value>),
-    /// Map using Vec for ordered iteration, could optimize with HashMap later
-    Map(Vec<(Value, Value)>),
+    Array(Rc<Vec<Value>>),
+    /// Insertion-ordered map with hashed key lookup
+    Map(Rc<OrderedMap>),
     Null,
     /// Index into function table
     Function(usize),
@@ -382,9 +579,29 @@ pub enum Value {
     ClosureVal(Box<Closure>),
     /// Module reference (module index)
     Module(usize),
+    /// Open file (index into the runtime's file table)
+    FileHandle(usize),
+    /// Instance of a ⌻ struct
+    Struct(Rc<StructValue>),
+    /// Variant of a ⎊ enum
+    Variant(Rc<VariantValue>),
+    /// Callables composed with ⋉
+    Composed(Rc<Composition>),
+    /// ⌭ iterator, shared by every copy
+    Iterator(IterHandle),
 }
 
 impl Value {
+    /// Wrap elements as an array value
+    pub fn array(elements: Vec<Value>) -> Self {
+        Value::Array(Rc::new(elements))
+    }
+
+    /// Wrap entries as a map value
+    pub fn map(entries: OrderedMap) -> Self {
+        Value::Map(Rc::new(entries))
+    }
+
     /// Get the type of this value
     pub fn value_type(&self) -> ValueType {
         match self {
@@ -399,6 +616,11 @@ impl Value {
             Value::Function(_) => ValueType::Function,
             Value::ClosureVal(_) => ValueType::Closure,
             Value::Module(_) => ValueType::Module,
+            Value::FileHandle(_) => ValueType::FileHandle,
+            Value::Struct(_) => ValueType::Struct,
+            Value::Variant(_) => ValueType::Enum,
+            Value::Composed(_) => ValueType::Closure,
+            Value::Iterator(_) => ValueType::Iterator,
         }
     }
 
@@ -416,6 +638,11 @@ impl Value {
             Value::Function(_) => true,
             Value::ClosureVal(_) => true,
             Value::Module(_) => true,
+            Value::FileHandle(_) => true,
+            Value::Struct(_) => true,
+            Value::Variant(_) => true,
+            Value::Composed(_) => true,
+            Value::Iterator(_) => true,
         }
     }
 
@@ -455,14 +682,19 @@ impl Value {
             Value::Function(i) => format!("λ#{}", i),
             Value::ClosureVal(c) => format!("λ⊃#{}", c.function_index),
             Value::Module(i) => format!("📦#{}", i),
+            Value::FileHandle(i) => format!("📂#{}", i),
+            Value::Struct(s) => s.to_string(),
+            Value::Variant(v) => v.to_string(),
+            Value::Composed(c) => format!("{}⋉{}", c.first, c.then),
+            Value::Iterator(_) => "⌭".to_string(),
         }
     }
 
     /// Check if value can be used as map key (hashable)
     pub fn is_hashable(&self) -> bool {
         matches!(self,
-            Value::Integer(_) | Value::String(_) | Value::Boolean(_) |
-            Value::Rune(_) | Value::Null
+            Value::Integer(_) | Value::Real(_) | Value::String(_) |
+            Value::Boolean(_) | Value::Rune(_) | Value::Null
         )
     }
 }
@@ -488,11 +720,27 @@ pub struct FunctionInfo {
     pub length: usize,
     /// Names of captured variables (for closures)
     pub capture_names: Vec<String>,
+    /// Default values of the trailing parameters before a variadic one
+    pub defaults: Vec<Value>,
+    /// Whether the last parameter collects the remaining arguments into a ⌬
+    pub variadic: bool,
+    /// Whether the body yields with ⌭, so a call makes a generator
+    pub generator: bool,
 }
 
 impl FunctionInfo {
     pub fn new(name: String, arity: u8, params: Vec<(String, ValueType)>, start: usize, length: usize) -> Self {
-        Self { name, arity, params, start, length, capture_names: Vec::new() }
+        Self { name, arity, params, start, length, capture_names: Vec::new(), defaults: Vec::new(), variadic: false, generator: false }
+    }
+
+    /// Parameters that take one argument each, leaving out a variadic one
+    pub fn fixed_params(&self) -> usize {
+        self.params.len() - self.variadic as usize
+    }
+
+    /// Number of arguments a call must give
+    pub fn required_params(&self) -> usize {
+        self.fixed_params() - self.defaults.len()
     }
 
     /// Check if this function requires closure (has captures)
@@ -519,6 +767,10 @@ pub struct Chunk {
     pub name: String,
     /// Function table
     pub functions: Vec<FunctionInfo>,
+    /// ⌻ struct and ⎊ enum definitions
+    pub types: Vec<Rc<TypeDef>>,
+    /// Trait methods of each type with a ⍜ implementation
+    pub method_tables: Vec<MethodTable>,
 }
 
 impl Chunk {
@@ -530,6 +782,8 @@ impl Chunk {
             lines: Vec::new(),
             name: name.into(),
             functions: Vec::new(),
+            types: Vec::new(),
+            method_tables: Vec::new(),
         }
     }
 
@@ -567,6 +821,29 @@ impl Chunk {
         (self.strings.len() - 1) as u16
     }
 
+    /// Add a struct or enum definition and return its index
+    pub fn add_type(&mut self, def: TypeDef) -> u16 {
+        self.types.push(Rc::new(def));
+        (self.types.len() - 1) as u16
+    }
+
+    /// Attach a method implementation to a type's method table
+    pub fn add_method(&mut self, target: FieldType, name: impl Into<String>, func: u16) {
+        let position = match self.method_tables.iter().position(|table| table.target == target) {
+            Some(position) => position,
+            None => {
+                self.method_tables.push(MethodTable { target, methods: Vec::new() });
+                self.method_tables.len() - 1
+            }
+        };
+        self.method_tables[position].methods.push((name.into(), func));
+    }
+
+    /// Method table of a type, if it implements any trait
+    pub fn method_table(&self, target: FieldType) -> Option<&MethodTable> {
+        self.method_tables.iter().find(|table| table.target == target)
+    }
+
     /// Add a function and return its index
     pub fn add_function(&mut self, func: FunctionInfo) -> u16 {
         self.functions.push(func);
@@ -617,16 +894,73 @@ impl Chunk {
                 offset += 2;
                 format!("{} #{} ({})", opcode, idx, self.constants.get(idx as usize).map(|v| v.to_string()).unwrap_or_default())
             }
-            OpCode::LoadVar | OpCode::StoreVar | OpCode::LoadGlobal | OpCode::StoreGlobal => {
+            OpCode::LoadVar | OpCode::StoreVar | OpCode::LoadGlobal | OpCode::StoreGlobal
+            | OpCode::AppendVar | OpCode::SetIndexVar | OpCode::BindLoopVar | OpCode::MatchBind => {
                 let idx = self.read_u16(offset);
                 offset += 2;
                 format!("{} #{} ({})", opcode, idx, self.strings.get(idx as usize).cloned().unwrap_or_default())
             }
-            OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfTrue | OpCode::Loop => {
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfTrue | OpCode::Loop
+            | OpCode::RangeNext => {
                 let target = self.read_u16(offset);
                 offset += 2;
                 format!("{} -> {}", opcode, target)
             }
+            OpCode::TryBegin => {
+                let catch = self.read_u16(offset);
+                let finally = self.read_u16(offset + 2);
+                offset += 4;
+                format!("{} catch -> {}, finally -> {}", opcode, catch, finally)
+            }
+            OpCode::IterNext => {
+                let bindings = self.code[offset];
+                let target = self.read_u16(offset + 1);
+                offset += 3;
+                format!("{} {} -> {}", opcode, bindings, target)
+            }
+            OpCode::MakeStruct => {
+                let idx = self.read_u16(offset);
+                offset += 2;
+                format!("{} #{} ({})", opcode, idx, self.type_name(idx as usize))
+            }
+            OpCode::MakeVariant | OpCode::MatchUserType => {
+                let idx = self.read_u16(offset);
+                let tag = self.code[offset + 2];
+                offset += 3;
+                let variant = match self.types.get(idx as usize) {
+                    Some(def) if tag != u8::MAX && matches!(def.kind, TypeKind::Enum(_)) => {
+                        format!("⊷{}", def.variant_name(tag as usize))
+                    }
+                    _ => String::new(),
+                };
+                format!("{} #{} ({}{})", opcode, idx, self.type_name(idx as usize), variant)
+            }
+            OpCode::GetField => {
+                let idx = self.read_u16(offset);
+                offset += 2;
+                format!("{} ⊷{}", opcode, self.strings.get(idx as usize).cloned().unwrap_or_default())
+            }
+            OpCode::SetFieldVar => {
+                let var = self.read_u16(offset);
+                let field = self.read_u16(offset + 2);
+                offset += 4;
+                format!("{} {}⊷{}", opcode,
+                    self.strings.get(var as usize).cloned().unwrap_or_default(),
+                    self.strings.get(field as usize).cloned().unwrap_or_default())
+            }
+            OpCode::Invoke => {
+                let idx = self.read_u16(offset);
+                let arity = self.code[offset + 2];
+                offset += 3;
+                format!("{} ⊷{}/{}", opcode, self.strings.get(idx as usize).cloned().unwrap_or_default(), arity)
+            }
+            OpCode::CallNative => {
+                let idx = self.read_u16(offset);
+                let arity = self.code[offset + 2];
+                offset += 3;
+                let name = crate::stdlib::get(idx as usize).map(|b| b.name).unwrap_or("?");
+                format!("{} #{} ({}/{})", opcode, idx, name, arity)
+            }
             _ => {
                 offset += operand_count;
                 format!("{}", opcode)
@@ -636,6 +970,10 @@ impl Chunk {
         (instruction, offset)
     }
 
+    fn type_name(&self, index: usize) -> String {
+        self.types.get(index).map(|def| def.name.clone()).unwrap_or_else(|| "?".to_string())
+    }
+
     fn read_u16(&self, offset: usize) -> u16 {
         let low = self.code[offset] as u16;
         let high = self.code[offset + 1] as u16;
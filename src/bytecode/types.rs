@@ -0,0 +1,135 @@
+//! # User Types
+//!
+//! Struct (⌻) and enum (⎊) definitions, kept in each chunk's type table,
+//! the values built from them, and the method tables that trait (⍜)
+//! implementations attach to types.
+
+use super::opcode::{Value, ValueType};
+use std::fmt;
+use std::rc::Rc;
+
+/// Declared type of a struct field or variant payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    /// A builtin value type (⟁, ⌘, ⌬, ...)
+    Value(ValueType),
+    /// A struct or enum from the chunk's type table
+    User(u16),
+}
+
+/// A named, typed field
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub ty: FieldType,
+}
+
+/// One variant of an enum and its payload fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantDef {
+    pub name: String,
+    pub fields: Vec<Field>,
+}
+
+/// What a user type is made of
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeKind {
+    Struct(Vec<Field>),
+    Enum(Vec<VariantDef>),
+}
+
+/// A ⌻ struct or ⎊ enum definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDef {
+    pub name: String,
+    pub kind: TypeKind,
+}
+
+impl TypeDef {
+    /// Fields of the struct, or the payload of the variant with the given tag
+    pub fn fields(&self, tag: usize) -> &[Field] {
+        match &self.kind {
+            TypeKind::Struct(fields) => fields,
+            TypeKind::Enum(variants) => variants.get(tag).map_or(&[], |v| &v.fields),
+        }
+    }
+
+    /// Find an enum variant by name, returning its tag
+    pub fn variant(&self, name: &str) -> Option<(usize, &VariantDef)> {
+        match &self.kind {
+            TypeKind::Struct(_) => None,
+            TypeKind::Enum(variants) => variants.iter().enumerate().find(|(_, v)| v.name == name),
+        }
+    }
+
+    /// Name of the variant with the given tag (empty for a struct)
+    pub fn variant_name(&self, tag: usize) -> &str {
+        match &self.kind {
+            TypeKind::Struct(_) => "",
+            TypeKind::Enum(variants) => variants.get(tag).map_or("?", |v| v.name.as_str()),
+        }
+    }
+}
+
+/// Methods implemented for one type, across all of its ⍜ implementations
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodTable {
+    /// The type receiving the methods (a builtin type or a user type)
+    pub target: FieldType,
+    /// Method names and the functions implementing them
+    pub methods: Vec<(String, u16)>,
+}
+
+impl MethodTable {
+    /// Function implementing a method, if this type has it
+    pub fn method(&self, name: &str) -> Option<u16> {
+        self.methods.iter().find(|(method, _)| method == name).map(|&(_, func)| func)
+    }
+}
+
+/// An instance of a ⌻ struct
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructValue {
+    pub def: Rc<TypeDef>,
+    pub fields: Vec<Value>,
+}
+
+/// A ⎊ enum variant with its payload
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantValue {
+    pub def: Rc<TypeDef>,
+    pub tag: usize,
+    pub fields: Vec<Value>,
+}
+
+impl fmt::Display for StructValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "⌻{}", self.def.name)?;
+        write_payload(f, &self.fields)
+    }
+}
+
+impl fmt::Display for VariantValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "⎊{}⊷{}", self.def.name, self.def.variant_name(self.tag))?;
+        if self.fields.is_empty() {
+            return Ok(());
+        }
+        write_payload(f, &self.fields)
+    }
+}
+
+/// Write field values as [a, b, ...]
+fn write_payload(f: &mut fmt::Formatter<'_>, fields: &[Value]) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, value) in fields.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        match value {
+            Value::String(s) => write!(f, "{:?}", s)?,
+            other => write!(f, "{}", other)?,
+        }
+    }
+    write!(f, "]")
+}
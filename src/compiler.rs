@@ -3,9 +3,11 @@
 //! Compiles Obfusku source code into bytecode for the VM.
 //! This is a single-pass compiler that emits bytecode directly.
 
-use crate::bytecode::{Chunk, FunctionInfo, OpCode, Value, ValueType};
+use crate::bytecode::{Chunk, Field, FieldType, FunctionInfo, OpCode, TypeDef, TypeKind, Value, ValueType, VariantDef};
+use crate::interpolate::{self, Segment, TemplateError};
 use crate::lexer::{Lexer, LexerError, Token, TokenKind};
 use crate::source_map::SourceMap;
+use crate::stdlib;
 use crate::symbols::{SymbolMeaning, SymbolTable};
 use thiserror::Error;
 
@@ -45,8 +47,8 @@ pub enum CompileError {
     #[error("⚡ Function '{name}' is already defined")]
     DuplicateFunction { name: String },
 
-    #[error("❓ Function '{name}' is not defined")]
-    UndefinedFunction { name: String },
+    #[error("❓ Function '{name}' is not defined (line {line})")]
+    UndefinedFunction { name: String, line: usize },
 
     #[error("🚫 Return statement outside of function — nowhere to return to")]
     ReturnOutsideFunction,
@@ -54,19 +56,156 @@ pub enum CompileError {
     #[error("⚡ Wrong number of arguments for '{name}': expected {expected}, got {got}")]
     WrongArity { name: String, expected: u8, got: u8 },
 
+    #[error("🎯 {name} has no parameter '{param}' (line {line})")]
+    UnknownArgument { name: String, param: String, line: usize },
+
+    #[error("🎯 '{param}' is given twice in the call to {name} (line {line})")]
+    DuplicateArgument { name: String, param: String, line: usize },
+
+    #[error("🎯 Parameter '{param}' at line {line} needs a default value, as the ones before it have one")]
+    RequiredAfterDefault { param: String, line: usize },
+
+    #[error("🎯 Variadic parameter '{param}' at line {line} must be the last one and declared ⌬")]
+    MisplacedVariadic { param: String, line: usize },
+
     #[error("🔮 Variable '{name}' not found in any scope")]
     UndefinedVariable { name: String },
 
     #[error("🔮 Cannot capture variable '{name}' — closure capture failed")]
     CaptureError { name: String },
+
+    #[error("✤ Malformed template at line {line}: {error}")]
+    TemplateError { line: usize, error: TemplateError },
+
+    #[error("🥞 {glyph} at line {line} needs {needed} value(s) on the spell stack, but only {available} would be there")]
+    StackUnderflow { glyph: String, line: usize, needed: usize, available: usize },
+
+    #[error("🏷️ No enclosing loop is labeled '{label}' (line {line})")]
+    UnknownLabel { label: String, line: usize },
+
+    #[error("🏷️ Loop label '{label}' at line {line} is already used by an enclosing loop")]
+    DuplicateLabel { label: String, line: usize },
+
+    #[error("✹ ✹[{depth}] at line {line} reaches past the {loops} enclosing loop(s)")]
+    AccumulatorOutOfReach { depth: usize, loops: usize, line: usize },
+
+    #[error("🛡️ ⚠ without a value at line {line} rethrows, so it must be inside a ☊ block")]
+    RethrowOutsideCatch { line: usize },
+
+    #[error("🛡️ {glyph} at line {line} would leave a ☋ block — a finally block always runs to its end")]
+    LeavesFinally { glyph: String, line: usize },
+
+    #[error("⌭ ⌭[...] at line {line} yields from outside any function — only a λ can be a generator")]
+    YieldOutsideFunction { line: usize },
+
+    #[error("⌭ ⌭[...] at line {line} is inside ☄ — a generator cannot be suspended while a handler is watching it")]
+    YieldInTry { line: usize },
+
+    #[error("🥞 Unbalanced spell stack at line {line}: {construct} leaves {found} value(s) where {expected} were expected")]
+    StackImbalance { construct: String, line: usize, expected: usize, found: usize },
+
+    #[error("🧪 Mismatched arm types at line {line}: {construct} yields {found} where the other arms yield {expected}")]
+    ArmTypeMismatch { construct: String, line: usize, expected: ValueType, found: ValueType },
+
+    #[error("🌫️ The ⟡ match at line {line} is used as a value but has no ◇ arm to yield one when nothing else matches")]
+    NonExhaustiveMatchValue { line: usize },
+
+    #[error("🧩 Type '{name}' at line {line} is already defined")]
+    DuplicateType { name: String, line: usize },
+
+    #[error("🧩 Unknown type '{name}' at line {line}")]
+    UnknownType { name: String, line: usize },
+
+    #[error("🧩 '{field}' is declared twice in {type_name} (line {line})")]
+    DuplicateField { type_name: String, field: String, line: usize },
+
+    #[error("🧩 {type_name} has no variant '{variant}' (line {line})")]
+    UnknownVariant { type_name: String, variant: String, line: usize },
+
+    #[error("🧩 {name} at line {line} has {expected} field(s), but {got} were given")]
+    WrongFieldCount { name: String, line: usize, expected: usize, got: usize },
+
+    #[error("🪞 Trait '{name}' at line {line} is already defined")]
+    DuplicateTrait { name: String, line: usize },
+
+    #[error("🪞 Unknown trait '{name}' at line {line}")]
+    UnknownTrait { name: String, line: usize },
+
+    #[error("🪞 Method '{method}' at line {line} is already declared by trait {trait_name}")]
+    DuplicateMethod { trait_name: String, method: String, line: usize },
+
+    #[error("🪞 {type_name} already implements {trait_name} (line {line})")]
+    DuplicateImpl { trait_name: String, type_name: String, line: usize },
+
+    #[error("🪞 ⍜{trait_name} for {type_name} at line {line} is missing method '{method}'")]
+    MissingMethod { trait_name: String, type_name: String, method: String, line: usize },
+
+    #[error("🪞 '{method}' at line {line} is not a method of trait {trait_name}")]
+    ForeignMethod { trait_name: String, method: String, line: usize },
+
+    #[error("🪞 Method '{method}' at line {line} must take {expected} as declared by {trait_name}, not {found}")]
+    MethodSignatureMismatch { trait_name: String, method: String, line: usize, expected: String, found: String },
+
+    #[error("🪞 No trait declares a method '{method}' (line {line})")]
+    UnknownMethod { method: String, line: usize },
+
+    #[error("🪞 {glyph} at line {line} must be at the top level of the spell")]
+    NestedTraitItem { glyph: String, line: usize },
 }
 
 type CompileResult<T> = Result<T, CompileError>;
 
+/// Compilation warnings: the spell compiles, but may not do what was meant
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CompileWarning {
+    #[error("🌫️ The ⟡ match at line {line} has no ◇ arm; a value no arm accepts is silently skipped")]
+    NonExhaustiveMatch { line: usize },
+}
+
+/// Static type of an arithmetic result: integers stay integers, and any
+/// real operand makes it real
+fn arithmetic_type(left: Option<ValueType>, right: Option<ValueType>) -> Option<ValueType> {
+    match (left?, right?) {
+        (ValueType::Integer, ValueType::Integer) => Some(ValueType::Integer),
+        (ValueType::Integer | ValueType::Real, ValueType::Integer | ValueType::Real) => Some(ValueType::Real),
+        _ => None,
+    }
+}
+
+/// The type every arm of a value form yields, when all of them are known
+fn common_type(arm_types: &[Option<ValueType>]) -> Option<ValueType> {
+    let types: Option<Vec<ValueType>> = arm_types.iter().copied().collect();
+    types?.first().copied()
+}
+
 /// Loop information for break/continue handling
 struct LoopInfo {
     start: usize,
     break_jumps: Vec<usize>,
+    /// Name given with ⊂name[...], targeted by ⊗[name] and ↺[name]
+    label: Option<String>,
+    /// Spell stack depth when the loop was entered
+    spell_depth: usize,
+    /// Values held on the operand stack when the body starts
+    stack_temps: usize,
+    /// Try regions open when the loop was entered
+    try_depth: usize,
+    /// Finally blocks open when the loop was entered
+    finally_depth: usize,
+}
+
+/// A method signature declared by a ⍟ trait; the receiver is implicit
+#[derive(Debug, Clone)]
+struct MethodSig {
+    name: String,
+    params: Vec<ValueType>,
+}
+
+/// Parameter list of a method as written in errors: [⌻self, ⧆, ⌘]
+fn signature_text(receiver: ValueType, params: &[ValueType]) -> String {
+    let mut parts = vec![format!("{}self", receiver)];
+    parts.extend(params.iter().map(|ty| ty.to_string()));
+    format!("[{}]", parts.join(", "))
 }
 
 /// Function compilation state - used for tracking function context during compilation
@@ -87,6 +226,8 @@ struct FunctionScope {
     locals: Vec<String>,
     /// Whether this function needs closure (has captures)
     is_closure: bool,
+    /// Whether the body yields with ⌭
+    is_generator: bool,
 }
 
 impl FunctionScope {
@@ -100,6 +241,7 @@ impl FunctionScope {
             captures: Vec::new(),
             locals,
             is_closure: false,
+            is_generator: false,
         }
     }
 
@@ -133,6 +275,10 @@ impl FunctionScope {
     }
 }
 
+/// Parameters of a λ: names and types, defaults of the trailing ones, and
+/// whether the last is variadic
+type Parameters = (Vec<(String, ValueType)>, Vec<Value>, bool);
+
 /// The Obfusku compiler
 pub struct Compiler<'a> {
     symbol_table: &'a SymbolTable,
@@ -143,14 +289,50 @@ pub struct Compiler<'a> {
     had_end_program: bool,
     /// Stack of functions being compiled (supports nesting for closures)
     function_stack: Vec<FunctionScope>,
+    /// Offset and opcode of the last Call or CallClosure emitted
+    last_call: Option<(usize, OpCode)>,
     /// Function name to index mapping
     function_indices: std::collections::HashMap<String, u16>,
+    /// Top-level λ definitions declared ahead of compilation: token position
+    /// of the λ to the function index reserved for it
+    declared_functions: std::collections::HashMap<usize, u16>,
+    /// Identifiers used anywhere other than right after ⤷, so possibly
+    /// variables holding a function
+    bound_names: std::collections::HashSet<String>,
+    /// Variables declared at the top level so far
+    global_names: std::collections::HashSet<String>,
     /// Source map for error reporting
     source_map: Option<SourceMap>,
     /// Original source code
     source: String,
     /// Closure capture info: function index -> capture variable names
     closure_captures: std::collections::HashMap<u16, Vec<String>>,
+    /// Number of values on the spell stack at the current point of the
+    /// function being compiled
+    spell_depth: usize,
+    /// Values that statement-spanning constructs (match subjects, for-each
+    /// state) hold on the operand stack at the current point
+    stack_temps: usize,
+    /// Number of ☄ try regions (try and catch bodies) enclosing the current point
+    try_depth: usize,
+    /// Number of ☋ finally blocks enclosing the current point
+    finally_depth: usize,
+    /// Number of ☊ catch blocks enclosing the current point
+    catch_depth: usize,
+    /// Warnings gathered by the last compilation
+    warnings: Vec<CompileWarning>,
+    /// Static type of the expression compiled last, when it is known
+    expr_type: Option<ValueType>,
+    /// ⌻ struct and ⎊ enum names to their index in the chunk's type table
+    type_indices: std::collections::HashMap<String, u16>,
+    /// ⍟ trait names to their method signatures
+    traits: std::collections::HashMap<String, Vec<MethodSig>>,
+    /// Trait method names to the trait declaring them and their arity
+    trait_methods: std::collections::HashMap<String, (String, usize)>,
+    /// ⍜ implementations compiled so far, as (trait, type)
+    impls: Vec<(String, FieldType)>,
+    /// Name of the type whose ⍜ methods are being compiled
+    method_owner: Option<String>,
 }
 
 impl<'a> Compiler<'a> {
@@ -164,13 +346,34 @@ impl<'a> Compiler<'a> {
             loops: Vec::new(),
             had_end_program: false,
             function_stack: Vec::new(),
+            last_call: None,
             function_indices: std::collections::HashMap::new(),
+            declared_functions: std::collections::HashMap::new(),
+            bound_names: std::collections::HashSet::new(),
+            global_names: std::collections::HashSet::new(),
             source_map: None,
             source: String::new(),
             closure_captures: std::collections::HashMap::new(),
+            spell_depth: 0,
+            stack_temps: 0,
+            try_depth: 0,
+            finally_depth: 0,
+            catch_depth: 0,
+            warnings: Vec::new(),
+            expr_type: None,
+            type_indices: std::collections::HashMap::new(),
+            traits: std::collections::HashMap::new(),
+            trait_methods: std::collections::HashMap::new(),
+            impls: Vec::new(),
+            method_owner: None,
         }
     }
 
+    /// Warnings gathered by the last compilation
+    pub fn warnings(&self) -> &[CompileWarning] {
+        &self.warnings
+    }
+
     /// Compile source code into bytecode
     pub fn compile(&mut self, source: &str) -> CompileResult<Chunk> {
         // Create source map for error reporting
@@ -184,8 +387,26 @@ impl<'a> Compiler<'a> {
         self.loops.clear();
         self.had_end_program = false;
         self.function_stack.clear();
+        self.last_call = None;
         self.function_indices.clear();
+        self.declared_functions.clear();
+        self.bound_names.clear();
+        self.global_names.clear();
         self.closure_captures.clear();
+        self.type_indices.clear();
+        self.traits.clear();
+        self.trait_methods.clear();
+        self.impls.clear();
+        self.method_owner = None;
+        self.spell_depth = 0;
+        self.stack_temps = 0;
+        self.try_depth = 0;
+        self.finally_depth = 0;
+        self.catch_depth = 0;
+        self.warnings.clear();
+
+        // Declare top-level functions so they can be called from anywhere
+        self.declare_functions()?;
 
         // Parse and compile statements
         while !self.is_at_end() {
@@ -200,6 +421,66 @@ impl<'a> Compiler<'a> {
         Ok(std::mem::take(&mut self.chunk))
     }
 
+    // ═══════════════════════════════════════════════════════════════
+    // DECLARATION PRE-PASS
+    // ═══════════════════════════════════════════════════════════════
+
+    /// Reserve a function index, with its parameters, for every λ defined
+    /// outside any function body or ⍜ block, and note which identifiers are
+    /// used other than as a call target
+    fn declare_functions(&mut self) -> CompileResult<()> {
+        let mut depth = 0usize;
+        let mut in_impl = false;
+
+        for position in 0..self.tokens.len() {
+            match &self.tokens[position].kind {
+                TokenKind::Symbol(SymbolMeaning::FunctionStart) => {
+                    if depth == 0 && !in_impl {
+                        self.declare_function(position)?;
+                    }
+                    depth += 1;
+                }
+                TokenKind::Symbol(SymbolMeaning::FunctionEnd) => depth = depth.saturating_sub(1),
+                TokenKind::Symbol(SymbolMeaning::TraitImpl) if depth == 0 => in_impl = true,
+                TokenKind::Symbol(SymbolMeaning::MatchEnd) if depth == 0 => in_impl = false,
+                TokenKind::Identifier(name) => {
+                    let call_target = position > 0
+                        && matches!(self.tokens[position - 1].kind, TokenKind::Symbol(SymbolMeaning::Call));
+                    if !call_target {
+                        self.bound_names.insert(name.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.current = 0;
+        Ok(())
+    }
+
+    /// Declare the named λ at a token position; anonymous ones are skipped
+    fn declare_function(&mut self, position: usize) -> CompileResult<()> {
+        let name = match self.tokens.get(position + 1).map(|token| &token.kind) {
+            Some(TokenKind::Identifier(name)) => name.clone(),
+            _ => return Ok(()),
+        };
+        if self.function_indices.contains_key(&name) {
+            return Err(CompileError::DuplicateFunction { name });
+        }
+
+        self.current = position + 2;
+        self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+        let (params, defaults, variadic) = self.parameter_list()?;
+
+        let mut func_info = FunctionInfo::new(name.clone(), params.len() as u8, params, 0, 0);
+        func_info.defaults = defaults;
+        func_info.variadic = variadic;
+        let func_idx = self.chunk.add_function(func_info);
+        self.function_indices.insert(name, func_idx);
+        self.declared_functions.insert(position, func_idx);
+        Ok(())
+    }
+
     // ═══════════════════════════════════════════════════════════════
     // STATEMENT COMPILATION
     // ═══════════════════════════════════════════════════════════════
@@ -262,10 +543,16 @@ impl<'a> Compiler<'a> {
                 self.accumulator_statement()?;
             }
 
-            // End program (❧)
+            // End program (❧), optionally with an exit status: ❧[2]
             TokenKind::Symbol(SymbolMeaning::EndProgram) => {
                 self.advance();
-                self.emit_op(OpCode::Halt);
+                if self.match_symbol(SymbolMeaning::LeftBracket) {
+                    self.expression()?;
+                    self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+                    self.emit_op(OpCode::Exit);
+                } else {
+                    self.emit_op(OpCode::Halt);
+                }
                 self.had_end_program = true;
             }
 
@@ -289,6 +576,40 @@ impl<'a> Compiler<'a> {
                 self.map_declaration()?;
             }
 
+            // Yield (⌭[value]) or iterator variable (⌭it=...)
+            TokenKind::Symbol(SymbolMeaning::TypeIterator) if self.check_next_symbol(SymbolMeaning::LeftBracket) => {
+                self.yield_statement()?;
+            }
+            TokenKind::Symbol(SymbolMeaning::TypeIterator) => {
+                self.variable_declaration()?;
+            }
+
+            // Struct or enum variable (⌻p=..., ⎊s=...)
+            TokenKind::Symbol(SymbolMeaning::TypeStruct | SymbolMeaning::TypeEnum)
+                if self.tokens.get(self.current + 2).is_some_and(|t| t.kind == TokenKind::Equals) => {
+                self.variable_declaration()?;
+            }
+
+            // Struct definition (⌻)
+            TokenKind::Symbol(SymbolMeaning::TypeStruct) => {
+                self.struct_definition()?;
+            }
+
+            // Enum definition (⎊)
+            TokenKind::Symbol(SymbolMeaning::TypeEnum) => {
+                self.enum_definition()?;
+            }
+
+            // Trait definition (⍟)
+            TokenKind::Symbol(SymbolMeaning::TraitDefine) => {
+                self.trait_definition()?;
+            }
+
+            // Trait implementation (⍜)
+            TokenKind::Symbol(SymbolMeaning::TraitImpl) => {
+                self.trait_implementation()?;
+            }
+
             // Match expression (⟡) - v0.3.0
             TokenKind::Symbol(SymbolMeaning::MatchStart) => {
                 self.match_expression()?;
@@ -314,9 +635,39 @@ impl<'a> Compiler<'a> {
                 self.push_statement()?;
             }
 
-            // Pop from stack (⇓)
+            // Pop from stack (⇓) - the value is discarded
             TokenKind::Symbol(SymbolMeaning::Pop) => {
                 self.advance();
+                self.spell_stack_effect("⇓", 1, 0)?;
+                self.emit_op(OpCode::SpellPop);
+                self.emit_op(OpCode::Pop);
+            }
+
+            // Duplicate top of stack (⇕)
+            TokenKind::Symbol(SymbolMeaning::Dup) => {
+                self.advance();
+                self.spell_stack_effect("⇕", 1, 2)?;
+                self.emit_op(OpCode::SpellDup);
+            }
+
+            // Swap top two values (⇆)
+            TokenKind::Symbol(SymbolMeaning::Swap) => {
+                self.advance();
+                self.spell_stack_effect("⇆", 2, 2)?;
+                self.emit_op(OpCode::SpellSwap);
+            }
+
+            // Rotate top three values (↻)
+            TokenKind::Symbol(SymbolMeaning::Rotate) => {
+                self.advance();
+                self.spell_stack_effect("↻", 3, 3)?;
+                self.emit_op(OpCode::SpellRot);
+            }
+
+            // Call (⤷) for its effect - the result is discarded
+            TokenKind::Symbol(SymbolMeaning::Call) => {
+                self.advance();
+                self.function_call()?;
                 self.emit_op(OpCode::Pop);
             }
 
@@ -325,7 +676,20 @@ impl<'a> Compiler<'a> {
                 // Check if followed by assignment operator
                 if self.check_next(TokenKind::Equals) {
                     self.simple_assignment()?;
+                } else if self.check_next_symbol(SymbolMeaning::Index) {
+                    self.index_assignment()?;
+                } else if self.check_next_symbol(SymbolMeaning::Append) {
+                    self.append_statement()?;
+                } else if self.check_next_symbol(SymbolMeaning::ModuleAccess)
+                    && self.tokens.get(self.current + 3)
+                        .is_some_and(|t| t.kind == TokenKind::Symbol(SymbolMeaning::LeftBracket)) {
+                    // Method call for its effect - the result is discarded
+                    self.expression()?;
+                    self.emit_op(OpCode::Pop);
+                } else if self.check_next_symbol(SymbolMeaning::ModuleAccess) {
+                    self.field_assignment()?;
                 } else {
+                    #[allow(clippy::useless_format)]
                     return Err(self.error(&format!("Unexpected identifier")));
                 }
             }
@@ -415,7 +779,13 @@ impl<'a> Compiler<'a> {
             ) {
                 if *meaning == SymbolMeaning::Accumulator {
                     self.advance();
-                    self.emit_op(OpCode::StoreAcc);
+                    match self.accumulator_depth()? {
+                        0 => self.emit_op(OpCode::StoreAcc),
+                        depth => {
+                            self.emit_op(OpCode::StoreOuterAcc);
+                            self.emit_byte(depth);
+                        }
+                    }
                     return Ok(());
                 }
                 self.advance(); // skip type symbol
@@ -427,10 +797,7 @@ impl<'a> Compiler<'a> {
             TokenKind::Identifier(s) => s.clone(),
             _ => unreachable!(),
         };
-        let name_idx = self.chunk.add_string(&name);
-
-        self.emit_op(OpCode::StoreVar);
-        self.emit_u16(name_idx);
+        self.emit_variable_store(&name);
 
         Ok(())
     }
@@ -442,13 +809,76 @@ impl<'a> Compiler<'a> {
             TokenKind::Identifier(s) => s.clone(),
             _ => unreachable!(),
         };
-        let name_idx = self.chunk.add_string(&name);
 
         self.consume(TokenKind::Equals, "=")?;
         self.expression()?;
+        self.emit_variable_store(&name);
 
-        self.emit_op(OpCode::StoreVar);
-        self.emit_u16(name_idx);
+        Ok(())
+    }
+
+    /// Element assignment in place: xs⌷0=value or m⌷"key"=value
+    fn index_assignment(&mut self) -> CompileResult<()> {
+        let name_token = self.advance();
+        let name = match &name_token.kind {
+            TokenKind::Identifier(s) => s.clone(),
+            _ => unreachable!(),
+        };
+
+        self.advance(); // consume ⌷
+        self.primary()?;
+        if self.check_symbol(SymbolMeaning::Index) {
+            return Err(self.error("= (only one level of ⌷ can be assigned)"));
+        }
+
+        self.consume(TokenKind::Equals, "=")?;
+        self.expression()?;
+
+        self.emit_variable_update(OpCode::SetIndexVar, &name);
+
+        Ok(())
+    }
+
+    /// Field assignment in place: p⊷x=value
+    fn field_assignment(&mut self) -> CompileResult<()> {
+        let name_token = self.advance();
+        let name = match &name_token.kind {
+            TokenKind::Identifier(s) => s.clone(),
+            _ => unreachable!(),
+        };
+
+        self.advance(); // consume ⊷
+        let field_token = self.consume_identifier("field name")?;
+        let field = match &field_token.kind {
+            TokenKind::Identifier(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        let field_idx = self.chunk.add_string(&field);
+        if self.check_symbol(SymbolMeaning::ModuleAccess) {
+            return Err(self.error("= (only one level of ⊷ can be assigned)"));
+        }
+
+        self.consume(TokenKind::Equals, "=")?;
+        self.expression()?;
+
+        self.emit_variable_update(OpCode::SetFieldVar, &name);
+        self.emit_u16(field_idx);
+
+        Ok(())
+    }
+
+    /// Append in place: xs⊞value
+    fn append_statement(&mut self) -> CompileResult<()> {
+        let name_token = self.advance();
+        let name = match &name_token.kind {
+            TokenKind::Identifier(s) => s.clone(),
+            _ => unreachable!(),
+        };
+
+        self.advance(); // consume ⊞
+        self.expression()?;
+
+        self.emit_variable_update(OpCode::AppendVar, &name);
 
         Ok(())
     }
@@ -474,18 +904,86 @@ impl<'a> Compiler<'a> {
         let has_bracket = self.match_symbol(SymbolMeaning::LeftBracket);
 
         let text = self.consume_string("string literal")?;
-        let idx = self.chunk.add_string(&text);
+        let line = self.previous().location.line;
+        let segments = interpolate::parse_template(&text)
+            .map_err(|error| CompileError::TemplateError { line, error })?;
+
+        if segments.iter().any(|s| matches!(s, Segment::Hole { .. })) {
+            self.interpolation(segments, line)?;
+            self.emit_op(OpCode::Print);
+        } else {
+            // Plain text, with any {{ }} escapes resolved
+            let text: String = segments.into_iter()
+                .map(|s| match s {
+                    Segment::Text(t) => t,
+                    Segment::Hole { .. } => unreachable!(),
+                })
+                .collect();
+            let idx = self.chunk.add_string(&text);
+            self.emit_op(OpCode::PrintLit);
+            self.emit_u16(idx);
+        }
 
         if has_bracket {
             self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
         }
 
-        self.emit_op(OpCode::PrintLit);
-        self.emit_u16(idx);
+        Ok(())
+    }
+
+    /// Compile a template's holes and emit `Interpolate` with its positional form
+    fn interpolation(&mut self, segments: Vec<Segment>, line: usize) -> CompileResult<()> {
+        let mut template = String::new();
+        let mut count: u8 = 0;
+
+        for segment in segments {
+            match segment {
+                Segment::Text(text) => template.push_str(&interpolate::escape_text(&text)),
+                Segment::Hole { source, spec } => {
+                    if count == u8::MAX {
+                        return Err(self.error("at most 255 holes in one template"));
+                    }
+                    self.hole_expression(&source, line)?;
+                    match spec {
+                        Some(spec) => template.push_str(&format!("{{{}:{}}}", count, spec)),
+                        None => template.push_str(&format!("{{{}}}", count)),
+                    }
+                    count += 1;
+                }
+            }
+        }
 
+        let idx = self.chunk.add_string(&template);
+        self.emit_op(OpCode::Interpolate);
+        self.emit_u16(idx);
+        self.emit_byte(count);
         Ok(())
     }
 
+    /// Compile the source of one template hole as an expression
+    fn hole_expression(&mut self, source: &str, line: usize) -> CompileResult<()> {
+        let mut tokens = Lexer::new(source, self.symbol_table).tokenize()?;
+        for token in &mut tokens {
+            token.location.line += line - 1;
+        }
+
+        // Compile the hole as if its tokens were the whole spell
+        let outer_tokens = std::mem::replace(&mut self.tokens, tokens);
+        let outer_current = std::mem::replace(&mut self.current, 0);
+
+        let result = self.expression().and_then(|()| {
+            if self.is_at_end() {
+                Ok(())
+            } else {
+                Err(self.error("end of template hole"))
+            }
+        });
+
+        self.tokens = outer_tokens;
+        self.current = outer_current;
+        result
+    }
+
     /// Input: ⚓⟁var
     fn input(&mut self) -> CompileResult<()> {
         self.advance(); // consume ⚓
@@ -511,6 +1009,14 @@ impl<'a> Compiler<'a> {
     fn loop_statement(&mut self) -> CompileResult<()> {
         self.advance(); // consume ⊂
 
+        let label = self.loop_label()?;
+        if self.is_for_each_header() {
+            return self.for_each_statement(label);
+        }
+
+        // The loop gets its own ✹, starting from the enclosing one's value
+        self.emit_op(OpCode::EnterLoop);
+
         let loop_start = self.chunk.current_offset();
 
         // Condition
@@ -523,10 +1029,139 @@ impl<'a> Compiler<'a> {
         let exit_jump = self.chunk.current_offset();
         self.emit_u16(0xFFFF); // Placeholder
 
-        // Loop body
+        let loop_info = self.loop_body(loop_start, label)?;
+
+        // Patch exit jump
+        self.chunk.patch_jump(exit_jump);
+
+        // Patch break jumps
+        for jump in loop_info.break_jumps {
+            self.chunk.patch_jump(jump);
+        }
+
+        // Normal exit and ⊗ both close the loop's accumulator frame
+        self.emit_op(OpCode::ExitLoop);
+
+        Ok(())
+    }
+
+    /// Optional loop label: ⊂name[...]
+    fn loop_label(&mut self) -> CompileResult<Option<String>> {
+        let label = match &self.peek().kind {
+            TokenKind::Identifier(name) => name.clone(),
+            _ => return Ok(None),
+        };
+        let line = self.advance().location.line;
+
+        if self.loops.iter().any(|l| l.label.as_deref() == Some(label.as_str())) {
+            return Err(CompileError::DuplicateLabel { label, line });
+        }
+        Ok(Some(label))
+    }
+
+    /// Whether a loop header is `[x ∈ ...]` or `[k ⇒ v ∈ ...]`
+    fn is_for_each_header(&self) -> bool {
+        let kind = |n: usize| self.tokens.get(self.current + n).map(|t| &t.kind);
+        let is_symbol = |n: usize, meaning: SymbolMeaning| kind(n) == Some(&TokenKind::Symbol(meaning));
+        let is_identifier = |n: usize| matches!(kind(n), Some(TokenKind::Identifier(_)));
+
+        is_symbol(0, SymbolMeaning::LeftBracket) && is_identifier(1) && (
+            is_symbol(2, SymbolMeaning::In) ||
+            (is_symbol(2, SymbolMeaning::MapArrow) && is_identifier(3) && is_symbol(4, SymbolMeaning::In))
+        )
+    }
+
+    /// For-each loop: ⊂[x ∈ xs] ... ⊃, ⊂[k ⇒ v ∈ m] ... ⊃ or ⊂[i ∈ 0‥n∆2] ... ⊃
+    ///
+    /// The iteration state stays on the stack below the body and the loop
+    /// variables live until the loop ends.
+    fn for_each_statement(&mut self, label: Option<String>) -> CompileResult<()> {
+        self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+
+        let first = self.consume_identifier("loop variable")?;
+        let (key, value) = if self.match_symbol(SymbolMeaning::MapArrow) {
+            let second = self.consume_identifier("loop variable")?;
+            (Some(first), second)
+        } else {
+            (None, first)
+        };
+        self.consume_symbol(SymbolMeaning::In, "∈")?;
+
+        // Source: a collection, or a range start‥end with an optional ∆step
+        self.expression()?;
+        let is_range = self.match_symbol(SymbolMeaning::Range);
+        if is_range {
+            if key.is_some() {
+                return Err(self.error("a single loop variable for a ‥ range"));
+            }
+            self.expression()?;
+            if self.match_symbol(SymbolMeaning::Step) {
+                self.expression()?;
+            } else {
+                self.emit_constant(Value::Integer(1));
+            }
+        }
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+
+        let (begin, state_slots) = if is_range {
+            (OpCode::RangeBegin, 3)
+        } else {
+            (OpCode::IterBegin, 2)
+        };
+        self.emit_op(begin);
+        self.emit_op(OpCode::EnterLoop);
+
+        self.stack_temps += state_slots;
+
+        let loop_start = self.chunk.current_offset();
+        if is_range {
+            self.emit_op(OpCode::RangeNext);
+        } else {
+            self.emit_op(OpCode::IterNext);
+            self.emit_byte(if key.is_some() { 2 } else { 1 });
+        }
+        let exit_jump = self.chunk.current_offset();
+        self.emit_u16(0xFFFF);
+
+        // The value is pushed last, so it is bound first
+        for variable in std::iter::once(&value).chain(key.as_ref()) {
+            let name = match &variable.kind {
+                TokenKind::Identifier(s) => s.clone(),
+                _ => unreachable!(),
+            };
+            self.register_local(&name);
+            let name_idx = self.chunk.add_string(&name);
+            self.emit_op(OpCode::BindLoopVar);
+            self.emit_u16(name_idx);
+        }
+
+        let loop_info = self.loop_body(loop_start, label)?;
+
+        self.chunk.patch_jump(exit_jump);
+        for jump in loop_info.break_jumps {
+            self.chunk.patch_jump(jump);
+        }
+        self.emit_op(OpCode::ExitLoop);
+
+        for _ in 0..state_slots {
+            self.emit_op(OpCode::Pop);
+        }
+        self.stack_temps -= state_slots;
+
+        Ok(())
+    }
+
+    /// Compile a loop body up to ⊃ and jump back to `loop_start`,
+    /// returning the loop's pending break jumps
+    fn loop_body(&mut self, loop_start: usize, label: Option<String>) -> CompileResult<LoopInfo> {
         self.loops.push(LoopInfo {
             start: loop_start,
             break_jumps: Vec::new(),
+            label,
+            spell_depth: self.spell_depth,
+            stack_temps: self.stack_temps,
+            try_depth: self.try_depth,
+            finally_depth: self.finally_depth,
         });
 
         while !self.check_symbol(SymbolMeaning::LoopEnd) && !self.is_at_end() {
@@ -535,21 +1170,16 @@ impl<'a> Compiler<'a> {
 
         self.consume_symbol(SymbolMeaning::LoopEnd, "⊃")?;
 
+        // Every iteration must leave the spell stack as it found it
+        let entry_depth = self.loops.last().unwrap().spell_depth;
+        self.expect_spell_depth("a ⊂ loop body", entry_depth)?;
+
         // Jump back to start
         self.emit_op(OpCode::Loop);
         let loop_offset = self.chunk.current_offset() - loop_start + 2;
         self.emit_u16(loop_offset as u16);
 
-        // Patch exit jump
-        self.chunk.patch_jump(exit_jump);
-
-        // Patch break jumps
-        let loop_info = self.loops.pop().unwrap();
-        for jump in loop_info.break_jumps {
-            self.chunk.patch_jump(jump);
-        }
-
-        Ok(())
+        Ok(self.loops.pop().unwrap())
     }
 
     /// Conditional: ⟨condition⟩...⟩else⟫
@@ -565,6 +1195,8 @@ impl<'a> Compiler<'a> {
         let then_jump = self.chunk.current_offset();
         self.emit_u16(0xFFFF);
 
+        let entry_depth = self.spell_depth;
+
         // Then branch
         while !self.check_symbol(SymbolMeaning::Else) &&
               !self.check_symbol(SymbolMeaning::IfEnd) &&
@@ -580,12 +1212,16 @@ impl<'a> Compiler<'a> {
 
             self.chunk.patch_jump(then_jump);
 
+            // Both branches must leave the spell stack at the same depth
+            let then_depth = std::mem::replace(&mut self.spell_depth, entry_depth);
             while !self.check_symbol(SymbolMeaning::IfEnd) && !self.is_at_end() {
                 self.statement()?;
             }
+            self.expect_spell_depth("the ⟩ branch", then_depth)?;
 
             self.chunk.patch_jump(else_jump);
         } else {
+            self.expect_spell_depth("a ⟨ branch without ⟩", entry_depth)?;
             self.chunk.patch_jump(then_jump);
         }
 
@@ -594,32 +1230,30 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
-    /// Break statement
+    /// Break statement: ⊗ or ⊗[label]
     fn break_statement(&mut self) -> CompileResult<()> {
         self.advance();
 
-        if self.loops.is_empty() {
-            return Err(self.error("⊗ (break) used outside of loop"));
-        }
+        let target = self.loop_target("⊗ (break)")?;
+        self.leave_loops(target, "⊗")?;
 
         self.emit_op(OpCode::Jump);
         let jump = self.chunk.current_offset();
         self.emit_u16(0xFFFF);
 
-        self.loops.last_mut().unwrap().break_jumps.push(jump);
+        self.loops[target].break_jumps.push(jump);
 
         Ok(())
     }
 
-    /// Continue statement
+    /// Continue statement: ↺ or ↺[label]
     fn continue_statement(&mut self) -> CompileResult<()> {
         self.advance();
 
-        if self.loops.is_empty() {
-            return Err(self.error("↺ (continue) used outside of loop"));
-        }
+        let target = self.loop_target("↺ (continue)")?;
+        self.leave_loops(target, "↺")?;
 
-        let loop_start = self.loops.last().unwrap().start;
+        let loop_start = self.loops[target].start;
         let offset = self.chunk.current_offset() - loop_start + 3;
 
         self.emit_op(OpCode::Loop);
@@ -628,10 +1262,90 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
-    /// Accumulator statement: ✹=value or ✹ (increment)
-    fn accumulator_statement(&mut self) -> CompileResult<()> {
+    /// Resolve the loop a ⊗ or ↺ refers to: the innermost one, or the one
+    /// named by a following [label]
+    fn loop_target(&mut self, what: &str) -> CompileResult<usize> {
+        if self.loops.is_empty() {
+            return Err(self.error(&format!("{} used outside of loop", what)));
+        }
+        if !self.match_symbol(SymbolMeaning::LeftBracket) {
+            return Ok(self.loops.len() - 1);
+        }
+
+        let token = self.consume_identifier("loop label")?;
+        let label = match &token.kind {
+            TokenKind::Identifier(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+
+        self.loops.iter()
+            .rposition(|l| l.label.as_deref() == Some(label.as_str()))
+            .ok_or(CompileError::UnknownLabel { label, line: token.location.line })
+    }
+
+    /// Emit the cleanup for jumping from the current point into the body of
+    /// loop `target`: close the try regions and inner loops in between and
+    /// drop the operand stack values they hold
+    fn leave_loops(&mut self, target: usize, glyph: &str) -> CompileResult<()> {
+        let (spell_depth, stack_temps, finally_depth) = {
+            let info = &self.loops[target];
+            (info.spell_depth, info.stack_temps, info.finally_depth)
+        };
+        self.expect_spell_depth(glyph, spell_depth)?;
+        if finally_depth < self.finally_depth {
+            return Err(CompileError::LeavesFinally {
+                glyph: glyph.to_string(),
+                line: self.previous().location.line,
+            });
+        }
+
+        // Innermost first, so each finally block runs inside the loops
+        // that enclose its try region
+        let mut open_tries = self.try_depth;
+        for inner in (target + 1..self.loops.len()).rev() {
+            let try_depth = self.loops[inner].try_depth;
+            for _ in try_depth..open_tries {
+                self.emit_op(OpCode::TryEnd);
+            }
+            self.emit_op(OpCode::ExitLoop);
+            open_tries = try_depth;
+        }
+        for _ in self.loops[target].try_depth..open_tries {
+            self.emit_op(OpCode::TryEnd);
+        }
+        for _ in stack_temps..self.stack_temps {
+            self.emit_op(OpCode::Pop);
+        }
+        Ok(())
+    }
+
+    /// Accumulator statement: ✹=value or ✹ (increment), optionally of an
+    /// enclosing loop: ✹[1]=value, ✹[2]⊕
+    fn accumulator_statement(&mut self) -> CompileResult<()> {
         self.advance(); // consume ✹
 
+        let depth = self.accumulator_depth()?;
+        if depth > 0 {
+            if self.match_token(TokenKind::Equals) {
+                self.expression()?;
+            } else {
+                let step = if self.match_symbol(SymbolMeaning::Decrement) {
+                    OpCode::Sub
+                } else {
+                    self.match_symbol(SymbolMeaning::Increment);
+                    OpCode::Add
+                };
+                self.emit_op(OpCode::LoadOuterAcc);
+                self.emit_byte(depth);
+                self.emit_constant(Value::Integer(1));
+                self.emit_op(step);
+            }
+            self.emit_op(OpCode::StoreOuterAcc);
+            self.emit_byte(depth);
+            return Ok(());
+        }
+
         if self.match_token(TokenKind::Equals) {
             self.expression()?;
             self.emit_op(OpCode::StoreAcc);
@@ -647,49 +1361,66 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    /// Optional loop depth after ✹: `[n]` reaches the accumulator n loops out
+    fn accumulator_depth(&mut self) -> CompileResult<u8> {
+        if !self.match_symbol(SymbolMeaning::LeftBracket) {
+            return Ok(0);
+        }
+
+        let token = self.advance();
+        let depth = match token.kind {
+            TokenKind::Integer(n) if (0..=u8::MAX as i64).contains(&n) => n as u8,
+            _ => return Err(self.error("loop depth (0-255) after ✹[")),
+        };
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+
+        // Function bodies may run inside their caller's loops, so only
+        // top-level code can be checked here
+        if self.function_stack.is_empty() && depth as usize > self.loops.len() {
+            return Err(CompileError::AccumulatorOutOfReach {
+                depth: depth as usize,
+                loops: self.loops.len(),
+                line: token.location.line,
+            });
+        }
+        Ok(depth)
+    }
+
     /// Function definition: λname[params] ... Λ
-    fn function_definition(&mut self) -> CompileResult<()> {
+    fn function_definition(&mut self) -> CompileResult<u16> {
+        let position = self.current;
         self.advance(); // consume λ
 
-        // Get function name
+        // Get function name; methods are named after their type (Shape⊷area)
         let name_token = self.consume_identifier("function name")?;
-        let name = match &name_token.kind {
-            TokenKind::Identifier(s) => s.clone(),
+        let name = match (&name_token.kind, &self.method_owner) {
+            (TokenKind::Identifier(s), None) => s.clone(),
+            (TokenKind::Identifier(s), Some(owner)) => format!("{}⊷{}", owner, s),
             _ => unreachable!(),
         };
 
+        // Top-level functions already have an index from the pre-pass
+        if let Some(func_idx) = self.declared_functions.remove(&position) {
+            return self.function_body(name, Some(func_idx));
+        }
+
         // Check for duplicate
         if self.function_indices.contains_key(&name) {
             return Err(CompileError::DuplicateFunction { name });
         }
 
+        let func_idx = self.function_body(name.clone(), None)?;
+        self.function_indices.insert(name, func_idx);
+        Ok(func_idx)
+    }
+
+    /// Parameters and body of a function, up to Λ, compiled in place behind a
+    /// jump; fills the reserved index if there is one and returns the
+    /// function's index
+    fn function_body(&mut self, name: String, reserved: Option<u16>) -> CompileResult<u16> {
         // Parse parameters: [⟁a, ⌘b, ...]
         self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
-
-        let mut params: Vec<(String, ValueType)> = Vec::new();
-
-        if !self.check_symbol(SymbolMeaning::RightBracket) {
-            loop {
-                // Parse type
-                let type_token = self.advance();
-                let param_type = self.token_to_value_type(&type_token)?;
-
-                // Parse name
-                let param_name_token = self.consume_identifier("parameter name")?;
-                let param_name = match &param_name_token.kind {
-                    TokenKind::Identifier(s) => s.clone(),
-                    _ => unreachable!(),
-                };
-
-                params.push((param_name, param_type));
-
-                if !self.match_symbol(SymbolMeaning::Separator) {
-                    break;
-                }
-            }
-        }
-
-        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+        let (params, defaults, variadic) = self.parameter_list()?;
 
         let arity = params.len() as u8;
 
@@ -708,12 +1439,28 @@ impl<'a> Compiler<'a> {
             func_start,
         ));
 
+        // A function body starts with an empty spell stack of its own, and
+        // ⊗/↺ can't reach the loops around the definition
+        let outer_depth = std::mem::replace(&mut self.spell_depth, 0);
+        let outer_loops = std::mem::take(&mut self.loops);
+        let outer_temps = std::mem::replace(&mut self.stack_temps, 0);
+        let outer_tries = std::mem::replace(&mut self.try_depth, 0);
+        let outer_finallies = std::mem::replace(&mut self.finally_depth, 0);
+        let outer_catches = std::mem::replace(&mut self.catch_depth, 0);
+
         // Compile function body
         while !self.check_symbol(SymbolMeaning::FunctionEnd) && !self.is_at_end() {
             self.statement()?;
         }
 
         self.consume_symbol(SymbolMeaning::FunctionEnd, "Λ")?;
+        self.expect_spell_depth(&format!("function '{}'", name), 0)?;
+        self.spell_depth = outer_depth;
+        self.loops = outer_loops;
+        self.stack_temps = outer_temps;
+        self.try_depth = outer_tries;
+        self.finally_depth = outer_finallies;
+        self.catch_depth = outer_catches;
 
         // Implicit return null
         self.emit_op(OpCode::Null);
@@ -727,13 +1474,22 @@ impl<'a> Compiler<'a> {
         // Get closure info before popping
         let func_scope = self.function_stack.pop().unwrap();
         let is_closure = func_scope.is_closure;
+        let is_generator = func_scope.is_generator;
         let captures = func_scope.captures.clone();
 
         // Register function with capture info
         let mut func_info = FunctionInfo::new(name.clone(), arity, params, func_start, func_length);
         func_info.capture_names = captures.clone();
-        let func_idx = self.chunk.add_function(func_info);
-        self.function_indices.insert(name.clone(), func_idx);
+        func_info.defaults = defaults;
+        func_info.variadic = variadic;
+        func_info.generator = is_generator;
+        let func_idx = match reserved {
+            Some(func_idx) => {
+                self.chunk.functions[func_idx as usize] = func_info;
+                func_idx
+            }
+            None => self.chunk.add_function(func_info),
+        };
 
         // Store capture info for when function is loaded as value
         if is_closure {
@@ -741,7 +1497,98 @@ impl<'a> Compiler<'a> {
             self.closure_captures.insert(func_idx, captures);
         }
 
-        Ok(())
+        Ok(func_idx)
+    }
+
+    /// Parameters up to the closing ], once the [ is consumed
+    fn parameter_list(&mut self) -> CompileResult<Parameters> {
+        let mut params: Vec<(String, ValueType)> = Vec::new();
+        let mut defaults = Vec::new();
+        let mut variadic = false;
+
+        if !self.check_symbol(SymbolMeaning::RightBracket) {
+            loop {
+                // A variadic parameter (‥⌬rest) comes last
+                if variadic {
+                    let param = params.last().map(|(name, _)| name.clone()).unwrap_or_default();
+                    return Err(CompileError::MisplacedVariadic { param, line: self.peek().location.line });
+                }
+                let collects = self.match_symbol(SymbolMeaning::Range);
+
+                // Parse type
+                let type_token = self.advance();
+                let param_type = self.token_to_value_type(&type_token)?;
+
+                // Parse name
+                let param_name_token = self.consume_identifier("parameter name")?;
+                let param_name = match &param_name_token.kind {
+                    TokenKind::Identifier(s) => s.clone(),
+                    _ => unreachable!(),
+                };
+                let line = param_name_token.location.line;
+
+                if collects {
+                    if param_type != ValueType::Array {
+                        return Err(CompileError::MisplacedVariadic { param: param_name, line });
+                    }
+                    variadic = true;
+                } else if self.match_token(TokenKind::Equals) {
+                    defaults.push(self.default_value()?);
+                } else if !defaults.is_empty() {
+                    return Err(CompileError::RequiredAfterDefault { param: param_name, line });
+                }
+
+                params.push((param_name, param_type));
+
+                if !self.match_symbol(SymbolMeaning::Separator) {
+                    break;
+                }
+            }
+        }
+
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+        Ok((params, defaults, variadic))
+    }
+
+    /// Turn the call emitted last into a tail call when nothing follows it,
+    /// so its result is the value being returned: ⤶[⤷f[n]]
+    fn make_tail_call(&mut self) {
+        let Some((offset, op)) = self.last_call.take() else {
+            return;
+        };
+        if offset + 1 + op.operand_count() != self.chunk.current_offset() {
+            return;
+        }
+        let tail = match op {
+            OpCode::Call => OpCode::TailCall,
+            _ => OpCode::TailCallClosure,
+        };
+        self.chunk.code[offset] = tail as u8;
+    }
+
+    /// Default value of a parameter: a literal, numbers negated with ☠︎
+    fn default_value(&mut self) -> CompileResult<Value> {
+        let negate = self.match_symbol(SymbolMeaning::Subtract);
+        let token = self.advance();
+        match (&token.kind, negate) {
+            (TokenKind::Integer(i), _) => Ok(Value::Integer(if negate { -*i } else { *i })),
+            (TokenKind::Float(f), _) => Ok(Value::Real(if negate { -*f } else { *f })),
+            (TokenKind::String(s), false) => Ok(Value::String(s.clone())),
+            (TokenKind::Boolean(b), false) => Ok(Value::Boolean(*b)),
+            (TokenKind::Symbol(SymbolMeaning::True), false) => Ok(Value::Boolean(true)),
+            (TokenKind::Symbol(SymbolMeaning::False), false) => Ok(Value::Boolean(false)),
+            (TokenKind::Symbol(SymbolMeaning::Null), false) => Ok(Value::Null),
+            _ => {
+                let context = self.get_source_context(token.location.line);
+                Err(CompileError::UnexpectedToken {
+                    lexeme: token.lexeme.clone(),
+                    line: token.location.line,
+                    column: token.location.column,
+                    expected: "literal default value".to_string(),
+                    context,
+                })
+            }
+        }
     }
 
     /// Return statement: ⤶[expr] or ⤶
@@ -756,15 +1603,54 @@ impl<'a> Compiler<'a> {
         if self.match_symbol(SymbolMeaning::LeftBracket) {
             self.expression()?;
             self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+            // Outside ☄ and ⊂ the frame has nothing left to do once the
+            // call returns, so the callee can take it over; a loop's ✹
+            // must stay in place for it
+            if self.try_depth == 0 && self.loops.is_empty() {
+                self.make_tail_call();
+            }
         } else {
             self.emit_op(OpCode::Null);
         }
+        self.expect_spell_depth("⤶", 0)?;
+        if self.finally_depth > 0 {
+            return Err(CompileError::LeavesFinally {
+                glyph: "⤶".to_string(),
+                line: self.previous().location.line,
+            });
+        }
 
         self.emit_op(OpCode::Return);
 
         Ok(())
     }
 
+    /// Yield: ⌭[expr] hands a value to whoever resumed the generator and
+    /// suspends it there; any ⌭ makes the enclosing λ a generator
+    fn yield_statement(&mut self) -> CompileResult<()> {
+        let line = self.advance().location.line; // consume ⌭
+
+        if self.function_stack.is_empty() {
+            return Err(CompileError::YieldOutsideFunction { line });
+        }
+        // A suspended frame cannot keep its handlers, and a ☋ block always
+        // runs to its end
+        if self.try_depth > 0 || self.finally_depth > 0 {
+            return Err(CompileError::YieldInTry { line });
+        }
+
+        self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+        self.expression()?;
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+        self.expect_spell_depth("⌭", 0)?;
+
+        self.emit_op(OpCode::Yield);
+        if let Some(scope) = self.function_stack.last_mut() {
+            scope.is_generator = true;
+        }
+        Ok(())
+    }
+
     /// Array declaration: ⌬name=[elements]
     fn array_declaration(&mut self) -> CompileResult<()> {
         self.advance(); // consume ⌬
@@ -776,8 +1662,19 @@ impl<'a> Compiler<'a> {
             _ => unreachable!(),
         };
         let name_idx = self.chunk.add_string(&name);
+        self.register_local(&name);
 
         self.consume(TokenKind::Equals, "=")?;
+
+        // Any non-literal initializer is an expression producing the array
+        if !self.check_symbol(SymbolMeaning::LeftBracket) {
+            self.expression()?;
+            self.emit_op(OpCode::DeclareVar);
+            self.emit_u16(name_idx);
+            self.emit_byte(ValueType::Array as u8);
+            return Ok(());
+        }
+
         self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
 
         // Parse elements
@@ -822,8 +1719,19 @@ impl<'a> Compiler<'a> {
             _ => unreachable!(),
         };
         let name_idx = self.chunk.add_string(&name);
+        self.register_local(&name);
 
         self.consume(TokenKind::Equals, "=")?;
+
+        // Any non-literal initializer is an expression producing the map
+        if !self.check_symbol(SymbolMeaning::LeftBrace) {
+            self.expression()?;
+            self.emit_op(OpCode::DeclareVar);
+            self.emit_u16(name_idx);
+            self.emit_byte(ValueType::Map as u8);
+            return Ok(());
+        }
+
         self.consume_symbol(SymbolMeaning::LeftBrace, "{")?;
 
         // Parse key-value pairs
@@ -867,161 +1775,978 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
-    /// Match expression: ⟡expr] ⟢pattern] ... ⟢pattern] ... ⟣
-    fn match_expression(&mut self) -> CompileResult<()> {
-        self.advance(); // consume ⟡
+    /// Struct definition: ⌻Point[⟁x, ⟁y]
+    fn struct_definition(&mut self) -> CompileResult<()> {
+        self.advance(); // consume ⌻
+        let (name, type_idx) = self.declare_type()?;
 
-        // Evaluate expression to match
-        self.expression()?;
-        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+        self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+        let fields = self.field_list(&name)?;
 
-        // Track jump patches for each arm
-        let mut end_jumps: Vec<usize> = Vec::new();
+        let added = self.chunk.add_type(TypeDef { name, kind: TypeKind::Struct(fields) });
+        debug_assert_eq!(added, type_idx);
+        Ok(())
+    }
 
-        // Parse match arms
-        while self.check_symbol(SymbolMeaning::MatchArm) {
-            self.advance(); // consume ⟢
+    /// Enum definition: ⎊Shape[Circle[⧆r], Rect[⧆w, ⧆h], Empty]
+    fn enum_definition(&mut self) -> CompileResult<()> {
+        self.advance(); // consume ⎊
+        let (name, type_idx) = self.declare_type()?;
 
-            // Duplicate the match value for comparison
-            self.emit_op(OpCode::Dup);
+        self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+        let mut variants: Vec<VariantDef> = Vec::new();
+        while !self.check_symbol(SymbolMeaning::RightBracket) {
+            let variant_token = self.consume_identifier("variant name")?;
+            let variant = match &variant_token.kind {
+                TokenKind::Identifier(s) => s.clone(),
+                _ => unreachable!(),
+            };
+            if variants.iter().any(|v| v.name == variant) {
+                return Err(CompileError::DuplicateField {
+                    type_name: name,
+                    field: variant,
+                    line: variant_token.location.line,
+                });
+            }
 
-            // Check for wildcard
-            if self.match_symbol(SymbolMeaning::Wildcard) {
-                // Wildcard matches anything - just pop the dup'd value
-                self.emit_op(OpCode::Pop);
+            // Optional payload
+            let fields = if self.match_symbol(SymbolMeaning::LeftBracket) {
+                self.field_list(&format!("{}⊷{}", name, variant))?
             } else {
-                // Pattern expression
-                self.expression()?;
+                Vec::new()
+            };
+            variants.push(VariantDef { name: variant, fields });
 
-                // Compare
-                self.emit_op(OpCode::Eq);
+            if !self.match_symbol(SymbolMeaning::Separator) {
+                break;
+            }
+        }
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
 
-                // Jump if no match
-                self.emit_op(OpCode::JumpIfFalse);
-                let no_match_jump = self.chunk.current_offset();
-                self.emit_u16(0xFFFF);
+        if variants.len() >= u8::MAX as usize {
+            return Err(self.error(&format!("at most {} variants", u8::MAX - 1)));
+        }
 
-                self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+        let added = self.chunk.add_type(TypeDef { name, kind: TypeKind::Enum(variants) });
+        debug_assert_eq!(added, type_idx);
+        Ok(())
+    }
 
-                // Arm body
-                while !self.check_symbol(SymbolMeaning::MatchArm) &&
-                      !self.check_symbol(SymbolMeaning::MatchEnd) &&
-                      !self.is_at_end() {
-                    self.statement()?;
-                }
+    /// Name a new user type, reserving its index so fields may refer to it
+    fn declare_type(&mut self) -> CompileResult<(String, u16)> {
+        let name_token = self.consume_identifier("type name")?;
+        let name = match &name_token.kind {
+            TokenKind::Identifier(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        if self.type_indices.contains_key(&name) {
+            return Err(CompileError::DuplicateType { name, line: name_token.location.line });
+        }
 
-                // Jump to end after arm executes
-                self.emit_op(OpCode::Jump);
-                end_jumps.push(self.chunk.current_offset());
-                self.emit_u16(0xFFFF);
+        let type_idx = self.chunk.types.len() as u16;
+        self.type_indices.insert(name.clone(), type_idx);
+        Ok((name, type_idx))
+    }
 
-                // Patch the no-match jump
-                self.chunk.patch_jump(no_match_jump);
+    /// Typed fields up to the closing bracket: ⟁x, ⌘name, Point origin
+    fn field_list(&mut self, owner: &str) -> CompileResult<Vec<Field>> {
+        let mut fields: Vec<Field> = Vec::new();
+        while !self.check_symbol(SymbolMeaning::RightBracket) {
+            let type_token = self.advance();
+            let ty = match &type_token.kind {
+                TokenKind::Identifier(type_name) => match self.type_indices.get(type_name) {
+                    Some(&index) => FieldType::User(index),
+                    None => return Err(CompileError::UnknownType {
+                        name: type_name.clone(),
+                        line: type_token.location.line,
+                    }),
+                },
+                _ => FieldType::Value(self.token_to_value_type(&type_token)?),
+            };
+
+            let field_token = self.consume_identifier("field name")?;
+            let field = match &field_token.kind {
+                TokenKind::Identifier(s) => s.clone(),
+                _ => unreachable!(),
+            };
+            if fields.iter().any(|f| f.name == field) {
+                return Err(CompileError::DuplicateField {
+                    type_name: owner.to_string(),
+                    field,
+                    line: field_token.location.line,
+                });
+            }
+            fields.push(Field { name: field, ty });
 
-                continue;
+            if !self.match_symbol(SymbolMeaning::Separator) {
+                break;
             }
+        }
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
 
-            self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+        if fields.len() > u8::MAX as usize {
+            return Err(self.error(&format!("at most {} fields", u8::MAX)));
+        }
+        Ok(fields)
+    }
 
-            // Arm body (for wildcard)
-            while !self.check_symbol(SymbolMeaning::MatchArm) &&
-                  !self.check_symbol(SymbolMeaning::MatchEnd) &&
-                  !self.is_at_end() {
-                self.statement()?;
-            }
+    /// Trait definition: ⍟Area[area[], scaled[⧆k]]
+    fn trait_definition(&mut self) -> CompileResult<()> {
+        let line = self.advance().location.line; // consume ⍟
+        if !self.function_stack.is_empty() {
+            return Err(CompileError::NestedTraitItem { glyph: "⍟".to_string(), line });
+        }
 
-            // Jump to end
-            self.emit_op(OpCode::Jump);
-            end_jumps.push(self.chunk.current_offset());
-            self.emit_u16(0xFFFF);
+        let name_token = self.consume_identifier("trait name")?;
+        let name = match &name_token.kind {
+            TokenKind::Identifier(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        if self.traits.contains_key(&name) {
+            return Err(CompileError::DuplicateTrait { name, line });
         }
 
-        self.consume_symbol(SymbolMeaning::MatchEnd, "⟣")?;
+        self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+        let mut sigs: Vec<MethodSig> = Vec::new();
+        while !self.check_symbol(SymbolMeaning::RightBracket) {
+            let method_token = self.consume_identifier("method name")?;
+            let method = match &method_token.kind {
+                TokenKind::Identifier(s) => s.clone(),
+                _ => unreachable!(),
+            };
+            let declared_by = match self.trait_methods.get(&method) {
+                Some((owner, _)) => Some(owner.clone()),
+                None => sigs.iter().any(|sig| sig.name == method).then(|| name.clone()),
+            };
+            if let Some(trait_name) = declared_by {
+                return Err(CompileError::DuplicateMethod { trait_name, method, line: method_token.location.line });
+            }
 
-        // Pop the match value
-        self.emit_op(OpCode::Pop);
+            // Parameters after the receiver: [⧆k, ⌘label]
+            self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+            let mut params = Vec::new();
+            while !self.check_symbol(SymbolMeaning::RightBracket) {
+                let type_token = self.advance();
+                params.push(self.token_to_value_type(&type_token)?);
+                self.consume_identifier("parameter name")?;
+                if !self.match_symbol(SymbolMeaning::Separator) {
+                    break;
+                }
+            }
+            self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+            sigs.push(MethodSig { name: method, params });
 
-        // Patch all end jumps
-        for jump in end_jumps {
-            self.chunk.patch_jump(jump);
+            if !self.match_symbol(SymbolMeaning::Separator) {
+                break;
+            }
         }
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
 
+        for sig in &sigs {
+            self.trait_methods.insert(sig.name.clone(), (name.clone(), sig.params.len()));
+        }
+        self.traits.insert(name, sigs);
         Ok(())
     }
 
-    /// Try statement: ☄ ... ☊ ... ☋ ... ⟣
-    fn try_statement(&mut self) -> CompileResult<()> {
-        self.advance(); // consume ☄
-
-        // Emit try begin with handler offset (to be patched)
-        self.emit_op(OpCode::TryBegin);
-        let handler_jump = self.chunk.current_offset();
-        self.emit_u16(0xFFFF);
-
-        // Try block body
-        while !self.check_symbol(SymbolMeaning::CatchBlock) &&
-              !self.check_symbol(SymbolMeaning::FinallyBlock) &&
-              !self.check_symbol(SymbolMeaning::MatchEnd) &&
-              !self.is_at_end() {
-            self.statement()?;
+    /// Trait implementation: ⍜Area[Shape] λarea[⎊self] ... Λ ... ⟣
+    fn trait_implementation(&mut self) -> CompileResult<()> {
+        let line = self.advance().location.line; // consume ⍜
+        if !self.function_stack.is_empty() {
+            return Err(CompileError::NestedTraitItem { glyph: "⍜".to_string(), line });
         }
 
-        self.emit_op(OpCode::TryEnd);
+        let trait_token = self.consume_identifier("trait name")?;
+        let trait_name = match &trait_token.kind {
+            TokenKind::Identifier(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        let sigs = self.traits.get(&trait_name).cloned().ok_or(CompileError::UnknownTrait {
+            name: trait_name.clone(),
+            line,
+        })?;
 
-        // Jump over catch block
-        self.emit_op(OpCode::Jump);
-        let end_jump = self.chunk.current_offset();
-        self.emit_u16(0xFFFF);
+        // Implementing type: a user type name or a type glyph
+        self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+        let type_token = self.advance();
+        let (target, type_name) = match &type_token.kind {
+            TokenKind::Identifier(name) => match self.type_indices.get(name) {
+                Some(&index) => (FieldType::User(index), name.clone()),
+                None => return Err(CompileError::UnknownType { name: name.clone(), line }),
+            },
+            _ => {
+                let value_type = self.token_to_value_type(&type_token)?;
+                (FieldType::Value(value_type), value_type.to_string())
+            }
+        };
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
 
-        // Patch handler jump
-        self.chunk.patch_jump(handler_jump);
+        // Type the receiver parameter of every method must have
+        let receiver = match target {
+            FieldType::User(index) if matches!(self.chunk.types[index as usize].kind, TypeKind::Enum(_)) => ValueType::Enum,
+            FieldType::User(_) => ValueType::Struct,
+            FieldType::Value(value_type) => value_type,
+        };
 
-        // Catch block
-        if self.match_symbol(SymbolMeaning::CatchBlock) {
-            // Optional variable binding for exception
-            if self.check_symbol(SymbolMeaning::LeftBracket) {
-                self.advance();
-                let var_token = self.consume_identifier("exception variable")?;
-                let var_name = match &var_token.kind {
-                    TokenKind::Identifier(s) => s.clone(),
-                    _ => unreachable!(),
-                };
-                let var_idx = self.chunk.add_string(&var_name);
-                self.emit_op(OpCode::Catch);
-                self.emit_u16(var_idx);
-                self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+        if self.impls.iter().any(|(t, ty)| *t == trait_name && *ty == target) {
+            return Err(CompileError::DuplicateImpl { trait_name, type_name, line });
+        }
+        self.impls.push((trait_name.clone(), target));
+
+        let mut implemented: Vec<String> = Vec::new();
+        while !self.check_symbol(SymbolMeaning::MatchEnd) && !self.is_at_end() {
+            if !self.check_symbol(SymbolMeaning::FunctionStart) {
+                return Err(self.error("λ method or ⟣"));
+            }
+            let method_line = self.peek().location.line;
+
+            self.method_owner = Some(type_name.clone());
+            let defined = self.function_definition();
+            self.method_owner = None;
+            let func_idx = defined?;
+
+            let func = &self.chunk.functions[func_idx as usize];
+            let method = func.name[type_name.len() + "⊷".len()..].to_string();
+            let found: Vec<ValueType> = func.params.iter().skip(1).map(|(_, ty)| *ty).collect();
+            let sig = sigs.iter().find(|sig| sig.name == method).ok_or_else(|| CompileError::ForeignMethod {
+                trait_name: trait_name.clone(),
+                method: method.clone(),
+                line: method_line,
+            })?;
+            let own = func.params.first().map(|(_, ty)| *ty);
+            if own != Some(receiver) || found != sig.params {
+                return Err(CompileError::MethodSignatureMismatch {
+                    trait_name,
+                    method,
+                    line: method_line,
+                    expected: signature_text(receiver, &sig.params),
+                    found: own.map_or("[]".to_string(), |own| signature_text(own, &found)),
+                });
             }
 
-            // Catch body
-            while !self.check_symbol(SymbolMeaning::FinallyBlock) &&
+            self.chunk.add_method(target, method.clone(), func_idx);
+            implemented.push(method);
+        }
+        self.consume_symbol(SymbolMeaning::MatchEnd, "⟣")?;
+
+        if let Some(missing) = sigs.iter().find(|sig| !implemented.contains(&sig.name)) {
+            return Err(CompileError::MissingMethod {
+                trait_name,
+                type_name,
+                method: missing.name.clone(),
+                line,
+            });
+        }
+        Ok(())
+    }
+
+    /// Match expression: ⟡expr] ⟢pattern] ... ⟢pattern] ... ⟣
+    fn match_expression(&mut self) -> CompileResult<()> {
+        let match_line = self.advance().location.line; // consume ⟡
+
+        // Evaluate expression to match
+        self.expression()?;
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+
+        // Track jump patches for each arm
+        let mut end_jumps: Vec<usize> = Vec::new();
+
+        // The match value stays on the stack while the arms run
+        self.stack_temps += 1;
+
+        // Every arm must leave the spell stack at the same depth
+        let entry_depth = self.spell_depth;
+        let mut arm_depth: Option<usize> = None;
+        let mut exhaustive = false;
+
+        // Parse match arms: ⟢pattern] or ⟢pattern ⟨guard]
+        while self.check_symbol(SymbolMeaning::MatchArm) {
+            self.advance(); // consume ⟢
+
+            // Test the pattern against the match value, binding as it goes
+            let irrefutable = self.pattern()?;
+            self.emit_op(OpCode::JumpIfFalse);
+            let mut no_match_jumps = vec![self.chunk.current_offset()];
+            self.emit_u16(0xFFFF);
+
+            // An optional guard must hold as well
+            let guarded = self.match_symbol(SymbolMeaning::IfStart);
+            if guarded {
+                self.expression()?;
+                self.emit_op(OpCode::JumpIfFalse);
+                no_match_jumps.push(self.chunk.current_offset());
+                self.emit_u16(0xFFFF);
+            }
+            self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+
+            // An unguarded arm accepting every value makes the match exhaustive
+            exhaustive |= irrefutable && !guarded;
+
+            // Arm body
+            while !self.check_symbol(SymbolMeaning::MatchArm) &&
                   !self.check_symbol(SymbolMeaning::MatchEnd) &&
                   !self.is_at_end() {
                 self.statement()?;
             }
+            self.end_match_arm(entry_depth, &mut arm_depth)?;
+
+            // Jump to end after arm executes
+            self.emit_op(OpCode::Jump);
+            end_jumps.push(self.chunk.current_offset());
+            self.emit_u16(0xFFFF);
+
+            // A failed pattern or guard moves on to the next arm
+            for jump in no_match_jumps {
+                self.chunk.patch_jump(jump);
+            }
+        }
+
+        self.consume_symbol(SymbolMeaning::MatchEnd, "⟣")?;
+
+        // Without a catch-all arm the value may match no arm at all
+        let arm_depth = arm_depth.unwrap_or(entry_depth);
+        if !exhaustive {
+            self.warnings.push(CompileWarning::NonExhaustiveMatch { line: match_line });
+            self.spell_depth = arm_depth;
+            self.expect_spell_depth("a ⟡ match without a wildcard arm", entry_depth)?;
+        }
+        self.spell_depth = arm_depth;
+
+        // Patch all end jumps
+        for jump in end_jumps {
+            self.chunk.patch_jump(jump);
+        }
+
+        // Pop the match value, whichever arm ran
+        self.emit_op(OpCode::Pop);
+        self.stack_temps -= 1;
+
+        Ok(())
+    }
+
+    /// Match in expression position: ⟡value] ⟢pattern] expr ... ⟣, yielding
+    /// the expression of the arm taken
+    fn match_value(&mut self, line: usize) -> CompileResult<()> {
+        self.expression()?;
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+
+        let mut end_jumps: Vec<usize> = Vec::new();
+        let entry_depth = self.spell_depth;
+        let mut arm_depth: Option<usize> = None;
+        let mut arm_types: Vec<Option<ValueType>> = Vec::new();
+        let mut exhaustive = false;
+
+        while self.check_symbol(SymbolMeaning::MatchArm) {
+            let arm_line = self.advance().location.line; // consume ⟢
+
+            let irrefutable = self.pattern()?;
+            self.emit_op(OpCode::JumpIfFalse);
+            let mut no_match_jumps = vec![self.chunk.current_offset()];
+            self.emit_u16(0xFFFF);
+
+            let guarded = self.match_symbol(SymbolMeaning::IfStart);
+            if guarded {
+                self.expression()?;
+                self.emit_op(OpCode::JumpIfFalse);
+                no_match_jumps.push(self.chunk.current_offset());
+                self.emit_u16(0xFFFF);
+            }
+            self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+            exhaustive |= irrefutable && !guarded;
+
+            // The arm's value replaces the match value
+            self.expression()?;
+            self.check_arm_type(&mut arm_types, "this ⟢ arm", arm_line)?;
+            self.emit_op(OpCode::Swap);
+            self.emit_op(OpCode::Pop);
+            self.end_match_arm(entry_depth, &mut arm_depth)?;
+
+            self.emit_op(OpCode::Jump);
+            end_jumps.push(self.chunk.current_offset());
+            self.emit_u16(0xFFFF);
+
+            for jump in no_match_jumps {
+                self.chunk.patch_jump(jump);
+            }
+        }
+
+        self.consume_symbol(SymbolMeaning::MatchEnd, "⟣")?;
+
+        // Falling past every arm would leave no value to yield
+        if !exhaustive {
+            return Err(CompileError::NonExhaustiveMatchValue { line });
+        }
+        self.spell_depth = arm_depth.unwrap_or(entry_depth);
+
+        for jump in end_jumps {
+            self.chunk.patch_jump(jump);
+        }
+
+        self.expr_type = common_type(&arm_types);
+        Ok(())
+    }
+
+    /// Conditional in expression position: ⟨cond] expr ⟩ expr ⟫, yielding
+    /// the expression of the branch taken
+    fn if_value(&mut self) -> CompileResult<()> {
+        self.expression()?;
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+
+        self.emit_op(OpCode::JumpIfFalse);
+        let then_jump = self.chunk.current_offset();
+        self.emit_u16(0xFFFF);
+
+        let entry_depth = self.spell_depth;
+        let mut arm_types: Vec<Option<ValueType>> = Vec::new();
+
+        self.expression()?;
+        self.check_arm_type(&mut arm_types, "the ⟨ branch", self.previous().location.line)?;
+
+        // Without ⟩ there would be no value when the condition fails
+        let else_token = self.consume_symbol(SymbolMeaning::Else, "⟩ (a ⟨ used as a value needs both branches)")?;
+        self.emit_op(OpCode::Jump);
+        let else_jump = self.chunk.current_offset();
+        self.emit_u16(0xFFFF);
+        self.chunk.patch_jump(then_jump);
+
+        let then_depth = std::mem::replace(&mut self.spell_depth, entry_depth);
+        self.expression()?;
+        self.check_arm_type(&mut arm_types, "the ⟩ branch", else_token.location.line)?;
+        self.expect_spell_depth("the ⟩ branch", then_depth)?;
+
+        self.chunk.patch_jump(else_jump);
+        self.consume_symbol(SymbolMeaning::IfEnd, "⟫")?;
+
+        self.expr_type = common_type(&arm_types);
+        Ok(())
+    }
+
+    /// Record the type of the arm just compiled, rejecting one that
+    /// differs from a type the earlier arms are known to yield
+    fn check_arm_type(&self, arm_types: &mut Vec<Option<ValueType>>, construct: &str, line: usize) -> CompileResult<()> {
+        if let (Some(found), Some(expected)) = (self.expr_type, arm_types.iter().flatten().next()) {
+            if found != *expected {
+                return Err(CompileError::ArmTypeMismatch {
+                    construct: construct.to_string(),
+                    line,
+                    expected: *expected,
+                    found,
+                });
+            }
+        }
+        arm_types.push(self.expr_type);
+        Ok(())
+    }
+
+    /// Compile a pattern, alternatives separated by ∨. The value under test
+    /// stays on the stack with a boolean pushed on top; bindings are made as
+    /// the pattern matches. Returns whether the pattern accepts every value.
+    fn pattern(&mut self) -> CompileResult<bool> {
+        let mut irrefutable = self.pattern_primary()?;
+        if !self.check_symbol(SymbolMeaning::Or) {
+            return Ok(irrefutable);
+        }
+
+        // Stop at the first alternative that matches
+        let mut matched_jumps = Vec::new();
+        while self.match_symbol(SymbolMeaning::Or) {
+            self.emit_op(OpCode::JumpIfTrue);
+            matched_jumps.push(self.chunk.current_offset());
+            self.emit_u16(0xFFFF);
+            irrefutable |= self.pattern_primary()?;
+        }
+        self.emit_op(OpCode::Jump);
+        let done_jump = self.chunk.current_offset();
+        self.emit_u16(0xFFFF);
+
+        for jump in matched_jumps {
+            self.chunk.patch_jump(jump);
+        }
+        self.emit_op(OpCode::True);
+        self.chunk.patch_jump(done_jump);
+
+        Ok(irrefutable)
+    }
+
+    /// A single pattern: ◇, ≔name, a type glyph with an optional name, an
+    /// array or map shape, a range lo‥hi, or a value compared for equality
+    fn pattern_primary(&mut self) -> CompileResult<bool> {
+        let token = self.peek().clone();
+        match &token.kind {
+            TokenKind::Symbol(SymbolMeaning::Wildcard) => {
+                self.advance();
+                self.emit_op(OpCode::True);
+                Ok(true)
+            }
+
+            TokenKind::Symbol(SymbolMeaning::Bind) => {
+                self.advance();
+                self.emit_op(OpCode::Dup);
+                self.pattern_binding()?;
+                self.emit_op(OpCode::True);
+                Ok(true)
+            }
+
+            // A scalar glyph before [ is a conversion, compared as a value
+            TokenKind::Symbol(SymbolMeaning::TypeInteger |
+                SymbolMeaning::TypeReal |
+                SymbolMeaning::TypeString |
+                SymbolMeaning::TypeBoolean |
+                SymbolMeaning::TypeRune) if self.check_next_symbol(SymbolMeaning::LeftBracket) => {
+                self.value_pattern()?;
+                Ok(false)
+            }
+
+            TokenKind::Symbol(SymbolMeaning::TypeInteger |
+                SymbolMeaning::TypeReal |
+                SymbolMeaning::TypeString |
+                SymbolMeaning::TypeBoolean |
+                SymbolMeaning::TypeRune |
+                SymbolMeaning::TypeArray |
+                SymbolMeaning::TypeMap |
+                SymbolMeaning::TypeStruct |
+                SymbolMeaning::TypeEnum |
+                SymbolMeaning::TypeIterator) => {
+                self.advance();
+                let value_type = self.token_to_value_type(&token)?;
+                self.emit_op(OpCode::MatchType);
+                self.emit_byte(value_type as u8);
+
+                // ⟁n binds the value when the type matches
+                if matches!(self.peek().kind, TokenKind::Identifier(_)) {
+                    self.emit_op(OpCode::JumpIfFalse);
+                    let fail_jump = self.chunk.current_offset();
+                    self.emit_u16(0xFFFF);
+                    self.emit_op(OpCode::Dup);
+                    self.pattern_binding()?;
+                    self.finish_pattern(vec![], vec![fail_jump]);
+                }
+                Ok(false)
+            }
+
+            TokenKind::Symbol(SymbolMeaning::LeftBracket) => {
+                self.array_pattern()?;
+                Ok(false)
+            }
+
+            TokenKind::Symbol(SymbolMeaning::LeftBrace) => {
+                self.map_pattern()?;
+                Ok(false)
+            }
+
+            TokenKind::Identifier(name) if self.type_indices.contains_key(name) => {
+                self.advance();
+                let type_idx = self.type_indices[name];
+                self.user_type_pattern(type_idx)?;
+                Ok(false)
+            }
+
+            _ => {
+                self.value_pattern()?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Value pattern compared with =, or a half-open range lo‥hi
+    fn value_pattern(&mut self) -> CompileResult<()> {
+        self.emit_op(OpCode::Dup);
+        self.additive_expression()?;
+        if self.match_symbol(SymbolMeaning::Range) {
+            self.additive_expression()?;
+            self.emit_op(OpCode::MatchRange);
+        } else {
+            self.emit_op(OpCode::Eq);
+        }
+        Ok(())
+    }
+
+    /// Array shape: [p, ...] with an optional trailing ‥ or ‥≔rest
+    fn array_pattern(&mut self) -> CompileResult<()> {
+        let open = self.advance(); // consume [
+
+        self.emit_op(OpCode::MatchArray);
+        let shape_offset = self.chunk.current_offset();
+        self.emit_byte(0);
+        self.emit_byte(0);
+        self.emit_op(OpCode::JumpIfFalse);
+        let fail_jump = self.chunk.current_offset();
+        self.emit_u16(0xFFFF);
+
+        let mut element_fails = Vec::new();
+        let mut length: usize = 0;
+        let mut has_rest = false;
+        while !self.check_symbol(SymbolMeaning::RightBracket) && !self.is_at_end() {
+            if self.match_symbol(SymbolMeaning::Range) {
+                // The rest must come last; ≔name binds the remaining elements
+                has_rest = true;
+                if self.match_symbol(SymbolMeaning::Bind) {
+                    self.emit_op(OpCode::Dup);
+                    self.emit_op(OpCode::MatchRest);
+                    self.emit_byte(length as u8);
+                    self.pattern_binding()?;
+                }
+                break;
+            }
+
+            if length == u8::MAX as usize {
+                return Err(CompileError::UnexpectedToken {
+                    lexeme: open.lexeme.clone(),
+                    line: open.location.line,
+                    column: open.location.column,
+                    expected: format!("at most {} elements in an array pattern", u8::MAX),
+                    context: self.get_source_context(open.location.line),
+                });
+            }
+
+            // Test the element, keeping it on the stack until it passes
+            self.emit_op(OpCode::Dup);
+            self.emit_constant(Value::Integer(length as i64));
+            self.emit_op(OpCode::Index);
+            self.pattern()?;
+            self.emit_op(OpCode::JumpIfFalse);
+            element_fails.push(self.chunk.current_offset());
+            self.emit_u16(0xFFFF);
+            self.emit_op(OpCode::Pop);
+            length += 1;
+
+            if !self.match_symbol(SymbolMeaning::Separator) {
+                break;
+            }
+        }
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+
+        self.chunk.code[shape_offset] = length as u8;
+        self.chunk.code[shape_offset + 1] = has_rest as u8;
+        self.finish_pattern(element_fails, vec![fail_jump]);
+        Ok(())
+    }
+
+    /// Map shape: {key ⇒ p, ...}; every key must be present, others are ignored
+    fn map_pattern(&mut self) -> CompileResult<()> {
+        self.advance(); // consume {
+
+        self.emit_op(OpCode::MatchType);
+        self.emit_byte(ValueType::Map as u8);
+        self.emit_op(OpCode::JumpIfFalse);
+        let fail_jump = self.chunk.current_offset();
+        self.emit_u16(0xFFFF);
+
+        let mut entry_fails = Vec::new();
+        while !self.check_symbol(SymbolMeaning::RightBrace) && !self.is_at_end() {
+            // Look the key up, keeping its value on the stack until it passes
+            self.emit_op(OpCode::Dup);
+            self.expression()?;
+            self.consume_symbol(SymbolMeaning::MapArrow, "⇒")?;
+            self.emit_op(OpCode::MatchEntry);
+            self.emit_op(OpCode::JumpIfFalse);
+            entry_fails.push(self.chunk.current_offset());
+            self.emit_u16(0xFFFF);
+            self.pattern()?;
+            self.emit_op(OpCode::JumpIfFalse);
+            entry_fails.push(self.chunk.current_offset());
+            self.emit_u16(0xFFFF);
+            self.emit_op(OpCode::Pop);
+
+            // Optional separator (⋄ or ,)
+            if !self.match_symbol(SymbolMeaning::MapSeparator) &&
+               !self.match_symbol(SymbolMeaning::Separator) {
+                break;
+            }
+        }
+        self.consume_symbol(SymbolMeaning::RightBrace, "}")?;
+
+        self.finish_pattern(entry_fails, vec![fail_jump]);
+        Ok(())
+    }
+
+    /// User type pattern, once the type name is consumed: Point, Point p,
+    /// Point[p, ...], Shape⊷Circle[p], Shape⊷Empty. Without ⊷ any variant
+    /// of an enum matches.
+    fn user_type_pattern(&mut self, type_idx: u16) -> CompileResult<()> {
+        let def = std::rc::Rc::clone(&self.chunk.types[type_idx as usize]);
+        let line = self.previous().location.line;
+
+        let (tag, name) = if self.check_symbol(SymbolMeaning::ModuleAccess) {
+            let (tag, variant) = self.variant_path(&def, line)?;
+            (Some(tag), format!("{}⊷{}", def.name, variant.name))
+        } else {
+            (None, def.name.clone())
+        };
+
+        self.emit_op(OpCode::MatchUserType);
+        self.emit_u16(type_idx);
+        self.emit_byte(tag.map_or(u8::MAX, |tag| tag as u8));
+
+        let is_enum = matches!(def.kind, TypeKind::Enum(_));
+        if self.check_symbol(SymbolMeaning::LeftBracket) && !(is_enum && tag.is_none()) {
+            // Positional field patterns
+            self.advance(); // consume [
+            self.emit_op(OpCode::JumpIfFalse);
+            let fail_jump = self.chunk.current_offset();
+            self.emit_u16(0xFFFF);
+
+            let mut field_fails = Vec::new();
+            let mut given = 0;
+            while !self.check_symbol(SymbolMeaning::RightBracket) && !self.is_at_end() {
+                self.emit_op(OpCode::Dup);
+                self.emit_op(OpCode::FieldAt);
+                self.emit_byte(given as u8);
+                self.pattern()?;
+                self.emit_op(OpCode::JumpIfFalse);
+                field_fails.push(self.chunk.current_offset());
+                self.emit_u16(0xFFFF);
+                self.emit_op(OpCode::Pop);
+                given += 1;
+
+                if !self.match_symbol(SymbolMeaning::Separator) {
+                    break;
+                }
+            }
+            self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+
+            let expected = def.fields(tag.unwrap_or(0)).len();
+            if given != expected {
+                return Err(CompileError::WrongFieldCount { name, line, expected, got: given });
+            }
+            self.finish_pattern(field_fails, vec![fail_jump]);
+        } else if matches!(self.peek().kind, TokenKind::Identifier(_)) {
+            // Point p binds the value when the type matches
+            self.emit_op(OpCode::JumpIfFalse);
+            let fail_jump = self.chunk.current_offset();
+            self.emit_u16(0xFFFF);
+            self.emit_op(OpCode::Dup);
+            self.pattern_binding()?;
+            self.finish_pattern(vec![], vec![fail_jump]);
         }
+        Ok(())
+    }
 
-        // Finally block (optional)
+    /// Bind the value on top of the stack to the name that follows
+    fn pattern_binding(&mut self) -> CompileResult<()> {
+        let name_token = self.consume_identifier("binding name")?;
+        let name = match &name_token.kind {
+            TokenKind::Identifier(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        let name_idx = self.chunk.add_string(&name);
+        self.register_local(&name);
+        self.emit_op(OpCode::MatchBind);
+        self.emit_u16(name_idx);
+        Ok(())
+    }
+
+    /// Push the outcome of a compound pattern: true when control falls
+    /// through, false from the failure jumps. Inner failures still hold
+    /// the part being tested above the value, so they drop it first.
+    fn finish_pattern(&mut self, inner_fails: Vec<usize>, fails: Vec<usize>) {
+        self.emit_op(OpCode::True);
+        self.emit_op(OpCode::Jump);
+        let done_jump = self.chunk.current_offset();
+        self.emit_u16(0xFFFF);
+
+        if !inner_fails.is_empty() {
+            for jump in inner_fails {
+                self.chunk.patch_jump(jump);
+            }
+            self.emit_op(OpCode::Pop);
+        }
+        for jump in fails {
+            self.chunk.patch_jump(jump);
+        }
+        self.emit_op(OpCode::False);
+        self.chunk.patch_jump(done_jump);
+    }
+
+    /// Check a finished match arm against the depth the other arms left,
+    /// then rewind for the next arm
+    fn end_match_arm(&mut self, entry_depth: usize, arm_depth: &mut Option<usize>) -> CompileResult<()> {
+        match *arm_depth {
+            Some(depth) => self.expect_spell_depth("this ⟢ arm", depth)?,
+            None => *arm_depth = Some(self.spell_depth),
+        }
+        self.spell_depth = entry_depth;
+        Ok(())
+    }
+
+    /// Try statement: ☄ ... ☊ ... ☋ ... ⟣
+    fn try_statement(&mut self) -> CompileResult<()> {
+        self.advance(); // consume ☄
+
+        // Emit try begin with the ☊ and ☋ offsets, patched once those
+        // blocks are found (0 when absent)
+        self.emit_op(OpCode::TryBegin);
+        let catch_jump = self.chunk.current_offset();
+        self.emit_u16(0);
+        let finally_jump = self.chunk.current_offset();
+        self.emit_u16(0);
+
+        // A throw rewinds the spell stack to its depth here, so every
+        // part of the statement must leave it unchanged
+        let entry_depth = self.spell_depth;
+
+        // The handler covers both the try body and the catch body; each
+        // ends with a TryEnd that runs the finally block
+        self.try_depth += 1;
+        while !self.check_symbol(SymbolMeaning::CatchBlock) &&
+              !self.check_symbol(SymbolMeaning::FinallyBlock) &&
+              !self.check_symbol(SymbolMeaning::MatchEnd) &&
+              !self.is_at_end() {
+            self.statement()?;
+        }
+        self.expect_spell_depth("a ☄ block", entry_depth)?;
+
+        self.emit_op(OpCode::TryEnd);
+
+        // Jump over catch and finally blocks
+        self.emit_op(OpCode::Jump);
+        let mut end_jumps = vec![self.chunk.current_offset()];
+        self.emit_u16(0xFFFF);
+
+        // Catch blocks, tried in order until one's filter accepts the exception
+        if self.check_symbol(SymbolMeaning::CatchBlock) {
+            self.chunk.patch_jump(catch_jump);
+            let mut catches_all = false;
+
+            while self.match_symbol(SymbolMeaning::CatchBlock) {
+                let no_match_jump = self.catch_clause()?;
+
+                // Catch body
+                self.catch_depth += 1;
+                while !self.check_symbol(SymbolMeaning::CatchBlock) &&
+                      !self.check_symbol(SymbolMeaning::FinallyBlock) &&
+                      !self.check_symbol(SymbolMeaning::MatchEnd) &&
+                      !self.is_at_end() {
+                    self.statement()?;
+                }
+                self.catch_depth -= 1;
+                self.expect_spell_depth("a ☊ block", entry_depth)?;
+
+                self.emit_op(OpCode::TryEnd);
+                self.emit_op(OpCode::Jump);
+                end_jumps.push(self.chunk.current_offset());
+                self.emit_u16(0xFFFF);
+
+                match no_match_jump {
+                    Some(jump) => self.chunk.patch_jump(jump),
+                    None => catches_all = true,
+                }
+            }
+
+            // No ☊ accepted it: pass the exception on (after the finally block)
+            if !catches_all {
+                self.emit_op(OpCode::Rethrow);
+            }
+        }
+        self.try_depth -= 1;
+
+        // Finally block (optional), entered by TryEnd, a throw or a return
+        // and left through EndFinally
         if self.match_symbol(SymbolMeaning::FinallyBlock) {
+            self.chunk.patch_jump(finally_jump);
             self.emit_op(OpCode::Finally);
 
+            self.finally_depth += 1;
             while !self.check_symbol(SymbolMeaning::MatchEnd) && !self.is_at_end() {
                 self.statement()?;
             }
+            self.finally_depth -= 1;
+            self.expect_spell_depth("a ☋ block", entry_depth)?;
+
+            self.emit_op(OpCode::EndFinally);
         }
 
         self.consume_symbol(SymbolMeaning::MatchEnd, "⟣")?;
 
-        // Patch end jump
-        self.chunk.patch_jump(end_jump);
+        for jump in end_jumps {
+            self.chunk.patch_jump(jump);
+        }
 
         Ok(())
     }
 
-    /// Throw statement: ⚠[expr]
+    /// The head of a catch block: nothing, [name], [name ∈ Kind, ...] or
+    /// [name ⟢ pattern], where ◇ may stand for the name. Emits the filter and
+    /// the binding, returning the jump taken when the filter rejects the
+    /// exception.
+    fn catch_clause(&mut self) -> CompileResult<Option<usize>> {
+        if !self.match_symbol(SymbolMeaning::LeftBracket) {
+            return Ok(None);
+        }
+
+        let name = if self.match_symbol(SymbolMeaning::Wildcard) {
+            None
+        } else {
+            let var_token = self.consume_identifier("exception variable")?;
+            match &var_token.kind {
+                TokenKind::Identifier(s) => Some(s.clone()),
+                _ => unreachable!(),
+            }
+        };
+
+        let filtered = if self.match_symbol(SymbolMeaning::In) {
+            // Kind filter: any of the listed kinds
+            let mut first = true;
+            loop {
+                let kind_token = self.consume_identifier("exception kind")?;
+                let kind = match &kind_token.kind {
+                    TokenKind::Identifier(s) => s.clone(),
+                    _ => unreachable!(),
+                };
+                self.emit_op(OpCode::LoadException);
+                self.emit_op(OpCode::ExceptionKind);
+                self.emit_constant(Value::String(kind));
+                self.emit_op(OpCode::Eq);
+                if !first {
+                    self.emit_op(OpCode::Or);
+                }
+                first = false;
+
+                if !self.match_symbol(SymbolMeaning::Separator) {
+                    break;
+                }
+            }
+            true
+        } else if self.match_symbol(SymbolMeaning::MatchArm) {
+            // Pattern filter, tested like a ⟢ arm
+            self.emit_op(OpCode::LoadException);
+            self.pattern()?;
+            self.emit_op(OpCode::Swap);
+            self.emit_op(OpCode::Pop);
+            true
+        } else {
+            false
+        };
+
+        let mut no_match_jump = None;
+        if filtered {
+            self.emit_op(OpCode::JumpIfFalse);
+            no_match_jump = Some(self.chunk.current_offset());
+            self.emit_u16(0xFFFF);
+        }
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+
+        if let Some(name) = name {
+            let var_idx = self.chunk.add_string(&name);
+            self.register_local(&name);
+            self.emit_op(OpCode::Catch);
+            self.emit_u16(var_idx);
+        }
+        Ok(no_match_jump)
+    }
+
+    /// Throw statement: ⚠[expr], or a bare ⚠ rethrowing the exception
+    /// of the enclosing ☊ block
     fn throw_statement(&mut self) -> CompileResult<()> {
         self.advance(); // consume ⚠
 
-        self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+        if !self.match_symbol(SymbolMeaning::LeftBracket) {
+            if self.catch_depth == 0 {
+                return Err(CompileError::RethrowOutsideCatch { line: self.previous().location.line });
+            }
+            self.emit_op(OpCode::Rethrow);
+            return Ok(());
+        }
         self.expression()?;
         self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
 
@@ -1051,16 +2776,96 @@ impl<'a> Compiler<'a> {
         self.expression()?;
         self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
 
-        // Value is already on stack
+        self.spell_stack_effect("⇑", 0, 1)?;
+        self.emit_op(OpCode::SpellPush);
+        Ok(())
+    }
+
+    /// Account for a spell stack operation that takes `needed` values and
+    /// leaves `produced` in their place
+    fn spell_stack_effect(&mut self, glyph: &str, needed: usize, produced: usize) -> CompileResult<()> {
+        if self.spell_depth < needed {
+            return Err(CompileError::StackUnderflow {
+                glyph: glyph.to_string(),
+                line: self.previous().location.line,
+                needed,
+                available: self.spell_depth,
+            });
+        }
+        self.spell_depth = self.spell_depth - needed + produced;
+        Ok(())
+    }
+
+    /// Require the spell stack to hold exactly `expected` values here
+    fn expect_spell_depth(&self, construct: &str, expected: usize) -> CompileResult<()> {
+        if self.spell_depth != expected {
+            return Err(CompileError::StackImbalance {
+                construct: construct.to_string(),
+                line: self.previous().location.line,
+                expected,
+                found: self.spell_depth,
+            });
+        }
+        Ok(())
+    }
+
+    // ═══════════════════════════════════════════════════════════════
+    // EXPRESSION COMPILATION
+    // ═══════════════════════════════════════════════════════════════
+
+    fn expression(&mut self) -> CompileResult<()> {
+        self.pipeline_expression()
+    }
+
+    /// Collection operators and pipes, chained left to right:
+    /// xs ⧉ f, xs ⧊ p, xs ⧋[seed] f, x ⋈ f
+    fn pipeline_expression(&mut self) -> CompileResult<()> {
+        self.compose_expression()?;
+
+        loop {
+            // ⧉ and ⧊ keep the kind of collection they are given
+            let collection = self.expr_type;
+            if self.match_symbol(SymbolMeaning::MapWith) {
+                self.compose_expression()?;
+                self.emit_op(OpCode::MapWith);
+                self.expr_type = collection;
+            } else if self.match_symbol(SymbolMeaning::FilterWith) {
+                self.compose_expression()?;
+                self.emit_op(OpCode::FilterWith);
+                self.expr_type = collection;
+            } else if self.match_symbol(SymbolMeaning::ReduceWith) {
+                let seeded = self.match_symbol(SymbolMeaning::LeftBracket);
+                if seeded {
+                    self.expression()?;
+                    self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+                }
+                self.compose_expression()?;
+                self.emit_op(OpCode::ReduceWith);
+                self.emit_byte(seeded as u8);
+                self.expr_type = None;
+            } else if self.match_symbol(SymbolMeaning::Pipe) {
+                self.compose_expression()?;
+                self.emit_op(OpCode::Pipe);
+                self.expr_type = None;
+            } else {
+                break;
+            }
+        }
+
         Ok(())
     }
 
-    // ═══════════════════════════════════════════════════════════════
-    // EXPRESSION COMPILATION
-    // ═══════════════════════════════════════════════════════════════
+    /// Function composition, binding tighter than the pipeline: f ⋉ g
+    fn compose_expression(&mut self) -> CompileResult<()> {
+        self.or_expression()?;
 
-    fn expression(&mut self) -> CompileResult<()> {
-        self.or_expression()
+        while self.match_symbol(SymbolMeaning::Compose) {
+            self.or_expression()?;
+            self.emit_op(OpCode::Compose);
+            self.expr_type = None;
+        }
+
+        Ok(())
     }
 
     fn or_expression(&mut self) -> CompileResult<()> {
@@ -1069,6 +2874,7 @@ impl<'a> Compiler<'a> {
         while self.match_symbol(SymbolMeaning::Or) {
             self.and_expression()?;
             self.emit_op(OpCode::Or);
+            self.expr_type = Some(ValueType::Boolean);
         }
 
         Ok(())
@@ -1080,6 +2886,7 @@ impl<'a> Compiler<'a> {
         while self.match_symbol(SymbolMeaning::And) {
             self.equality_expression()?;
             self.emit_op(OpCode::And);
+            self.expr_type = Some(ValueType::Boolean);
         }
 
         Ok(())
@@ -1098,6 +2905,7 @@ impl<'a> Compiler<'a> {
             } else {
                 break;
             }
+            self.expr_type = Some(ValueType::Boolean);
         }
 
         Ok(())
@@ -1122,6 +2930,7 @@ impl<'a> Compiler<'a> {
             } else {
                 break;
             }
+            self.expr_type = Some(ValueType::Boolean);
         }
 
         Ok(())
@@ -1131,12 +2940,19 @@ impl<'a> Compiler<'a> {
         self.multiplicative_expression()?;
 
         loop {
+            let left = self.expr_type;
             if self.match_symbol(SymbolMeaning::Add) {
                 self.multiplicative_expression()?;
                 self.emit_op(OpCode::Add);
+                // ✚ also joins strings
+                self.expr_type = match (left, self.expr_type) {
+                    (Some(ValueType::String), Some(ValueType::String)) => Some(ValueType::String),
+                    (left, right) => arithmetic_type(left, right),
+                };
             } else if self.match_symbol(SymbolMeaning::Subtract) {
                 self.multiplicative_expression()?;
                 self.emit_op(OpCode::Sub);
+                self.expr_type = arithmetic_type(left, self.expr_type);
             } else {
                 break;
             }
@@ -1149,6 +2965,7 @@ impl<'a> Compiler<'a> {
         self.unary_expression()?;
 
         loop {
+            let left = self.expr_type;
             if self.match_symbol(SymbolMeaning::Multiply) {
                 self.unary_expression()?;
                 self.emit_op(OpCode::Mul);
@@ -1161,6 +2978,7 @@ impl<'a> Compiler<'a> {
             } else {
                 break;
             }
+            self.expr_type = arithmetic_type(left, self.expr_type);
         }
 
         Ok(())
@@ -1170,19 +2988,156 @@ impl<'a> Compiler<'a> {
         if self.match_symbol(SymbolMeaning::Not) {
             self.unary_expression()?;
             self.emit_op(OpCode::Not);
+            self.expr_type = Some(ValueType::Boolean);
         } else if self.match_symbol(SymbolMeaning::Subtract) {
             self.unary_expression()?;
             self.emit_op(OpCode::Neg);
         } else {
-            self.primary()?;
+            self.postfix_expression()?;
+        }
+
+        Ok(())
+    }
+
+    /// Indexing, field access and partial application:
+    /// xs⌷0, m⌷"key", grid⌷1⌷2, p⊷x, add⋊[1]
+    fn postfix_expression(&mut self) -> CompileResult<()> {
+        // A function named directly has a known arity to check ⋊ against,
+        // unless a variadic parameter takes whatever is left
+        let mut known = match &self.peek().kind {
+            TokenKind::Identifier(name) => self.function_value(name)
+                .and_then(|func_idx| self.chunk.get_function(func_idx as usize))
+                .filter(|func| !func.variadic)
+                .map(|func| (name.clone(), func.fixed_params() as u8)),
+            _ => None,
+        };
+        self.primary()?;
+
+        loop {
+            if self.match_symbol(SymbolMeaning::PartialApply) {
+                self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+                let arg_count = self.argument_list()?;
+                if let Some((name, arity)) = known.take() {
+                    if arg_count > arity {
+                        return Err(CompileError::WrongArity { name, expected: arity, got: arg_count });
+                    }
+                    known = Some((name, arity - arg_count));
+                }
+                self.emit_op(OpCode::BindArgs);
+                self.emit_byte(arg_count);
+                self.expr_type = None;
+                continue;
+            }
+
+            known = None;
+            if self.match_symbol(SymbolMeaning::Index) {
+                self.primary()?;
+                self.emit_op(OpCode::Index);
+            } else if self.match_symbol(SymbolMeaning::ModuleAccess) {
+                let field_token = self.consume_identifier("field name")?;
+                let field = match &field_token.kind {
+                    TokenKind::Identifier(s) => s.clone(),
+                    _ => unreachable!(),
+                };
+                let field_idx = self.chunk.add_string(&field);
+                if self.check_symbol(SymbolMeaning::LeftBracket) {
+                    self.method_call(field, field_idx, field_token.location.line)?;
+                } else {
+                    self.emit_op(OpCode::GetField);
+                    self.emit_u16(field_idx);
+                }
+            } else {
+                break;
+            }
+            self.expr_type = None;
+        }
+
+        Ok(())
+    }
+
+    /// Struct construction Point[1, 2], or an enum variant Shape⊷Circle[2.0]
+    /// (Shape⊷Empty without a payload), once the type name is consumed
+    fn user_type_value(&mut self, type_idx: u16) -> CompileResult<()> {
+        let def = std::rc::Rc::clone(&self.chunk.types[type_idx as usize]);
+        let line = self.previous().location.line;
+
+        let (tag, fields) = match &def.kind {
+            TypeKind::Struct(fields) => (None, fields.len()),
+            TypeKind::Enum(_) => {
+                let (tag, variant) = self.variant_path(&def, line)?;
+                (Some(tag), variant.fields.len())
+            }
+        };
+
+        // Unit variants need no brackets; structs and payloads do
+        let mut given = 0;
+        if fields > 0 || self.check_symbol(SymbolMeaning::LeftBracket) {
+            self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+            if !self.check_symbol(SymbolMeaning::RightBracket) {
+                loop {
+                    self.expression()?;
+                    given += 1;
+                    if !self.match_symbol(SymbolMeaning::Separator) {
+                        break;
+                    }
+                }
+            }
+            self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+        }
+
+        let name = match tag {
+            Some(tag) => format!("{}⊷{}", def.name, def.variant_name(tag)),
+            None => def.name.clone(),
+        };
+        if given != fields {
+            return Err(CompileError::WrongFieldCount { name, line, expected: fields, got: given });
         }
 
+        match tag {
+            Some(tag) => {
+                self.emit_op(OpCode::MakeVariant);
+                self.emit_u16(type_idx);
+                self.emit_byte(tag as u8);
+                self.expr_type = Some(ValueType::Enum);
+            }
+            None => {
+                self.emit_op(OpCode::MakeStruct);
+                self.emit_u16(type_idx);
+                self.expr_type = Some(ValueType::Struct);
+            }
+        }
         Ok(())
     }
 
+    /// ⊷Variant after an enum name, returning its tag and definition
+    fn variant_path<'d>(&mut self, def: &'d TypeDef, line: usize) -> CompileResult<(usize, &'d VariantDef)> {
+        self.consume_symbol(SymbolMeaning::ModuleAccess, "⊷ and a variant name")?;
+        let variant_token = self.consume_identifier("variant name")?;
+        let variant = match &variant_token.kind {
+            TokenKind::Identifier(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        def.variant(&variant).ok_or(CompileError::UnknownVariant {
+            type_name: def.name.clone(),
+            variant,
+            line,
+        })
+    }
+
+    #[allow(clippy::redundant_guards)]
     fn primary(&mut self) -> CompileResult<()> {
         let token = self.advance();
 
+        // Only literals, conversions and the value forms below have a known type
+        self.expr_type = match &token.kind {
+            TokenKind::Integer(_) => Some(ValueType::Integer),
+            TokenKind::Float(_) => Some(ValueType::Real),
+            TokenKind::String(_) => Some(ValueType::String),
+            TokenKind::Boolean(_) |
+            TokenKind::Symbol(SymbolMeaning::True | SymbolMeaning::False) => Some(ValueType::Boolean),
+            _ => None,
+        };
+
         match &token.kind {
             TokenKind::Integer(i) => {
                 self.emit_constant(Value::Integer(*i));
@@ -1217,12 +3172,25 @@ impl<'a> Compiler<'a> {
             }
 
             TokenKind::Symbol(SymbolMeaning::Accumulator) => {
-                self.emit_op(OpCode::LoadAcc);
+                match self.accumulator_depth()? {
+                    0 => self.emit_op(OpCode::LoadAcc),
+                    depth => {
+                        self.emit_op(OpCode::LoadOuterAcc);
+                        self.emit_byte(depth);
+                    }
+                }
+            }
+
+            // Top of the spell stack (⇓), consumed by the expression
+            TokenKind::Symbol(SymbolMeaning::Pop) => {
+                self.spell_stack_effect("⇓", 1, 0)?;
+                self.emit_op(OpCode::SpellPop);
             }
 
             // Function call (⤷name[args])
             TokenKind::Symbol(SymbolMeaning::Call) => {
                 self.function_call()?;
+                self.expr_type = None;
             }
 
             TokenKind::Symbol(SymbolMeaning::LeftParen) => {
@@ -1230,19 +3198,39 @@ impl<'a> Compiler<'a> {
                 self.consume_symbol(SymbolMeaning::RightParen, ")")?;
             }
 
+            // Anonymous function: λ[⟁x] ... Λ
+            TokenKind::Symbol(SymbolMeaning::FunctionStart) if self.check_symbol(SymbolMeaning::LeftBracket) => {
+                let func_idx = self.function_body("λ".to_string(), None)?;
+                match self.closure_captures.get(&func_idx).cloned() {
+                    Some(captures) => self.emit_closure(func_idx, &captures),
+                    None => {
+                        self.emit_op(OpCode::LoadFunc);
+                        self.emit_u16(func_idx);
+                    }
+                }
+                self.expr_type = None;
+            }
+
+            // ⟡ and ⟨ in expression position yield the value of the arm taken
+            TokenKind::Symbol(SymbolMeaning::MatchStart) => {
+                self.match_value(token.location.line)?;
+            }
+
+            TokenKind::Symbol(SymbolMeaning::IfStart) => {
+                self.if_value()?;
+            }
+
+            TokenKind::Identifier(name) if self.type_indices.contains_key(name) => {
+                let type_idx = self.type_indices[name];
+                self.user_type_value(type_idx)?;
+            }
+
             TokenKind::Identifier(name) => {
                 // Check if this is a function name (for first-class function support)
-                if let Some(&func_idx) = self.function_indices.get(name) {
+                if let Some(func_idx) = self.function_value(name) {
                     // Check if this function needs closure (has captures)
                     if let Some(captures) = self.closure_captures.get(&func_idx).cloned() {
-                        // Push captured values onto stack
-                        for capture_name in &captures {
-                            self.emit_variable_load(capture_name)?;
-                        }
-                        // Create closure
-                        self.emit_op(OpCode::MakeClosure);
-                        self.emit_u16(func_idx);
-                        self.emit_byte(captures.len() as u8);
+                        self.emit_closure(func_idx, &captures);
                     } else {
                         // Load function as value (no captures)
                         self.emit_op(OpCode::LoadFunc);
@@ -1254,6 +3242,21 @@ impl<'a> Compiler<'a> {
                 }
             }
 
+            // Conversion: type glyph applied to a bracketed expression (⟁["42"], ⌘[n], ...)
+            TokenKind::Symbol(SymbolMeaning::TypeInteger |
+                SymbolMeaning::TypeReal |
+                SymbolMeaning::TypeString |
+                SymbolMeaning::TypeBoolean |
+                SymbolMeaning::TypeRune) if self.check_symbol(SymbolMeaning::LeftBracket) => {
+                let target = self.token_to_value_type(&token)?;
+                self.advance(); // consume [
+                self.expression()?;
+                self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+                self.emit_op(OpCode::Convert);
+                self.emit_byte(target as u8);
+                self.expr_type = Some(target);
+            }
+
             // Type prefix followed by identifier (⟁x, ⌘name, etc.)
             TokenKind::Symbol(meaning) if matches!(meaning,
                 SymbolMeaning::TypeInteger |
@@ -1286,7 +3289,41 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
-    /// Function call: ⤷name[args]
+    /// Method call on the receiver below: v⊷name[args]
+    fn method_call(&mut self, method: String, name_idx: u16, line: usize) -> CompileResult<()> {
+        let (_, expected) = self.trait_methods.get(&method).cloned()
+            .ok_or(CompileError::UnknownMethod { method: method.clone(), line })?;
+
+        self.advance(); // consume [
+        let arg_count = self.argument_list()?;
+
+        if arg_count as usize != expected {
+            return Err(CompileError::WrongArity { name: format!("⊷{}", method), expected: expected as u8, got: arg_count });
+        }
+        self.emit_op(OpCode::Invoke);
+        self.emit_u16(name_idx);
+        self.emit_byte(arg_count);
+        Ok(())
+    }
+
+    /// Arguments up to the closing ], once the [ is consumed
+    fn argument_list(&mut self) -> CompileResult<u8> {
+        let mut arg_count: u8 = 0;
+        if !self.check_symbol(SymbolMeaning::RightBracket) {
+            loop {
+                self.expression()?;
+                arg_count += 1;
+
+                if !self.match_symbol(SymbolMeaning::Separator) {
+                    break;
+                }
+            }
+        }
+        self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
+        Ok(arg_count)
+    }
+
+    /// Function call: ⤷name[args], with named arguments last: ⤷name[1, b=2]
     fn function_call(&mut self) -> CompileResult<()> {
         // Get function name
         let name_token = self.consume_identifier("function name")?;
@@ -1294,38 +3331,130 @@ impl<'a> Compiler<'a> {
             TokenKind::Identifier(s) => s.clone(),
             _ => unreachable!(),
         };
+        let line = name_token.location.line;
 
         self.consume_symbol(SymbolMeaning::LeftBracket, "[")?;
+        let (arg_count, named) = self.call_arguments()?;
+
+        // Check if this is a direct function call, a builtin, or indirect (via variable)
+        if let Some(func_idx) = self.function_named(&name) {
+            self.check_call(func_idx, arg_count, &named, line)?;
+            if let Some(captures) = self.closure_captures.get(&func_idx).cloned() {
+                // A nested function needs its captured variables, even when called directly
+                self.emit_closure(func_idx, &captures);
+                self.emit_closure_call(arg_count, &named);
+            } else {
+                // Direct function call
+                if named.is_empty() {
+                    self.last_call = Some((self.chunk.current_offset(), OpCode::Call));
+                }
+                self.emit_op(if named.is_empty() { OpCode::Call } else { OpCode::CallNamed });
+                self.emit_u16(func_idx);
+                self.emit_byte(arg_count);
+            }
+        } else if let Some((builtin_idx, builtin)) = self.builtin_for_call(&name) {
+            if let Some(param) = named.into_iter().next() {
+                return Err(CompileError::UnknownArgument { name, param, line });
+            }
+            if !builtin.accepts(arg_count) {
+                let expected = if arg_count < builtin.min_arity { builtin.min_arity } else { builtin.max_arity };
+                return Err(CompileError::WrongArity { name, expected, got: arg_count });
+            }
+            self.emit_op(OpCode::CallNative);
+            self.emit_u16(builtin_idx);
+            self.emit_byte(arg_count);
+        } else if !self.bound_names.contains(&name) {
+            // Never declared, assigned or taken as a parameter anywhere
+            return Err(CompileError::UndefinedFunction { name, line });
+        } else {
+            // Indirect call - load the variable value and call as closure
+            self.emit_variable_load(&name)?;
+            self.emit_closure_call(arg_count, &named);
+        }
 
-        // Parse arguments
-        let mut arg_count: u8 = 0;
+        Ok(())
+    }
 
+    /// Arguments of a ⤷ call up to the closing ]: the positional ones, then
+    /// any named ones gathered into a ⌖; returns the positional count and
+    /// the names given
+    fn call_arguments(&mut self) -> CompileResult<(u8, Vec<String>)> {
+        let mut arg_count: u8 = 0;
+        let mut named = Vec::new();
         if !self.check_symbol(SymbolMeaning::RightBracket) {
             loop {
-                self.expression()?;
-                arg_count += 1;
+                let is_named = matches!(self.peek().kind, TokenKind::Identifier(_)) && self.check_next(TokenKind::Equals);
+                if is_named {
+                    let param_token = self.advance();
+                    let param = match &param_token.kind {
+                        TokenKind::Identifier(s) => s.clone(),
+                        _ => unreachable!(),
+                    };
+                    self.advance(); // consume =
+                    self.emit_constant(Value::String(param.clone()));
+                    self.expression()?;
+                    named.push(param);
+                } else if !named.is_empty() {
+                    let token = self.peek().clone();
+                    let context = self.get_source_context(token.location.line);
+                    return Err(CompileError::UnexpectedToken {
+                        lexeme: token.lexeme,
+                        line: token.location.line,
+                        column: token.location.column,
+                        expected: "named argument (name=value) after the named ones".to_string(),
+                        context,
+                    });
+                } else {
+                    self.expression()?;
+                    arg_count += 1;
+                }
 
                 if !self.match_symbol(SymbolMeaning::Separator) {
                     break;
                 }
             }
         }
-
         self.consume_symbol(SymbolMeaning::RightBracket, "]")?;
 
-        // Check if this is a direct function call or indirect (via variable)
-        if let Some(&func_idx) = self.function_indices.get(&name) {
-            // Direct function call
-            self.emit_op(OpCode::Call);
-            self.emit_u16(func_idx);
-            self.emit_byte(arg_count);
-        } else {
-            // Indirect call - load the variable value and call as closure
-            self.emit_variable_load(&name)?;
-            self.emit_op(OpCode::CallClosure);
-            self.emit_byte(arg_count);
+        if !named.is_empty() {
+            self.emit_op(OpCode::MakeMap);
+            self.emit_u16(named.len() as u16);
+        }
+        Ok((arg_count, named))
+    }
+
+    /// Call the closure on top of the stack
+    fn emit_closure_call(&mut self, arg_count: u8, named: &[String]) {
+        if named.is_empty() {
+            self.last_call = Some((self.chunk.current_offset(), OpCode::CallClosure));
+        }
+        self.emit_op(if named.is_empty() { OpCode::CallClosure } else { OpCode::CallClosureNamed });
+        self.emit_byte(arg_count);
+    }
+
+    /// Check the arguments of a call to a known function against its parameters
+    fn check_call(&self, func_idx: u16, arg_count: u8, named: &[String], line: usize) -> CompileResult<()> {
+        let Some(func) = self.chunk.get_function(func_idx as usize) else {
+            return Ok(());
+        };
+        let fixed = func.fixed_params();
+        let got = arg_count + named.len() as u8;
+        if arg_count as usize > fixed && !func.variadic {
+            return Err(CompileError::WrongArity { name: func.name.clone(), expected: fixed as u8, got });
+        }
+
+        let mut given: Vec<bool> = (0..fixed).map(|position| position < arg_count as usize).collect();
+        for param in named {
+            let position = func.params[..fixed].iter().position(|(name, _)| name == param)
+                .ok_or_else(|| CompileError::UnknownArgument { name: func.name.clone(), param: param.clone(), line })?;
+            if std::mem::replace(&mut given[position], true) {
+                return Err(CompileError::DuplicateArgument { name: func.name.clone(), param: param.clone(), line });
+            }
         }
 
+        if given[..func.required_params()].contains(&false) {
+            return Err(CompileError::WrongArity { name: func.name.clone(), expected: func.required_params() as u8, got });
+        }
         Ok(())
     }
 
@@ -1333,57 +3462,116 @@ impl<'a> Compiler<'a> {
     // VARIABLE RESOLUTION WITH CLOSURE CAPTURE
     // ═══════════════════════════════════════════════════════════════
 
-    /// Emit bytecode to load a variable, handling closure captures
-    fn emit_variable_load(&mut self, name: &str) -> CompileResult<()> {
-        // Check if we're inside a function
-        if let Some(current_depth) = self.function_stack.len().checked_sub(1) {
-            let current_func = &self.function_stack[current_depth];
+    /// Capture index of a variable the current function reaches through an
+    /// upvalue, capturing it on first use; None for its own locals and for
+    /// top-level variables
+    fn resolve_capture(&mut self, name: &str) -> Option<usize> {
+        let current_depth = self.function_stack.len().checked_sub(1)?;
+        let current_func = &self.function_stack[current_depth];
 
-            // Check if it's a local variable in current function
-            if current_func.is_local(name) {
-                let name_idx = self.chunk.add_string(name);
-                self.emit_op(OpCode::LoadVar);
-                self.emit_u16(name_idx);
-                return Ok(());
-            }
+        // Check if it's a local variable in current function
+        if current_func.is_local(name) {
+            return None;
+        }
 
-            // Check if already captured
-            if let Some(capture_idx) = current_func.get_capture_index(name) {
-                self.emit_op(OpCode::LoadCapture);
-                self.emit_u16(capture_idx as u16);
-                return Ok(());
-            }
+        // Check if already captured
+        if let Some(capture_idx) = current_func.get_capture_index(name) {
+            return Some(capture_idx);
+        }
 
-            // Check if variable exists in outer scopes (needs capture)
-            let mut found_in_outer = false;
-            for depth in (0..current_depth).rev() {
-                if self.function_stack[depth].is_local(name) {
-                    found_in_outer = true;
-                    break;
-                }
-            }
+        // Check if variable exists in outer scopes (needs capture)
+        let found_in_outer = (0..current_depth).rev()
+            .any(|depth| self.function_stack[depth].is_local(name));
+        found_in_outer.then(|| self.function_stack[current_depth].add_capture(name.to_string()))
+    }
 
-            if found_in_outer {
-                // Add to captures and emit LoadCapture
-                // We need to modify function_stack mutably
-                let capture_idx = self.function_stack[current_depth].add_capture(name.to_string());
-                self.emit_op(OpCode::LoadCapture);
-                self.emit_u16(capture_idx as u16);
-                return Ok(());
-            }
+    /// Emit bytecode to load a variable, handling closure captures
+    fn emit_variable_load(&mut self, name: &str) -> CompileResult<()> {
+        if let Some(capture_idx) = self.resolve_capture(name) {
+            self.emit_op(OpCode::LoadCapture);
+            self.emit_u16(capture_idx as u16);
+            return Ok(());
         }
 
-        // Default: load as regular variable (global or not in function)
+        // Default: load as regular variable (local, global or not in function)
         let name_idx = self.chunk.add_string(name);
         self.emit_op(OpCode::LoadVar);
         self.emit_u16(name_idx);
         Ok(())
     }
 
-    /// Register a local variable in the current function scope
+    /// Emit bytecode to store the value on top into a variable, writing
+    /// through the upvalue when it is captured
+    fn emit_variable_store(&mut self, name: &str) {
+        if let Some(capture_idx) = self.resolve_capture(name) {
+            self.emit_op(OpCode::StoreCapture);
+            self.emit_u16(capture_idx as u16);
+            return;
+        }
+
+        let name_idx = self.chunk.add_string(name);
+        self.emit_op(OpCode::StoreVar);
+        self.emit_u16(name_idx);
+    }
+
+    /// Emit an instruction updating a variable in place by name (⌷, ⊷ and ⊞
+    /// assignments), capturing it first so that inside a closure the update
+    /// goes through the upvalue
+    fn emit_variable_update(&mut self, op: OpCode, name: &str) {
+        self.resolve_capture(name);
+        let name_idx = self.chunk.add_string(name);
+        self.emit_op(op);
+        self.emit_u16(name_idx);
+    }
+
+    /// Emit bytecode creating a closure over the variables a function captures
+    ///
+    /// A capture that is not a local of the current function makes the
+    /// current function capture it in turn, so the upvalue can be passed on.
+    fn emit_closure(&mut self, func_idx: u16, captures: &[String]) {
+        for name in captures {
+            self.resolve_capture(name);
+        }
+        self.emit_op(OpCode::MakeClosure);
+        self.emit_u16(func_idx);
+        self.emit_byte(captures.len() as u8);
+    }
+
+    /// Resolve a call target to a builtin, unless a local of the same name shadows it
+    fn builtin_for_call(&self, name: &str) -> Option<(u16, &'static stdlib::Builtin)> {
+        let shadowed = self.function_stack.iter().any(|f| f.is_local(name));
+        if shadowed {
+            return None;
+        }
+        stdlib::lookup(name)
+    }
+
+    /// Resolve a call target to a function, unless a local of the same name shadows it
+    fn function_named(&self, name: &str) -> Option<u16> {
+        let shadowed = self.function_stack.iter().any(|f| f.is_local(name));
+        if shadowed {
+            return None;
+        }
+        self.function_indices.get(name).copied()
+    }
+
+    /// Resolve a name used as a value to a function, unless a local or a
+    /// top-level variable of the same name shadows it
+    fn function_value(&self, name: &str) -> Option<u16> {
+        if self.global_names.contains(name) {
+            return None;
+        }
+        self.function_named(name)
+    }
+
+    /// Register a local variable in the current function scope, or a
+    /// top-level variable outside any function
     fn register_local(&mut self, name: &str) {
-        if let Some(func) = self.function_stack.last_mut() {
-            func.add_local(name.to_string());
+        match self.function_stack.last_mut() {
+            Some(func) => func.add_local(name.to_string()),
+            None => {
+                self.global_names.insert(name.to_string());
+            }
         }
     }
 
@@ -1449,6 +3637,10 @@ impl<'a> Compiler<'a> {
         std::mem::discriminant(&self.tokens[self.current + 1].kind) == std::mem::discriminant(&kind)
     }
 
+    fn check_next_symbol(&self, meaning: SymbolMeaning) -> bool {
+        self.tokens.get(self.current + 1).is_some_and(|t| t.is_symbol(meaning))
+    }
+
     fn match_token(&mut self, kind: TokenKind) -> bool {
         if self.check(kind) {
             self.advance();
@@ -1550,6 +3742,9 @@ impl<'a> Compiler<'a> {
             TokenKind::Symbol(SymbolMeaning::TypeRune) => Ok(ValueType::Rune),
             TokenKind::Symbol(SymbolMeaning::TypeArray) => Ok(ValueType::Array),
             TokenKind::Symbol(SymbolMeaning::TypeMap) => Ok(ValueType::Map),
+            TokenKind::Symbol(SymbolMeaning::TypeStruct) => Ok(ValueType::Struct),
+            TokenKind::Symbol(SymbolMeaning::TypeEnum) => Ok(ValueType::Enum),
+            TokenKind::Symbol(SymbolMeaning::TypeIterator) => Ok(ValueType::Iterator),
             _ => {
                 let context = self.get_source_context(token.location.line);
                 Err(CompileError::UnexpectedToken {
@@ -1596,6 +3791,11 @@ mod tests {
         compiler.compile(source)
     }
 
+    /// Compile declarations followed by a body, closed with ❧
+    fn compile_with(header: &str, body: &str) -> CompileResult<Chunk> {
+        compile_source(&format!("{}{}\n❧", header, body))
+    }
+
     #[test]
     fn test_simple_program() {
         let result = compile_source("⟁x=5\n⚡[x]\n❧");
@@ -1607,4 +3807,229 @@ mod tests {
         let result = compile_source("⟁x=5\n⚡[x]");
         assert!(matches!(result, Err(CompileError::MissingEndProgram)));
     }
+
+    #[test]
+    fn test_interpolation() {
+        let chunk = compile_source("⟁n=1\n✤\"n={n✚1:>3} {{n}}\"\n❧").unwrap();
+        assert!(chunk.strings.contains(&"n={0:>3} {{n}}".to_string()));
+
+        let result = compile_source("✤\"broken {n\"\n❧");
+        assert!(matches!(result, Err(CompileError::TemplateError { line: 1, .. })));
+
+        let result = compile_source("⟁n=1\n✤\"{n n}\"\n❧");
+        assert!(matches!(result, Err(CompileError::UnexpectedToken { line: 2, .. })));
+    }
+
+    #[test]
+    fn test_spell_stack_checking() {
+        assert!(compile_source("⇑[1]\n⇑[2]\n⇆\n⟁x=⇓ ✚ ⇓\n❧").is_ok());
+
+        let result = compile_source("⇑[1]\n↻\n❧");
+        assert!(matches!(result, Err(CompileError::StackUnderflow { line: 2, needed: 3, available: 1, .. })));
+
+        let result = compile_source("⟁x=⇓\n❧");
+        assert!(matches!(result, Err(CompileError::StackUnderflow { line: 1, needed: 1, .. })));
+
+        let result = compile_source("⊂[◉]\n⇑[1]\n⊃\n❧");
+        assert!(matches!(result, Err(CompileError::StackImbalance { expected: 0, found: 1, .. })));
+
+        let result = compile_source("⟨◉]\n⇑[1]\n⟩\n⇑[2]\n⟫\n⟁x=⇓\n❧");
+        assert!(result.is_ok());
+
+        let result = compile_source("λf[]\n⇑[1]\nΛ\n❧");
+        assert!(matches!(result, Err(CompileError::StackImbalance { expected: 0, found: 1, .. })));
+    }
+
+    #[test]
+    fn test_outer_accumulator_reach() {
+        assert!(compile_source("⊂[◉]\n⊂[◉]\n✹[2]⊕\n⚡[✹[1]]\n⊃\n⊃\n❧").is_ok());
+
+        let result = compile_source("⊂[◉]\n⚡[✹[2]]\n⊃\n❧");
+        assert!(matches!(result, Err(CompileError::AccumulatorOutOfReach { depth: 2, loops: 1, line: 2 })));
+    }
+
+    #[test]
+    fn test_loop_labels() {
+        assert!(compile_source("⊂a[◉]\n⊂b[◉]\n⊗[a]\n↺[b]\n⊃\n⊃\n❧").is_ok());
+
+        let result = compile_source("⊂a[◉]\n⊗[b]\n⊃\n❧");
+        assert!(matches!(result, Err(CompileError::UnknownLabel { line: 2, .. })));
+
+        let result = compile_source("⊂a[◉]\n⊂a[◉]\n⊃\n⊃\n❧");
+        assert!(matches!(result, Err(CompileError::DuplicateLabel { line: 2, .. })));
+
+        // Loops around a function definition are out of reach inside it
+        assert!(compile_source("⊂a[◉]\nλf[]\n⊗[a]\nΛ\n⊃\n❧").is_err());
+    }
+
+    #[test]
+    fn test_finally_cannot_be_left() {
+        let result = compile_source("⊂[◉]\n☄\n☋\n⊗\n⟣\n⊃\n❧");
+        assert!(matches!(result, Err(CompileError::LeavesFinally { line: 4, .. })));
+
+        let result = compile_source("λf[]\n☄\n☋\n⤶[1]\n⟣\nΛ\n❧");
+        assert!(matches!(result, Err(CompileError::LeavesFinally { line: 4, .. })));
+
+        // Loops inside the finally block are its own business
+        assert!(compile_source("☄\n☋\n⊂[◉]\n⊗\n⊃\n⟣\n❧").is_ok());
+    }
+
+    #[test]
+    fn test_rethrow_needs_catch() {
+        let result = compile_source("☄\n⚠\n☊\n⟣\n❧");
+        assert!(matches!(result, Err(CompileError::RethrowOutsideCatch { line: 2 })));
+
+        // A function defined in a ☊ block has no exception of its own
+        let result = compile_source("☄\n☊\nλf[]\n⚠\nΛ\n⟣\n❧");
+        assert!(matches!(result, Err(CompileError::RethrowOutsideCatch { line: 4 })));
+
+        assert!(compile_source("☄\n☊[e ∈ DivisionByZero, IoError]\n⚠\n☊[◇ ⟢ \"x\"]\n⟣\n❧").is_ok());
+    }
+
+    #[test]
+    fn test_non_exhaustive_match_warns() {
+        let table = SymbolTable::new();
+        let mut compiler = Compiler::new(&table);
+        compiler.compile("⟁x=1\n⟡x]\n⟢1]\n⟢2‥5]\n⟣\n⟡x]\n⟢≔y]\n⟣\n⟡x]\n⟢◇ ⟨x ▷ 0]\n⟣\n❧").unwrap();
+
+        // A bare binding covers everything; a guarded ◇ does not
+        assert_eq!(compiler.warnings(), &[
+            CompileWarning::NonExhaustiveMatch { line: 2 },
+            CompileWarning::NonExhaustiveMatch { line: 9 },
+        ]);
+    }
+
+    #[test]
+    fn test_value_arms_are_type_checked() {
+        let result = compile_source("⟁c=1\n⟁x=⟡c]\n⟢1] \"one\"\n⟢◇] 2 ✚ 3\n⟣\n❧");
+        assert!(matches!(result, Err(CompileError::ArmTypeMismatch {
+            line: 4, expected: ValueType::String, found: ValueType::Integer, ..
+        })));
+
+        let result = compile_source("⟁x=⟨◉] ⧆[\"1.5\"] ⟩ ◎ ⟫\n❧");
+        assert!(matches!(result, Err(CompileError::ArmTypeMismatch { found: ValueType::Boolean, .. })));
+
+        // Arms of unknown type are left to the runtime
+        assert!(compile_source("⟁c=1\n⟁x=⟡c] ⟢1] c ⟢◇] \"many\" ⟣\n❧").is_ok());
+
+        let result = compile_source("⟁c=1\n⟁x=⟡c] ⟢1] 1 ⟢⟁n ⟨n ▷ 1] n ⟣\n❧");
+        assert!(matches!(result, Err(CompileError::NonExhaustiveMatchValue { line: 2 })));
+    }
+
+    #[test]
+    fn test_user_type_errors() {
+        assert!(matches!(compile_source("⌻P[⟁x]\n⎊P[A]\n❧"), Err(CompileError::DuplicateType { line: 2, .. })));
+        assert!(matches!(compile_source("⌻P[⟁x, ⌘x]\n❧"), Err(CompileError::DuplicateField { .. })));
+        assert!(matches!(compile_source("⌻P[Q q]\n❧"), Err(CompileError::UnknownType { .. })));
+        assert!(matches!(compile_source("⌻P[⟁x, ⟁y]\n⚡[P[1]]\n❧"), Err(CompileError::WrongFieldCount { expected: 2, got: 1, .. })));
+        assert!(matches!(compile_source("⎊S[A, B]\n⚡[S⊷C]\n❧"), Err(CompileError::UnknownVariant { .. })));
+
+        // Recursive types may name themselves
+        assert!(compile_source("⎊L[Cons[⟁head, L tail], Nil]\n⚡[L⊷Cons[1, L⊷Nil]]\n❧").is_ok());
+    }
+
+    #[test]
+    fn test_trait_implementations_are_checked() {
+        let header = "⌻P[⟁x]\n⍟Show[show[], pad[⟁n]]\n";
+
+        assert!(compile_with(header, "⍜Show[P]\nλshow[⌻self]\n⤶[\"p\"]\nΛ\nλpad[⌻self, ⟁n]\n⤶[n]\nΛ\n⟣\n⚡[P[1]⊷pad[2]]").is_ok());
+        assert!(matches!(compile_with(header, "⍜Show[P]\nλshow[⌻self]\n⤶[\"p\"]\nΛ\n⟣"),
+            Err(CompileError::MissingMethod { method, line: 3, .. }) if method == "pad"));
+        assert!(matches!(compile_with(header, "⍜Show[P]\nλshow[⌻self]\nΛ\nλpad[⌻self, ⌘n]\nΛ\n⟣"),
+            Err(CompileError::MethodSignatureMismatch { .. })));
+        assert!(matches!(compile_with(header, "⍜Show[P]\nλshow[⟁self]\nΛ\nλpad[⌻self, ⟁n]\nΛ\n⟣"),
+            Err(CompileError::MethodSignatureMismatch { expected, found, .. }) if expected == "[⌻self]" && found == "[⟁self]"));
+        assert!(matches!(compile_with(header, "⍜Show[⟁]\nλshow[⌘self]\nΛ\nλpad[⟁self, ⟁n]\nΛ\n⟣"),
+            Err(CompileError::MethodSignatureMismatch { .. })));
+        assert!(matches!(compile_with(header, "⍜Show[P]\nλhide[⌻self]\nΛ\n⟣"), Err(CompileError::ForeignMethod { .. })));
+        assert!(matches!(compile_with(header, "⍜Draw[P]\n⟣"), Err(CompileError::UnknownTrait { .. })));
+        assert!(matches!(compile_with(header, "⍟Other[show[]]"), Err(CompileError::DuplicateMethod { .. })));
+        assert!(matches!(compile_with(header, "⍜Show[⟁]\nλshow[⟁self]\nΛ\nλpad[⟁self, ⟁n]\nΛ\n⟣\n⍜Show[⟁]\n⟣"),
+            Err(CompileError::DuplicateImpl { .. })));
+        assert!(matches!(compile_with(header, "⚡[P[1]⊷pad[]]"), Err(CompileError::WrongArity { expected: 1, got: 0, .. })));
+        assert!(matches!(compile_with(header, "⚡[P[1]⊷grow[]]"), Err(CompileError::UnknownMethod { .. })));
+    }
+
+    #[test]
+    fn test_partial_application_arity() {
+        let header = "λadd[⟁a, ⟁b]\n⤶[a✚b]\nΛ\n";
+
+        assert!(compile_with(header, "⟁inc=add⋊[1]\n⟁two=add⋊[1]⋊[1]").is_ok());
+        assert!(matches!(compile_with(header, "⟁f=add⋊[1, 2, 3]"), Err(CompileError::WrongArity { expected: 2, got: 3, .. })));
+        assert!(matches!(compile_with(header, "⟁f=add⋊[1]⋊[2, 3]"), Err(CompileError::WrongArity { expected: 1, got: 2, .. })));
+    }
+
+    #[test]
+    fn test_call_arguments_are_checked() {
+        let header = "λgreet[⌘name, ⌘greeting=\"Hello\", ⟁times=1]\n⤶[name]\nΛ\nλsum[⟁first, ‥⌬rest]\n⤶[first]\nΛ\n";
+
+        assert!(compile_with(header, "⤷greet[\"a\"]\n⤷greet[\"a\", \"b\", 2]\n⤷greet[times=2, name=\"a\"]\n⤷sum[1, 2, 3]").is_ok());
+        assert!(matches!(compile_with(header, "⤷greet[]"), Err(CompileError::WrongArity { expected: 1, got: 0, .. })));
+        assert!(matches!(compile_with(header, "⤷greet[\"a\", \"b\", 2, 3]"), Err(CompileError::WrongArity { expected: 3, got: 4, .. })));
+        assert!(matches!(compile_with(header, "⤷greet[times=2]"), Err(CompileError::WrongArity { expected: 1, got: 1, .. })));
+        assert!(matches!(compile_with(header, "⤷greet[\"a\", colour=\"red\"]"), Err(CompileError::UnknownArgument { .. })));
+        assert!(matches!(compile_with(header, "⤷greet[\"a\", name=\"b\"]"), Err(CompileError::DuplicateArgument { .. })));
+        assert!(matches!(compile_with(header, "⤷greet[name=\"a\", 2]"), Err(CompileError::UnexpectedToken { .. })));
+        assert!(matches!(compile_with(header, "⤷len[xs=1]"), Err(CompileError::UnknownArgument { .. })));
+
+        assert!(matches!(compile_source("λf[⟁a=1, ⟁b]\nΛ\n❧"), Err(CompileError::RequiredAfterDefault { .. })));
+        assert!(matches!(compile_source("λf[‥⌬rest, ⟁b]\nΛ\n❧"), Err(CompileError::MisplacedVariadic { .. })));
+        assert!(matches!(compile_source("λf[‥⟁rest]\nΛ\n❧"), Err(CompileError::MisplacedVariadic { .. })));
+    }
+
+    #[test]
+    fn test_functions_are_declared_ahead() {
+        let chunk = compile_source("⟁n=⤷twice[2]\nλtwice[⟁x, ⟁by=2]\n⤶[x✱by]\nΛ\n❧").unwrap();
+        assert_eq!(chunk.functions.len(), 1);
+        assert_eq!(chunk.functions[0].name, "twice");
+        assert!(chunk.functions[0].length > 0);
+
+        assert!(matches!(compile_source("⟁n=⤷twice[1, 2, 3]\nλtwice[⟁x, ⟁by=2]\nΛ\n❧"),
+            Err(CompileError::WrongArity { expected: 2, got: 3, .. })));
+        assert!(matches!(compile_source("⟁n=⤷twcie[2]\nλtwice[⟁x]\nΛ\n❧"),
+            Err(CompileError::UndefinedFunction { line: 1, .. })));
+        assert!(matches!(compile_source("⟁n=1\nλf[]\nΛ\nλf[]\nΛ\n❧"), Err(CompileError::DuplicateFunction { .. })));
+
+        // A name bound anywhere may hold a function, so the call goes through the value
+        assert!(compile_source("λapply[⟁g, ⟁x]\n⤶[⤷g[x]]\nΛ\n❧").is_ok());
+    }
+
+    #[test]
+    fn test_returned_calls_become_tail_calls() {
+        let ops = |source: &str| {
+            let chunk = compile_source(source).unwrap();
+            let mut offset = 0;
+            let mut ops = Vec::new();
+            while offset < chunk.code.len() {
+                let op: OpCode = unsafe { std::mem::transmute(chunk.code[offset]) };
+                offset += 1 + op.operand_count();
+                ops.push(op);
+            }
+            ops
+        };
+
+        let tail = ops("λdown[⟁n]\n⟨n ⩵ 0]\n⤶[0]\n⟫\n⤶[⤷down[n ☠︎ 1]]\nΛ\n❧");
+        assert!(tail.contains(&OpCode::TailCall) && !tail.contains(&OpCode::Call));
+        let closure = ops("λapply[⟁g, ⟁x]\n⤶[⤷g[x]]\nΛ\n❧");
+        assert!(closure.contains(&OpCode::TailCallClosure));
+
+        // The result is still worked on, or a handler is waiting for the call
+        let added = ops("λf[⟁n]\n⤶[⤷f[n] ✚ 1]\nΛ\n❧");
+        assert!(added.contains(&OpCode::Call) && !added.contains(&OpCode::TailCall));
+        let guarded = ops("λf[⟁n]\n☄\n⤶[⤷f[n]]\n☊[e]\n⟣\n⤶[0]\nΛ\n❧");
+        assert!(guarded.contains(&OpCode::Call) && !guarded.contains(&OpCode::TailCall));
+        let looped = ops("λf[⟁n]\n⊂[◉]\n⤶[⤷f[n]]\n⊃\nΛ\n❧");
+        assert!(looped.contains(&OpCode::Call) && !looped.contains(&OpCode::TailCall));
+    }
+
+    #[test]
+    fn test_yield_makes_a_generator() {
+        let chunk = compile_source("λgen[]\n⊂[i ∈ 0‥3]\n⌭[i]\n⊃\nΛ\nλplain[]\n⌭it=⤷gen[]\n⤶[it]\nΛ\n❧").unwrap();
+        assert!(chunk.functions[0].generator);
+        assert!(!chunk.functions[1].generator);
+
+        assert!(matches!(compile_source("⌭[1]\n❧"), Err(CompileError::YieldOutsideFunction { line: 1 })));
+        assert!(matches!(compile_source("λgen[]\n☄\n⌭[1]\n☊\n⟣\nΛ\n❧"), Err(CompileError::YieldInTry { line: 3 })));
+        assert!(matches!(compile_source("λgen[]\n☄\n☋\n⌭[1]\n⟣\nΛ\n❧"), Err(CompileError::YieldInTry { line: 4 })));
+    }
 }
@@ -0,0 +1,345 @@
+//! # String Interpolation
+//!
+//! Templates such as `✤"Hello {name}, you have {count✚1} points"` are split
+//! by the compiler into literal text and expression holes. Each hole is
+//! compiled as an ordinary expression and the template is rewritten to
+//! positional form (`"Hello {0}, you have {1} points"`), which the
+//! `Interpolate` instruction renders with the values left on the stack.
+//!
+//! ## Format Specifiers
+//!
+//! A hole may carry a specifier after a colon, modelled on Rust's:
+//!
+//! ```text
+//! {expr:[[fill]align][0][width][.precision]}
+//! ```
+//!
+//! - `align` is `<` (left), `>` (right) or `^` (center)
+//! - `0` pads numbers with zeros after the sign
+//! - `precision` is the number of decimals for numbers, or the maximum
+//!   number of runes for anything else
+//!
+//! Width and precision are at most 1024. Literal braces are written `{{`
+//! and `}}`.
+
+use crate::bytecode::Value;
+use thiserror::Error;
+
+/// Largest width or precision a specifier may ask for
+pub const MAX_SPEC_SIZE: usize = 1024;
+
+/// Template errors
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum TemplateError {
+    #[error("Unclosed '{{' in template")]
+    UnclosedHole,
+
+    #[error("Unmatched '}}' in template (write '}}}}' for a literal brace)")]
+    UnmatchedBrace,
+
+    #[error("Empty '{{}}' in template - a hole needs an expression")]
+    EmptyHole,
+
+    #[error("Invalid format specifier '{0}'")]
+    InvalidSpec(String),
+
+    #[error("Format specifier '{0}' is wider or more precise than {MAX_SPEC_SIZE}")]
+    SpecTooLarge(String),
+
+    #[error("Template refers to value {{{0}}} which was not provided")]
+    MissingValue(usize),
+}
+
+/// A piece of a source template
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    /// Literal text (braces already unescaped)
+    Text(String),
+    /// An expression hole with its optional format specifier
+    Hole { source: String, spec: Option<String> },
+}
+
+/// Split a source template into text and expression holes
+pub fn parse_template(template: &str) -> Result<Vec<Segment>, TemplateError> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => return Err(TemplateError::UnmatchedBrace),
+            '{' => {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(scan_hole(&mut chars)?);
+            }
+            _ => text.push(c),
+        }
+    }
+
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+/// Scan a hole body up to its closing brace, skipping nested braces and strings
+fn scan_hole(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Result<Segment, TemplateError> {
+    let mut source = String::new();
+    let mut spec = None;
+    let mut depth = 0usize;
+    let mut in_string = false;
+
+    loop {
+        let c = chars.next().ok_or(TemplateError::UnclosedHole)?;
+        match c {
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string && depth == 0 => break,
+            '}' if !in_string => depth -= 1,
+            ':' if !in_string && depth == 0 && spec.is_none() => {
+                spec = Some(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        match &mut spec {
+            Some(spec) => spec.push(c),
+            None => source.push(c),
+        }
+    }
+
+    if source.trim().is_empty() {
+        return Err(TemplateError::EmptyHole);
+    }
+    if let Some(spec) = &spec {
+        FormatSpec::parse(spec)?;
+    }
+    Ok(Segment::Hole { source: source.trim().to_string(), spec })
+}
+
+/// Escape literal text for use in a positional template
+pub fn escape_text(text: &str) -> String {
+    text.replace('{', "{{").replace('}', "}}")
+}
+
+/// Render a positional template (`{0}`, `{1:>8.2}`, ...) with values
+pub fn render(template: &str, values: &[Value]) -> Result<String, TemplateError> {
+    let mut result = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '}' => return Err(TemplateError::UnmatchedBrace),
+            '{' => {
+                let mut hole = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => hole.push(c),
+                        None => return Err(TemplateError::UnclosedHole),
+                    }
+                }
+
+                let (index, spec) = match hole.split_once(':') {
+                    Some((index, spec)) => (index, FormatSpec::parse(spec)?),
+                    None => (hole.as_str(), FormatSpec::default()),
+                };
+                let index: usize = index.parse()
+                    .map_err(|_| TemplateError::InvalidSpec(hole.clone()))?;
+                let value = values.get(index).ok_or(TemplateError::MissingValue(index))?;
+                result.push_str(&spec.apply(value));
+            }
+            _ => result.push(c),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Horizontal alignment within the field width
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// A parsed format specifier
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatSpec {
+    pub fill: char,
+    pub align: Option<Align>,
+    pub zero_pad: bool,
+    pub width: usize,
+    pub precision: Option<usize>,
+}
+
+impl Default for FormatSpec {
+    fn default() -> Self {
+        Self { fill: ' ', align: None, zero_pad: false, width: 0, precision: None }
+    }
+}
+
+impl FormatSpec {
+    /// Parse `[[fill]align][0][width][.precision]`
+    pub fn parse(spec: &str) -> Result<Self, TemplateError> {
+        let invalid = || TemplateError::InvalidSpec(spec.to_string());
+        let size = |digits: &[char]| {
+            digits.iter().collect::<String>().parse().ok()
+                .filter(|&n| n <= MAX_SPEC_SIZE)
+                .ok_or_else(|| TemplateError::SpecTooLarge(spec.to_string()))
+        };
+        let mut result = FormatSpec::default();
+        let chars: Vec<char> = spec.chars().collect();
+        let mut i = 0;
+
+        // Fill and alignment: the fill is only present when followed by an align char
+        if chars.len() >= 2 && align_of(chars[1]).is_some() {
+            result.fill = chars[0];
+            result.align = align_of(chars[1]);
+            i = 2;
+        } else if let Some(align) = chars.first().copied().and_then(align_of) {
+            result.align = Some(align);
+            i = 1;
+        }
+
+        if chars.get(i) == Some(&'0') {
+            result.zero_pad = true;
+            i += 1;
+        }
+
+        let digits = |from: usize| chars[from..].iter().take_while(|c| c.is_ascii_digit()).count();
+
+        let width_len = digits(i);
+        if width_len > 0 {
+            result.width = size(&chars[i..i + width_len])?;
+            i += width_len;
+        }
+
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            let precision_len = digits(i);
+            if precision_len == 0 {
+                return Err(invalid());
+            }
+            result.precision = Some(size(&chars[i..i + precision_len])?);
+            i += precision_len;
+        }
+
+        if i != chars.len() {
+            return Err(invalid());
+        }
+        Ok(result)
+    }
+
+    /// Format a value according to this specifier
+    pub fn apply(&self, value: &Value) -> String {
+        let numeric = matches!(value, Value::Integer(_) | Value::Real(_));
+
+        let text = match (value, self.precision) {
+            (Value::Integer(i), Some(0)) => i.to_string(),
+            (Value::Integer(i), Some(p)) => format!("{}.{}", i, "0".repeat(p)),
+            (Value::Real(r), Some(p)) => format!("{:.*}", p, r),
+            (_, Some(p)) if !numeric => value.to_string_value().chars().take(p).collect(),
+            _ => value.to_string_value(),
+        };
+
+        let len = text.chars().count();
+        if len >= self.width {
+            return text;
+        }
+        let padding = self.width - len;
+
+        // Zero padding goes between the sign and the digits
+        if self.zero_pad && numeric && self.align.is_none() {
+            let (sign, digits) = match text.strip_prefix('-') {
+                Some(rest) => ("-", rest),
+                None => ("", text.as_str()),
+            };
+            return format!("{}{}{}", sign, "0".repeat(padding), digits);
+        }
+
+        let fill = |n: usize| self.fill.to_string().repeat(n);
+        let default_align = if numeric { Align::Right } else { Align::Left };
+        match self.align.unwrap_or(default_align) {
+            Align::Left => format!("{}{}", text, fill(padding)),
+            Align::Right => format!("{}{}", fill(padding), text),
+            Align::Center => {
+                let left = padding / 2;
+                format!("{}{}{}", fill(left), text, fill(padding - left))
+            }
+        }
+    }
+}
+
+fn align_of(c: char) -> Option<Align> {
+    match c {
+        '<' => Some(Align::Left),
+        '>' => Some(Align::Right),
+        '^' => Some(Align::Center),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template() {
+        let segments = parse_template("Hi {name}, {{x}} {count✚1:>5}").unwrap();
+        assert_eq!(segments, vec![
+            Segment::Text("Hi ".to_string()),
+            Segment::Hole { source: "name".to_string(), spec: None },
+            Segment::Text(", {x} ".to_string()),
+            Segment::Hole { source: "count✚1".to_string(), spec: Some(">5".to_string()) },
+        ]);
+
+        assert_eq!(parse_template("oops {"), Err(TemplateError::UnclosedHole));
+        assert_eq!(parse_template("oops }"), Err(TemplateError::UnmatchedBrace));
+        assert_eq!(parse_template("{ }"), Err(TemplateError::EmptyHole));
+        assert!(matches!(parse_template("{x:??}"), Err(TemplateError::InvalidSpec(_))));
+        assert!(parse_template("{x:1024.1024}").is_ok());
+        assert!(matches!(parse_template("{x:999999999}"), Err(TemplateError::SpecTooLarge(_))));
+        assert!(matches!(parse_template("{x:.1025}"), Err(TemplateError::SpecTooLarge(_))));
+    }
+
+    #[test]
+    fn test_format_spec() {
+        let fmt = |spec: &str, value: Value| FormatSpec::parse(spec).unwrap().apply(&value);
+
+        assert_eq!(fmt("5", Value::Integer(42)), "   42");
+        assert_eq!(fmt("5", Value::String("ab".to_string())), "ab   ");
+        assert_eq!(fmt("05", Value::Integer(-42)), "-0042");
+        assert_eq!(fmt(".2", Value::Real(2.5)), "2.50");
+        assert_eq!(fmt(".2", Value::Integer(9007199254740993)), "9007199254740993.00");
+        assert_eq!(fmt(".0", Value::Integer(-7)), "-7");
+        assert_eq!(fmt("*^7.1", Value::Real(1.26)), "**1.3**");
+        assert_eq!(fmt(".3", Value::String("wizardry".to_string())), "wiz");
+    }
+
+    #[test]
+    fn test_render() {
+        let values = [Value::String("Merlin".to_string()), Value::Integer(7)];
+        assert_eq!(render("{0} has {1:03} {{spells}}", &values).unwrap(), "Merlin has 007 {spells}");
+        assert_eq!(render("{2}", &values), Err(TemplateError::MissingValue(2)));
+    }
+}
@@ -189,6 +189,7 @@ impl<'a> Lexer<'a> {
         }
 
         // Number literal
+        #[allow(clippy::unnecessary_map_or)]
         if c.is_ascii_digit() || (c == '-' && self.peek_second_char().map_or(false, |c| c.is_ascii_digit())) {
             return self.scan_number();
         }
@@ -538,6 +539,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_float_number() {
         let tokens = lex("3.14159").unwrap();
         assert!(matches!(tokens[0].kind, TokenKind::Float(f) if (f - 3.14159).abs() < 0.00001));
@@ -39,6 +39,8 @@ pub mod source_map;
 pub mod optimizer;
 pub mod modules;
 pub mod serialize;
+pub mod stdlib;
+pub mod interpolate;
 
 use clap::{Parser, Subcommand};
 use colored::*;
@@ -48,7 +50,7 @@ use std::path::PathBuf;
 
 use crate::compiler::Compiler;
 use crate::symbols::SymbolTable;
-use crate::vm::Runtime;
+use crate::vm::{Runtime, RuntimeError};
 use crate::serialize::BytecodeSerializer;
 
 /// Obfusku - The Magical Programming Language
@@ -72,6 +74,14 @@ enum Commands {
         /// Enable debug mode
         #[arg(short, long)]
         debug: bool,
+
+        /// Only allow file access inside this directory (repeatable)
+        #[arg(long = "allow-dir", value_name = "DIR")]
+        allow_dir: Vec<PathBuf>,
+
+        /// Arguments passed to the spell (after --)
+        #[arg(last = true)]
+        args: Vec<String>,
     },
 
     /// Compile a spell and show the bytecode
@@ -96,6 +106,14 @@ enum Commands {
         /// Enable debug mode
         #[arg(short, long)]
         debug: bool,
+
+        /// Only allow file access inside this directory (repeatable)
+        #[arg(long = "allow-dir", value_name = "DIR")]
+        allow_dir: Vec<PathBuf>,
+
+        /// Arguments passed to the spell (after --)
+        #[arg(last = true)]
+        args: Vec<String>,
     },
 
     /// Run Obfusku in interactive REPL mode
@@ -111,16 +129,27 @@ enum Commands {
         #[arg(short, long)]
         category: Option<String>,
     },
+
+    /// Show the builtin functions of the standard library
+    Builtins {
+        /// Filter by category (math, string, collection, conversion, file, system, exception, iterator)
+        #[arg(short, long)]
+        category: Option<String>,
+    },
 }
 
 fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Run { file, debug } => {
-            if let Err(e) = run_file(&file, debug) {
-                print_error(&e.to_string());
-                std::process::exit(1);
+        Commands::Run { file, debug, allow_dir, args } => {
+            match run_file(&file, debug, &allow_dir, args) {
+                Ok(0) => {}
+                Ok(code) => std::process::exit(code),
+                Err(e) => {
+                    report_error(&*e);
+                    std::process::exit(1);
+                }
             }
         }
 
@@ -131,10 +160,14 @@ fn main() {
             }
         }
 
-        Commands::Load { file, debug } => {
-            if let Err(e) = load_file(&file, debug) {
-                print_error(&e.to_string());
-                std::process::exit(1);
+        Commands::Load { file, debug, allow_dir, args } => {
+            match load_file(&file, debug, &allow_dir, args) {
+                Ok(0) => {}
+                Ok(code) => std::process::exit(code),
+                Err(e) => {
+                    report_error(&*e);
+                    std::process::exit(1);
+                }
             }
         }
 
@@ -148,11 +181,15 @@ fn main() {
         Commands::Symbols { category } => {
             show_symbols(category);
         }
+
+        Commands::Builtins { category } => {
+            show_builtins(category);
+        }
     }
 }
 
 /// Run an Obfusku file
-fn run_file(path: &PathBuf, debug: bool) -> Result<(), Box<dyn std::error::Error>> {
+fn run_file(path: &PathBuf, debug: bool, allow_dirs: &[PathBuf], args: Vec<String>) -> Result<i32, Box<dyn std::error::Error>> {
     // Check file extension
     if path.extension().map(|e| e.to_str()) != Some(Some("obk")) {
         print_warning("File does not have .obk extension - proceeding anyway");
@@ -167,6 +204,7 @@ fn run_file(path: &PathBuf, debug: bool) -> Result<(), Box<dyn std::error::Error
     let symbol_table = SymbolTable::new();
     let mut compiler = Compiler::new(&symbol_table);
     let chunk = compiler.compile(&source)?;
+    report_warnings(&compiler);
 
     if debug {
         println!("{}", chunk.disassemble());
@@ -175,11 +213,13 @@ fn run_file(path: &PathBuf, debug: bool) -> Result<(), Box<dyn std::error::Error
     // Execute
     let mut runtime = Runtime::new();
     runtime.set_debug(debug);
+    runtime.set_args(args);
+    for dir in allow_dirs {
+        runtime.allow_directory(dir)?;
+    }
     runtime.execute(chunk)?;
 
-    print_success("✨ Spell complete!");
-
-    Ok(())
+    finish_spell(runtime.exit_code())
 }
 
 /// Compile a file and optionally show bytecode
@@ -191,6 +231,7 @@ fn compile_file(path: &PathBuf, disassemble: bool, output: Option<PathBuf>) -> R
     let symbol_table = SymbolTable::new();
     let mut compiler = Compiler::new(&symbol_table);
     let chunk = compiler.compile(&source)?;
+    report_warnings(&compiler);
 
     print_success(&format!("✅ Compilation successful! ({} bytes of bytecode)", chunk.code.len()));
 
@@ -209,7 +250,7 @@ fn compile_file(path: &PathBuf, disassemble: bool, output: Option<PathBuf>) -> R
 }
 
 /// Load and execute compiled bytecode
-fn load_file(path: &PathBuf, debug: bool) -> Result<(), Box<dyn std::error::Error>> {
+fn load_file(path: &PathBuf, debug: bool, allow_dirs: &[PathBuf], args: Vec<String>) -> Result<i32, Box<dyn std::error::Error>> {
     // Check file extension
     if path.extension().map(|e| e.to_str()) != Some(Some("obc")) {
         print_warning("File does not have .obc extension - proceeding anyway");
@@ -231,18 +272,20 @@ fn load_file(path: &PathBuf, debug: bool) -> Result<(), Box<dyn std::error::Erro
     // Execute
     let mut runtime = Runtime::new();
     runtime.set_debug(debug);
+    runtime.set_args(args);
+    for dir in allow_dirs {
+        runtime.allow_directory(dir)?;
+    }
     runtime.execute(chunk)?;
 
-    print_success("✨ Spell complete!");
-
-    Ok(())
+    finish_spell(runtime.exit_code())
 }
 
 /// Run the interactive REPL
 fn run_repl(debug: bool) -> Result<(), Box<dyn std::error::Error>> {
     print_header("🌙 Obfusku Interactive Grimoire v0.3.0");
     println!("{}", "Type your spells. End with ❧ to execute.".dimmed());
-    println!("{}", "Commands: :help, :symbols, :history, :clear, :quit".dimmed());
+    println!("{}", "Commands: :help, :symbols, :builtins, :history, :clear, :quit".dimmed());
     println!();
 
     let symbol_table = SymbolTable::new();
@@ -293,6 +336,13 @@ fn run_repl(debug: bool) -> Result<(), Box<dyn std::error::Error>> {
                         stdout.flush()?;
                         continue;
                     }
+                    ":builtins" | ":b" => {
+                        show_builtins(None);
+                        input.clear();
+                        print!("{} ", "⚗️ ".cyan());
+                        stdout.flush()?;
+                        continue;
+                    }
                     ":debug" | ":d" => {
                         debug_mode = !debug_mode;
                         runtime.set_debug(debug_mode);
@@ -383,12 +433,14 @@ fn run_repl(debug: bool) -> Result<(), Box<dyn std::error::Error>> {
         let mut compiler = Compiler::new(&symbol_table);
         match compiler.compile(&input) {
             Ok(chunk) => {
+                report_warnings(&compiler);
                 if debug_mode {
                     println!("{}", chunk.disassemble());
                 }
 
                 if let Err(e) = runtime.execute(chunk) {
                     print_error(&format!("Runtime error: {}", e));
+                    print_trace(&e);
                 }
             }
             Err(e) => {
@@ -467,6 +519,62 @@ fn show_symbols(category_filter: Option<String>) {
     println!("{}", "═══════════════════════════════════════════════════════".cyan());
 }
 
+/// Show information about builtin functions
+fn show_builtins(category_filter: Option<String>) {
+    use crate::stdlib::BuiltinCategory;
+
+    println!();
+    println!("{}", "═══════════════════════════════════════════════════════".cyan());
+    println!("{}", "            📚 OBFUSKU BUILTIN SPELLS                   ".cyan().bold());
+    println!("{}", "═══════════════════════════════════════════════════════".cyan());
+
+    let categories = if let Some(ref filter) = category_filter {
+        match filter.to_lowercase().as_str() {
+            "math" => vec![BuiltinCategory::Math],
+            "string" | "strings" | "text" => vec![BuiltinCategory::String],
+            "collection" | "collections" => vec![BuiltinCategory::Collection],
+            "conversion" | "convert" => vec![BuiltinCategory::Conversion],
+            "file" | "files" => vec![BuiltinCategory::File],
+            "system" | "sys" => vec![BuiltinCategory::System],
+            "exception" | "exceptions" => vec![BuiltinCategory::Exception],
+            "iterator" | "iterators" | "iter" => vec![BuiltinCategory::Iterator],
+            _ => {
+                println!("{}", format!("Unknown category: {}", filter).red());
+                println!("Available: math, string, collection, conversion, file, system, exception, iterator");
+                return;
+            }
+        }
+    } else {
+        vec![
+            BuiltinCategory::Math,
+            BuiltinCategory::String,
+            BuiltinCategory::Collection,
+            BuiltinCategory::Conversion,
+            BuiltinCategory::File,
+            BuiltinCategory::System,
+            BuiltinCategory::Exception,
+            BuiltinCategory::Iterator,
+        ]
+    };
+
+    for category in categories {
+        println!();
+        println!("{}", format!("  {}", category).yellow().bold());
+        println!("{}", "  ─────────────────────────────────────".dimmed());
+
+        for builtin in stdlib::in_category(category) {
+            println!(
+                "    {}  {}",
+                builtin.signature.cyan().bold(),
+                builtin.description.dimmed()
+            );
+        }
+    }
+
+    println!();
+    println!("{}", "═══════════════════════════════════════════════════════".cyan());
+}
+
 fn print_repl_help() {
     println!();
     println!("{}", "═══════════════════════════════════════════════════════".cyan());
@@ -476,6 +584,7 @@ fn print_repl_help() {
     println!("{}", "Commands:".yellow());
     println!("  {}  - Show this help", ":help, :h".green());
     println!("  {}  - List all symbols", ":symbols, :s".green());
+    println!("  {}  - List builtin functions", ":builtins, :b".green());
     println!("  {}  - Toggle debug mode", ":debug, :d".green());
     println!("  {}  - Show spell history", ":history, :hist".green());
     println!("  {}  - Recall spell #N from history", ":!N".green());
@@ -502,6 +611,40 @@ fn print_warning(msg: &str) {
     eprintln!("{}", format!("⚠️  {}", msg).yellow());
 }
 
+/// Print the warnings the compiler gathered
+fn report_warnings(compiler: &Compiler) {
+    for warning in compiler.warnings() {
+        print_warning(&warning.to_string());
+    }
+}
+
+/// Report how a spell ended and hand back its exit status
+fn finish_spell(exit_code: i32) -> Result<i32, Box<dyn std::error::Error>> {
+    if exit_code == 0 {
+        print_success("✨ Spell complete!");
+    } else {
+        print_warning(&format!("Spell ended with status {}", exit_code));
+    }
+
+    Ok(exit_code)
+}
+
 fn print_error(msg: &str) {
     eprintln!("{}", format!("❌ {}", msg).red());
 }
+
+/// Print an error, followed by the calls an uncaught exception was thrown from
+fn report_error(error: &(dyn std::error::Error + 'static)) {
+    print_error(&error.to_string());
+    if let Some(error) = error.downcast_ref::<RuntimeError>() {
+        print_trace(error);
+    }
+}
+
+fn print_trace(error: &RuntimeError) {
+    if let RuntimeError::UncaughtException { trace, .. } = error {
+        for frame in trace {
+            eprintln!("{}", format!("   ↳ in {}", frame).red().dimmed());
+        }
+    }
+}
@@ -3,7 +3,9 @@
 //! Allows saving compiled spells to disk and reloading them.
 //! The format is version-aware for future compatibility.
 
-use crate::bytecode::{Chunk, FunctionInfo, Value, ValueType};
+use crate::bytecode::{Chunk, Field, FieldType, FunctionInfo, MethodTable, OrderedMap, TypeDef, TypeKind, Upvalue, Value, ValueType, VariantDef};
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::io::{self, Read, Write, BufReader, BufWriter};
 use std::fs::File;
 use std::path::Path;
@@ -14,7 +16,7 @@ const MAGIC: &[u8; 4] = b"OBFK";
 
 /// Current bytecode format version
 const VERSION_MAJOR: u8 = 1;
-const VERSION_MINOR: u8 = 0;
+const VERSION_MINOR: u8 = 3;
 const VERSION_PATCH: u8 = 0;
 
 /// Serialization errors
@@ -34,6 +36,9 @@ pub enum SerializeError {
     
     #[error("📜 Unknown value type: {0}")]
     UnknownValueType(u8),
+
+    #[error("📜 Value of type {0} only exists at runtime and cannot be serialized")]
+    UnserializableValue(ValueType),
 }
 
 /// Bytecode file header
@@ -129,6 +134,23 @@ impl BytecodeSerializer {
         for func in &chunk.functions {
             Self::write_function(writer, func)?;
         }
+
+        // Write user types (since 1.1)
+        Self::write_u16(writer, chunk.types.len() as u16)?;
+        for def in &chunk.types {
+            Self::write_type(writer, def)?;
+        }
+
+        // Write trait method tables (since 1.1)
+        Self::write_u16(writer, chunk.method_tables.len() as u16)?;
+        for table in &chunk.method_tables {
+            Self::write_field_type(writer, table.target)?;
+            Self::write_u16(writer, table.methods.len() as u16)?;
+            for (name, func) in &table.methods {
+                Self::write_string(writer, name)?;
+                Self::write_u16(writer, *func)?;
+            }
+        }
         
         Ok(())
     }
@@ -192,9 +214,30 @@ impl BytecodeSerializer {
         // Read functions
         let func_count = Self::read_u16(reader)? as usize;
         for _ in 0..func_count {
-            let func = Self::read_function(reader)?;
+            let func = Self::read_function(reader, header.version_minor)?;
             chunk.functions.push(func);
         }
+
+        // Read user types; 1.0 files have none
+        if header.version_minor >= 1 {
+            let type_count = Self::read_u16(reader)? as usize;
+            for _ in 0..type_count {
+                let def = Self::read_type(reader)?;
+                chunk.add_type(def);
+            }
+
+            let table_count = Self::read_u16(reader)? as usize;
+            for _ in 0..table_count {
+                let target = Self::read_field_type(reader)?;
+                let method_count = Self::read_u16(reader)? as usize;
+                let mut methods = Vec::with_capacity(method_count);
+                for _ in 0..method_count {
+                    let name = Self::read_string(reader)?;
+                    methods.push((name, Self::read_u16(reader)?));
+                }
+                chunk.method_tables.push(MethodTable { target, methods });
+            }
+        }
         
         Ok(chunk)
     }
@@ -255,14 +298,14 @@ impl BytecodeSerializer {
             Value::Array(arr) => {
                 writer.write_all(&[ValueType::Array as u8])?;
                 Self::write_u16(writer, arr.len() as u16)?;
-                for v in arr {
+                for v in arr.iter() {
                     Self::write_value(writer, v)?;
                 }
             }
             Value::Map(map) => {
                 writer.write_all(&[ValueType::Map as u8])?;
                 Self::write_u16(writer, map.len() as u16)?;
-                for (k, v) in map {
+                for (k, v) in map.iter() {
                     Self::write_value(writer, k)?;
                     Self::write_value(writer, v)?;
                 }
@@ -271,18 +314,29 @@ impl BytecodeSerializer {
                 writer.write_all(&[ValueType::Function as u8])?;
                 Self::write_u16(writer, *idx as u16)?;
             }
+            // The format has no room for arguments bound with ⋊
+            Value::ClosureVal(c) if !c.bound.is_empty() => {
+                return Err(SerializeError::UnserializableValue(ValueType::Closure));
+            }
             Value::ClosureVal(c) => {
                 writer.write_all(&[ValueType::Closure as u8])?;
                 Self::write_u16(writer, c.function_index as u16)?;
                 Self::write_u16(writer, c.captures.len() as u16)?;
+                // Only closed upvalues hold a value of their own
                 for cap in &c.captures {
-                    Self::write_value(writer, cap)?;
+                    match &*cap.borrow() {
+                        Upvalue::Closed(value) => Self::write_value(writer, value)?,
+                        Upvalue::Open { .. } => return Err(SerializeError::UnserializableValue(ValueType::Closure)),
+                    }
                 }
             }
             Value::Module(idx) => {
                 writer.write_all(&[ValueType::Module as u8])?;
                 Self::write_u16(writer, *idx as u16)?;
             }
+            Value::FileHandle(_) | Value::Struct(_) | Value::Variant(_) | Value::Composed(_) | Value::Iterator(_) => {
+                return Err(SerializeError::UnserializableValue(value.value_type()));
+            }
         }
         Ok(())
     }
@@ -297,6 +351,56 @@ impl BytecodeSerializer {
         }
         Self::write_u32(writer, func.start as u32)?;
         Self::write_u32(writer, func.length as u32)?;
+
+        Self::write_u16(writer, func.capture_names.len() as u16)?;
+        for name in &func.capture_names {
+            Self::write_string(writer, name)?;
+        }
+        Self::write_u16(writer, func.defaults.len() as u16)?;
+        for value in &func.defaults {
+            Self::write_value(writer, value)?;
+        }
+        writer.write_all(&[func.variadic as u8])?;
+        writer.write_all(&[func.generator as u8])?;
+        Ok(())
+    }
+    
+    fn write_type<W: Write>(writer: &mut W, def: &TypeDef) -> Result<(), SerializeError> {
+        Self::write_string(writer, &def.name)?;
+        match &def.kind {
+            TypeKind::Struct(fields) => {
+                writer.write_all(&[0])?;
+                Self::write_fields(writer, fields)?;
+            }
+            TypeKind::Enum(variants) => {
+                writer.write_all(&[1])?;
+                Self::write_u16(writer, variants.len() as u16)?;
+                for variant in variants {
+                    Self::write_string(writer, &variant.name)?;
+                    Self::write_fields(writer, &variant.fields)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_fields<W: Write>(writer: &mut W, fields: &[Field]) -> Result<(), SerializeError> {
+        Self::write_u16(writer, fields.len() as u16)?;
+        for field in fields {
+            Self::write_string(writer, &field.name)?;
+            Self::write_field_type(writer, field.ty)?;
+        }
+        Ok(())
+    }
+
+    fn write_field_type<W: Write>(writer: &mut W, ty: FieldType) -> Result<(), SerializeError> {
+        match ty {
+            FieldType::Value(ty) => writer.write_all(&[0, ty as u8])?,
+            FieldType::User(index) => {
+                writer.write_all(&[1])?;
+                Self::write_u16(writer, index)?;
+            }
+        }
         Ok(())
     }
     
@@ -366,17 +470,17 @@ impl BytecodeSerializer {
                 for _ in 0..len {
                     arr.push(Self::read_value(reader)?);
                 }
-                Ok(Value::Array(arr))
+                Ok(Value::array(arr))
             }
             x if x == ValueType::Map as u8 => {
                 let len = Self::read_u16(reader)? as usize;
-                let mut map = Vec::with_capacity(len);
+                let mut map = OrderedMap::with_capacity(len);
                 for _ in 0..len {
                     let k = Self::read_value(reader)?;
                     let v = Self::read_value(reader)?;
-                    map.push((k, v));
+                    map.insert(k, v).map_err(|_| SerializeError::CorruptedData { offset: 0 })?;
                 }
-                Ok(Value::Map(map))
+                Ok(Value::map(map))
             }
             x if x == ValueType::Function as u8 => {
                 let idx = Self::read_u16(reader)? as usize;
@@ -387,7 +491,8 @@ impl BytecodeSerializer {
                 let cap_count = Self::read_u16(reader)? as usize;
                 let mut captures = Vec::with_capacity(cap_count);
                 for _ in 0..cap_count {
-                    captures.push(Self::read_value(reader)?);
+                    let value = Self::read_value(reader)?;
+                    captures.push(Rc::new(RefCell::new(Upvalue::Closed(value))));
                 }
                 Ok(Value::ClosureVal(Box::new(crate::bytecode::Closure::new(func_idx, captures))))
             }
@@ -399,7 +504,56 @@ impl BytecodeSerializer {
         }
     }
     
-    fn read_function<R: Read>(reader: &mut R) -> Result<FunctionInfo, SerializeError> {
+    fn read_type<R: Read>(reader: &mut R) -> Result<TypeDef, SerializeError> {
+        let name = Self::read_string(reader)?;
+        let mut kind = [0u8; 1];
+        reader.read_exact(&mut kind)?;
+        let kind = match kind[0] {
+            0 => TypeKind::Struct(Self::read_fields(reader)?),
+            1 => {
+                let count = Self::read_u16(reader)? as usize;
+                let mut variants = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let name = Self::read_string(reader)?;
+                    let fields = Self::read_fields(reader)?;
+                    variants.push(VariantDef { name, fields });
+                }
+                TypeKind::Enum(variants)
+            }
+            _ => return Err(SerializeError::CorruptedData { offset: 0 }),
+        };
+        Ok(TypeDef { name, kind })
+    }
+
+    fn read_fields<R: Read>(reader: &mut R) -> Result<Vec<Field>, SerializeError> {
+        let count = Self::read_u16(reader)? as usize;
+        let mut fields = Vec::with_capacity(count);
+        for _ in 0..count {
+            let name = Self::read_string(reader)?;
+            let ty = Self::read_field_type(reader)?;
+            fields.push(Field { name, ty });
+        }
+        Ok(fields)
+    }
+
+    fn read_field_type<R: Read>(reader: &mut R) -> Result<FieldType, SerializeError> {
+        let mut tag = [0u8; 1];
+        reader.read_exact(&mut tag)?;
+        match tag[0] {
+            0 => {
+                let mut type_byte = [0u8; 1];
+                reader.read_exact(&mut type_byte)?;
+                if type_byte[0] > ValueType::Iterator as u8 {
+                    return Err(SerializeError::UnknownValueType(type_byte[0]));
+                }
+                Ok(FieldType::Value(unsafe { std::mem::transmute::<u8, ValueType>(type_byte[0]) }))
+            }
+            1 => Ok(FieldType::User(Self::read_u16(reader)?)),
+            _ => Err(SerializeError::CorruptedData { offset: 0 }),
+        }
+    }
+
+    fn read_function<R: Read>(reader: &mut R, version_minor: u8) -> Result<FunctionInfo, SerializeError> {
         let name = Self::read_string(reader)?;
         let mut arity = [0u8; 1];
         reader.read_exact(&mut arity)?;
@@ -417,8 +571,29 @@ impl BytecodeSerializer {
         
         let start = Self::read_u32(reader)? as usize;
         let length = Self::read_u32(reader)? as usize;
-        
-        Ok(FunctionInfo::new(name, arity, params, start, length))
+        let mut func = FunctionInfo::new(name, arity, params, start, length);
+
+        // Captures, defaults and variadics arrived with 1.2
+        if version_minor >= 2 {
+            let capture_count = Self::read_u16(reader)? as usize;
+            for _ in 0..capture_count {
+                func.capture_names.push(Self::read_string(reader)?);
+            }
+            let default_count = Self::read_u16(reader)? as usize;
+            for _ in 0..default_count {
+                func.defaults.push(Self::read_value(reader)?);
+            }
+            let mut variadic = [0u8; 1];
+            reader.read_exact(&mut variadic)?;
+            func.variadic = variadic[0] != 0;
+        }
+        // Generators arrived with 1.3
+        if version_minor >= 3 {
+            let mut generator = [0u8; 1];
+            reader.read_exact(&mut generator)?;
+            func.generator = generator[0] != 0;
+        }
+        Ok(func)
     }
 }
 
@@ -446,4 +621,53 @@ mod tests {
         assert_eq!(loaded.strings.len(), 1);
         assert_eq!(loaded.code.len(), 4);
     }
+
+    #[test]
+    fn test_type_table_round_trip() {
+        let mut chunk = Chunk::new("types");
+        chunk.add_type(TypeDef {
+            name: "Shape".to_string(),
+            kind: TypeKind::Enum(vec![
+                VariantDef { name: "Circle".to_string(), fields: vec![Field { name: "r".to_string(), ty: FieldType::Value(ValueType::Real) }] },
+                VariantDef { name: "Nested".to_string(), fields: vec![Field { name: "inner".to_string(), ty: FieldType::User(0) }] },
+            ]),
+        });
+        chunk.add_method(FieldType::User(0), "area", 3);
+        chunk.add_method(FieldType::Value(ValueType::Integer), "area", 4);
+
+        let mut buffer = Vec::new();
+        BytecodeSerializer::serialize(&chunk, &mut buffer).unwrap();
+        let loaded = BytecodeSerializer::deserialize(&mut std::io::Cursor::new(buffer)).unwrap();
+
+        assert_eq!(loaded.types.len(), 1);
+        assert_eq!(*loaded.types[0], *chunk.types[0]);
+        assert_eq!(loaded.method_tables, chunk.method_tables);
+    }
+
+    #[test]
+    fn test_function_table_round_trip() {
+        let mut chunk = Chunk::new("functions");
+        let params = vec![
+            ("name".to_string(), ValueType::String),
+            ("times".to_string(), ValueType::Integer),
+            ("rest".to_string(), ValueType::Array),
+        ];
+        let mut func = FunctionInfo::new("greet".to_string(), 3, params, 4, 12);
+        func.capture_names = vec!["prefix".to_string()];
+        func.defaults = vec![Value::Integer(1)];
+        func.variadic = true;
+        func.generator = true;
+        chunk.add_function(func);
+
+        let mut buffer = Vec::new();
+        BytecodeSerializer::serialize(&chunk, &mut buffer).unwrap();
+        let loaded = BytecodeSerializer::deserialize(&mut std::io::Cursor::new(buffer)).unwrap();
+
+        let func = loaded.get_function(0).unwrap();
+        assert_eq!(func.capture_names, vec!["prefix".to_string()]);
+        assert_eq!(func.defaults, vec![Value::Integer(1)]);
+        assert!(func.variadic);
+        assert!(func.generator);
+        assert_eq!(func.required_params(), 1);
+    }
 }
@@ -0,0 +1,618 @@
+//! # Standard Library of Builtin Spells
+//!
+//! Builtins are functions provided by the VM itself rather than defined
+//! with `λ`. They are invoked with the ordinary call syntax (`⤷len[arr]`)
+//! and compiled to a `CallNative` instruction carrying the builtin's index.
+//!
+//! ## Design Philosophy
+//!
+//! - **Same ritual, different source**: calling a builtin looks exactly like
+//!   calling a user function, and a user function with the same name wins
+//! - **Checked at compile time**: arity is validated by the compiler
+//! - **Pure**: builtins never touch variables, they only consume arguments
+//!   and produce a value; file builtins reach outside the VM only through
+//!   the runtime's sandboxed file table
+
+use crate::bytecode::{IterHandle, IterSource, Value};
+use crate::vm::RuntimeError;
+use std::cmp::Ordering;
+use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Grouping used when listing builtins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinCategory {
+    /// Numeric helpers (abs, min, sqrt, ...)
+    Math,
+    /// Text helpers (substring, split, upper, ...)
+    String,
+    /// Array and map helpers (len, sort, range, ...)
+    Collection,
+    /// Explicit conversions between value types
+    Conversion,
+    /// File access through `FileHandle` values
+    File,
+    /// The world outside the spell: arguments and environment
+    System,
+    /// Structured exception values
+    Exception,
+    /// ⌭ iterators over collections and ranges
+    Iterator,
+}
+
+impl fmt::Display for BuiltinCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuiltinCategory::Math => write!(f, "Math"),
+            BuiltinCategory::String => write!(f, "String"),
+            BuiltinCategory::Collection => write!(f, "Collection"),
+            BuiltinCategory::Conversion => write!(f, "Conversion"),
+            BuiltinCategory::File => write!(f, "File"),
+            BuiltinCategory::System => write!(f, "System"),
+            BuiltinCategory::Exception => write!(f, "Exception"),
+            BuiltinCategory::Iterator => write!(f, "Iterator"),
+        }
+    }
+}
+
+/// Identity of a builtin, used by the runtime to dispatch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinId {
+    Abs,
+    Min,
+    Max,
+    Floor,
+    Ceil,
+    Sqrt,
+    Len,
+    Substring,
+    Split,
+    Join,
+    Upper,
+    Lower,
+    Contains,
+    Sort,
+    Reverse,
+    Range,
+    ToString,
+    ToInt,
+    ToReal,
+    Open,
+    ReadLine,
+    ReadAll,
+    Write,
+    Close,
+    Args,
+    Env,
+    Error,
+    Iter,
+    IterRange,
+    Next,
+    Collect,
+}
+
+/// A builtin definition: its name, accepted arity and documentation
+#[derive(Debug, Clone)]
+pub struct Builtin {
+    /// Name used at call sites (`⤷name[...]`)
+    pub name: &'static str,
+    /// Dispatch identity
+    pub id: BuiltinId,
+    /// Minimum number of arguments
+    pub min_arity: u8,
+    /// Maximum number of arguments
+    pub max_arity: u8,
+    /// Category for help listings
+    pub category: BuiltinCategory,
+    /// Call signature shown in help
+    pub signature: &'static str,
+    /// Human-readable description
+    pub description: &'static str,
+}
+
+impl Builtin {
+    const fn new(
+        name: &'static str,
+        id: BuiltinId,
+        min_arity: u8,
+        max_arity: u8,
+        category: BuiltinCategory,
+        signature: &'static str,
+        description: &'static str,
+    ) -> Self {
+        Self { name, id, min_arity, max_arity, category, signature, description }
+    }
+
+    /// Check whether a call with `count` arguments is acceptable
+    pub fn accepts(&self, count: u8) -> bool {
+        count >= self.min_arity && count <= self.max_arity
+    }
+}
+
+/// The builtin table. A builtin's position here is its `CallNative` operand,
+/// so new entries must only ever be appended.
+pub static BUILTINS: &[Builtin] = &[
+    // ═══════════════════════════════════════════════════════════════
+    // MATH
+    // ═══════════════════════════════════════════════════════════════
+    Builtin::new("abs", BuiltinId::Abs, 1, 1, BuiltinCategory::Math,
+        "⤷abs[n]", "Absolute value of an integer or real"),
+    Builtin::new("min", BuiltinId::Min, 1, u8::MAX, BuiltinCategory::Math,
+        "⤷min[a, b, ...] or ⤷min[arr]", "Smallest of the arguments or of an array"),
+    Builtin::new("max", BuiltinId::Max, 1, u8::MAX, BuiltinCategory::Math,
+        "⤷max[a, b, ...] or ⤷max[arr]", "Largest of the arguments or of an array"),
+    Builtin::new("floor", BuiltinId::Floor, 1, 1, BuiltinCategory::Math,
+        "⤷floor[r]", "Round down to the nearest integer"),
+    Builtin::new("ceil", BuiltinId::Ceil, 1, 1, BuiltinCategory::Math,
+        "⤷ceil[r]", "Round up to the nearest integer"),
+    Builtin::new("sqrt", BuiltinId::Sqrt, 1, 1, BuiltinCategory::Math,
+        "⤷sqrt[n]", "Square root as a real"),
+
+    // ═══════════════════════════════════════════════════════════════
+    // COLLECTIONS
+    // ═══════════════════════════════════════════════════════════════
+    Builtin::new("len", BuiltinId::Len, 1, 1, BuiltinCategory::Collection,
+        "⤷len[x]", "Length of an array, map or string (in runes)"),
+
+    // ═══════════════════════════════════════════════════════════════
+    // STRINGS
+    // ═══════════════════════════════════════════════════════════════
+    Builtin::new("substring", BuiltinId::Substring, 2, 3, BuiltinCategory::String,
+        "⤷substring[s, start, end?]", "Runes from start up to (not including) end"),
+    Builtin::new("split", BuiltinId::Split, 2, 2, BuiltinCategory::String,
+        "⤷split[s, sep]", "Split a string into an array; empty sep splits runes"),
+    Builtin::new("join", BuiltinId::Join, 2, 2, BuiltinCategory::String,
+        "⤷join[arr, sep]", "Join array elements into a string"),
+    Builtin::new("upper", BuiltinId::Upper, 1, 1, BuiltinCategory::String,
+        "⤷upper[s]", "Uppercase copy of a string"),
+    Builtin::new("lower", BuiltinId::Lower, 1, 1, BuiltinCategory::String,
+        "⤷lower[s]", "Lowercase copy of a string"),
+    Builtin::new("contains", BuiltinId::Contains, 2, 2, BuiltinCategory::Collection,
+        "⤷contains[x, needle]", "Substring, array element or map key test"),
+
+    // ═══════════════════════════════════════════════════════════════
+    // COLLECTIONS (continued)
+    // ═══════════════════════════════════════════════════════════════
+    Builtin::new("sort", BuiltinId::Sort, 1, 1, BuiltinCategory::Collection,
+        "⤷sort[arr]", "Sorted copy of an array of numbers or strings"),
+    Builtin::new("reverse", BuiltinId::Reverse, 1, 1, BuiltinCategory::Collection,
+        "⤷reverse[x]", "Reversed copy of an array or string"),
+    Builtin::new("range", BuiltinId::Range, 1, 3, BuiltinCategory::Collection,
+        "⤷range[end] or ⤷range[start, end, step?]", "Array of integers from start up to end"),
+
+    // ═══════════════════════════════════════════════════════════════
+    // CONVERSIONS
+    // ═══════════════════════════════════════════════════════════════
+    Builtin::new("to_string", BuiltinId::ToString, 1, 1, BuiltinCategory::Conversion,
+        "⤷to_string[v]", "Convert any value to ⌘"),
+    Builtin::new("to_int", BuiltinId::ToInt, 1, 1, BuiltinCategory::Conversion,
+        "⤷to_int[v]", "Convert a real, boolean or numeric string to ⟁"),
+    Builtin::new("to_real", BuiltinId::ToReal, 1, 1, BuiltinCategory::Conversion,
+        "⤷to_real[v]", "Convert an integer, boolean or numeric string to ⧆"),
+
+    // ═══════════════════════════════════════════════════════════════
+    // FILES
+    // ═══════════════════════════════════════════════════════════════
+    Builtin::new("open", BuiltinId::Open, 1, 2, BuiltinCategory::File,
+        "⤷open[path, mode?]", "Open a file (\"r\" read, \"w\" write, \"a\" append) as a 📂 handle"),
+    Builtin::new("read_line", BuiltinId::ReadLine, 1, 1, BuiltinCategory::File,
+        "⤷read_line[h]", "Next line without its terminator, or ∅ at end of file"),
+    Builtin::new("read_all", BuiltinId::ReadAll, 1, 1, BuiltinCategory::File,
+        "⤷read_all[h]", "Everything remaining in the file as ⌘"),
+    Builtin::new("write", BuiltinId::Write, 2, 2, BuiltinCategory::File,
+        "⤷write[h, text]", "Write text to a handle opened with \"w\" or \"a\""),
+    Builtin::new("close", BuiltinId::Close, 1, 1, BuiltinCategory::File,
+        "⤷close[h]", "Close a handle, flushing pending writes"),
+
+    // ═══════════════════════════════════════════════════════════════
+    // SYSTEM
+    // ═══════════════════════════════════════════════════════════════
+    Builtin::new("args", BuiltinId::Args, 0, 0, BuiltinCategory::System,
+        "⤷args[]", "Arguments given after -- on the command line, as a ⌬ of ⌘"),
+    Builtin::new("env", BuiltinId::Env, 1, 1, BuiltinCategory::System,
+        "⤷env[name]", "Value of an environment variable, or ∅ if unset"),
+
+    // ═══════════════════════════════════════════════════════════════
+    // EXCEPTIONS
+    // ═══════════════════════════════════════════════════════════════
+    Builtin::new("error", BuiltinId::Error, 1, 2, BuiltinCategory::Exception,
+        "⤷error[kind, message?]", "Exception ⌖ with kind, message, line and trace, for ⚠"),
+
+    // ═══════════════════════════════════════════════════════════════
+    // ITERATORS
+    // ═══════════════════════════════════════════════════════════════
+    Builtin::new("iter", BuiltinId::Iter, 1, 1, BuiltinCategory::Iterator,
+        "⤷iter[x]", "⌭ over an array's elements, a map's keys or a string's graphemes"),
+    Builtin::new("iter_range", BuiltinId::IterRange, 1, 3, BuiltinCategory::Iterator,
+        "⤷iter_range[end] or ⤷iter_range[start, end, step?]", "⌭ counting from start up to end, one integer at a time"),
+    Builtin::new("next", BuiltinId::Next, 1, 1, BuiltinCategory::Iterator,
+        "⤷next[it]", "Next value of an iterator, or ∅ once it is exhausted"),
+    Builtin::new("collect", BuiltinId::Collect, 1, 1, BuiltinCategory::Iterator,
+        "⤷collect[it]", "Every remaining value of an iterator as a ⌬"),
+];
+
+/// Look up a builtin by name, returning its table index
+pub fn lookup(name: &str) -> Option<(u16, &'static Builtin)> {
+    BUILTINS.iter()
+        .enumerate()
+        .find(|(_, b)| b.name == name)
+        .map(|(i, b)| (i as u16, b))
+}
+
+/// Get a builtin by its table index
+pub fn get(index: usize) -> Option<&'static Builtin> {
+    BUILTINS.get(index)
+}
+
+/// Get all builtins of a category, in table order
+pub fn in_category(category: BuiltinCategory) -> Vec<&'static Builtin> {
+    BUILTINS.iter().filter(|b| b.category == category).collect()
+}
+
+// ═══════════════════════════════════════════════════════════════
+// IMPLEMENTATIONS
+// ═══════════════════════════════════════════════════════════════
+
+type BuiltinResult = Result<Value, RuntimeError>;
+
+/// Execute a builtin that does not need the runtime.
+///
+/// Conversions are dispatched by the runtime itself so they share
+/// `Runtime::convert_value` with the `Convert` opcode, and file and system
+/// builtins need state owned by the runtime.
+pub fn call(builtin: &Builtin, args: Vec<Value>) -> BuiltinResult {
+    match builtin.id {
+        BuiltinId::Abs => abs(builtin, &args[0]),
+        BuiltinId::Min => extreme(builtin, args, Ordering::Less),
+        BuiltinId::Max => extreme(builtin, args, Ordering::Greater),
+        BuiltinId::Floor => round(builtin, &args[0], f64::floor),
+        BuiltinId::Ceil => round(builtin, &args[0], f64::ceil),
+        BuiltinId::Sqrt => sqrt(builtin, &args[0]),
+        BuiltinId::Len => len(builtin, &args[0]),
+        BuiltinId::Substring => substring(builtin, &args),
+        BuiltinId::Split => split(builtin, &args[0], &args[1]),
+        BuiltinId::Join => join(builtin, &args[0], &args[1]),
+        BuiltinId::Upper => map_string(builtin, &args[0], str::to_uppercase),
+        BuiltinId::Lower => map_string(builtin, &args[0], str::to_lowercase),
+        BuiltinId::Contains => contains(builtin, &args[0], &args[1]),
+        BuiltinId::Sort => sort(builtin, &args[0]),
+        BuiltinId::Reverse => reverse(builtin, &args[0]),
+        BuiltinId::Range => range(builtin, &args),
+        BuiltinId::ToString | BuiltinId::ToInt | BuiltinId::ToReal => {
+            Err(refuse(builtin, "conversions are performed by the runtime"))
+        }
+        BuiltinId::Open | BuiltinId::ReadLine | BuiltinId::ReadAll
+        | BuiltinId::Write | BuiltinId::Close => {
+            Err(refuse(builtin, "file access is performed by the runtime"))
+        }
+        BuiltinId::Args | BuiltinId::Env => {
+            Err(refuse(builtin, "the outside world is reached through the runtime"))
+        }
+        BuiltinId::Error => Err(refuse(builtin, "exception values are built by the runtime")),
+        BuiltinId::Iter => iter(builtin, &args[0]),
+        BuiltinId::IterRange => iter_range(builtin, &args),
+        BuiltinId::Next | BuiltinId::Collect => {
+            Err(refuse(builtin, "iterators are advanced by the runtime"))
+        }
+    }
+}
+
+/// Build a builtin failure
+fn refuse(builtin: &Builtin, reason: impl Into<String>) -> RuntimeError {
+    RuntimeError::BuiltinError {
+        name: builtin.name.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Build a type mismatch for an argument
+fn mismatch(expected: &str, actual: &Value) -> RuntimeError {
+    RuntimeError::TypeMismatch {
+        expected: expected.to_string(),
+        actual: actual.value_type().to_string(),
+    }
+}
+
+/// Order two values the way `◁` does, for min/max/sort
+pub fn compare_values(a: &Value, b: &Value) -> Result<Ordering, RuntimeError> {
+    let ordering = match (a, b) {
+        (Value::Integer(x), Value::Integer(y)) => Some(x.cmp(y)),
+        (Value::Real(x), Value::Real(y)) => x.partial_cmp(y),
+        (Value::Integer(x), Value::Real(y)) => (*x as f64).partial_cmp(y),
+        (Value::Real(x), Value::Integer(y)) => x.partial_cmp(&(*y as f64)),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        (Value::Rune(x), Value::Rune(y)) => Some(x.cmp(y)),
+        _ => None,
+    };
+
+    ordering.ok_or_else(|| RuntimeError::TypeMismatch {
+        expected: "comparable types".to_string(),
+        actual: format!("{} and {}", a.value_type(), b.value_type()),
+    })
+}
+
+fn abs(builtin: &Builtin, value: &Value) -> BuiltinResult {
+    match value {
+        Value::Integer(i) => i.checked_abs()
+            .map(Value::Integer)
+            .ok_or(RuntimeError::ArithmeticOverflow),
+        Value::Real(r) => Ok(Value::Real(r.abs())),
+        other => Err(refuse(builtin, mismatch("numeric", other).to_string())),
+    }
+}
+
+fn extreme(builtin: &Builtin, args: Vec<Value>, wanted: Ordering) -> BuiltinResult {
+    // A single array argument means "the extreme of its elements"
+    let candidates = match args.as_slice() {
+        [Value::Array(elements)] => elements.to_vec(),
+        _ => args,
+    };
+
+    let mut iter = candidates.into_iter();
+    let mut best = iter.next()
+        .ok_or_else(|| refuse(builtin, "needs at least one value"))?;
+
+    for candidate in iter {
+        if compare_values(&candidate, &best)? == wanted {
+            best = candidate;
+        }
+    }
+
+    Ok(best)
+}
+
+fn round(builtin: &Builtin, value: &Value, op: fn(f64) -> f64) -> BuiltinResult {
+    match value {
+        Value::Integer(i) => Ok(Value::Integer(*i)),
+        Value::Real(r) => {
+            let rounded = op(*r);
+            if !rounded.is_finite() || rounded < i64::MIN as f64 || rounded > i64::MAX as f64 {
+                return Err(RuntimeError::ArithmeticOverflow);
+            }
+            Ok(Value::Integer(rounded as i64))
+        }
+        other => Err(refuse(builtin, mismatch("numeric", other).to_string())),
+    }
+}
+
+fn sqrt(builtin: &Builtin, value: &Value) -> BuiltinResult {
+    let n = match value {
+        Value::Integer(i) => *i as f64,
+        Value::Real(r) => *r,
+        other => return Err(refuse(builtin, mismatch("numeric", other).to_string())),
+    };
+
+    if n < 0.0 {
+        return Err(refuse(builtin, format!("cannot take the root of {}", value)));
+    }
+
+    Ok(Value::Real(n.sqrt()))
+}
+
+fn len(builtin: &Builtin, value: &Value) -> BuiltinResult {
+    let length = match value {
+        Value::Array(arr) => arr.len(),
+        Value::Map(map) => map.len(),
+        Value::String(s) => s.chars().count(),
+        other => return Err(refuse(builtin, mismatch("array, map or string", other).to_string())),
+    };
+
+    Ok(Value::Integer(length as i64))
+}
+
+fn substring(builtin: &Builtin, args: &[Value]) -> BuiltinResult {
+    let Value::String(s) = &args[0] else {
+        return Err(refuse(builtin, mismatch("string", &args[0]).to_string()));
+    };
+
+    let runes: Vec<char> = s.chars().collect();
+    let length = runes.len() as i64;
+
+    let start = expect_integer(builtin, &args[1])?;
+    let end = match args.get(2) {
+        Some(v) => expect_integer(builtin, v)?,
+        None => length,
+    };
+
+    if start < 0 || start > length {
+        return Err(RuntimeError::IndexOutOfBounds { index: start, length: runes.len() });
+    }
+    if end < start || end > length {
+        return Err(RuntimeError::IndexOutOfBounds { index: end, length: runes.len() });
+    }
+
+    Ok(Value::String(runes[start as usize..end as usize].iter().collect()))
+}
+
+fn split(builtin: &Builtin, value: &Value, separator: &Value) -> BuiltinResult {
+    let (Value::String(s), Value::String(sep)) = (value, separator) else {
+        return Err(refuse(builtin, "expects a string and a string separator"));
+    };
+
+    let parts: Vec<Value> = if sep.is_empty() {
+        s.chars().map(|c| Value::String(c.to_string())).collect()
+    } else {
+        s.split(sep.as_str()).map(|p| Value::String(p.to_string())).collect()
+    };
+
+    Ok(Value::array(parts))
+}
+
+fn join(builtin: &Builtin, value: &Value, separator: &Value) -> BuiltinResult {
+    let (Value::Array(arr), Value::String(sep)) = (value, separator) else {
+        return Err(refuse(builtin, "expects an array and a string separator"));
+    };
+
+    let parts: Vec<String> = arr.iter().map(|v| v.to_string_value()).collect();
+    Ok(Value::String(parts.join(sep)))
+}
+
+fn map_string(builtin: &Builtin, value: &Value, op: fn(&str) -> String) -> BuiltinResult {
+    match value {
+        Value::String(s) => Ok(Value::String(op(s))),
+        other => Err(refuse(builtin, mismatch("string", other).to_string())),
+    }
+}
+
+fn contains(builtin: &Builtin, haystack: &Value, needle: &Value) -> BuiltinResult {
+    let found = match (haystack, needle) {
+        (Value::String(s), Value::String(sub)) => s.contains(sub.as_str()),
+        (Value::String(s), Value::Rune(c)) => s.contains(*c),
+        (Value::Array(arr), needle) => arr.contains(needle),
+        (Value::Map(map), key) => map.contains_key(key),
+        (other, _) => return Err(refuse(builtin, mismatch("string, array or map", other).to_string())),
+    };
+
+    Ok(Value::Boolean(found))
+}
+
+fn sort(builtin: &Builtin, value: &Value) -> BuiltinResult {
+    let Value::Array(arr) = value else {
+        return Err(refuse(builtin, mismatch("array", value).to_string()));
+    };
+
+    // Validate every pair up-front so the sort itself cannot fail midway
+    for pair in arr.windows(2) {
+        compare_values(&pair[0], &pair[1])?;
+    }
+
+    let mut sorted = arr.to_vec();
+    sorted.sort_by(|a, b| compare_values(a, b).unwrap_or(Ordering::Equal));
+    Ok(Value::array(sorted))
+}
+
+fn reverse(builtin: &Builtin, value: &Value) -> BuiltinResult {
+    match value {
+        Value::Array(arr) => Ok(Value::array(arr.iter().rev().cloned().collect())),
+        Value::String(s) => Ok(Value::String(s.chars().rev().collect())),
+        other => Err(refuse(builtin, mismatch("array or string", other).to_string())),
+    }
+}
+
+/// Upper bound on the number of elements `range` may produce
+const MAX_RANGE_LEN: i64 = 1 << 24;
+
+fn range(builtin: &Builtin, args: &[Value]) -> BuiltinResult {
+    let (start, end) = match args {
+        [end] => (0, expect_integer(builtin, end)?),
+        [start, end, ..] => (expect_integer(builtin, start)?, expect_integer(builtin, end)?),
+        [] => unreachable!("arity checked by the compiler"),
+    };
+    let step = match args.get(2) {
+        Some(v) => expect_integer(builtin, v)?,
+        None => 1,
+    };
+
+    if step == 0 {
+        return Err(refuse(builtin, "step must not be zero"));
+    }
+
+    let count = if (step > 0 && end > start) || (step < 0 && end < start) {
+        (end.abs_diff(start) - 1) / step.unsigned_abs() + 1
+    } else {
+        0
+    };
+    if count > MAX_RANGE_LEN as u64 {
+        return Err(refuse(builtin, format!("would produce {} elements", count)));
+    }
+
+    let values = (0..count as i64)
+        .map(|i| Value::Integer(start + i * step))
+        .collect();
+
+    Ok(Value::array(values))
+}
+
+fn iter(builtin: &Builtin, value: &Value) -> BuiltinResult {
+    let items = match value {
+        Value::Array(elements) => elements.to_vec(),
+        Value::Map(entries) => entries.keys().cloned().collect(),
+        Value::String(s) => s.graphemes(true).map(|g| Value::String(g.to_string())).collect(),
+        // Already an iterator: hand it back, still shared
+        Value::Iterator(_) => return Ok(value.clone()),
+        other => return Err(refuse(builtin, mismatch("array, map or string", other).to_string())),
+    };
+    Ok(Value::Iterator(IterHandle::items(items)))
+}
+
+fn iter_range(builtin: &Builtin, args: &[Value]) -> BuiltinResult {
+    let (next, end) = match args {
+        [end] => (0, expect_integer(builtin, end)?),
+        [start, end, ..] => (expect_integer(builtin, start)?, expect_integer(builtin, end)?),
+        [] => unreachable!("arity checked by the compiler"),
+    };
+    let step = match args.get(2) {
+        Some(v) => expect_integer(builtin, v)?,
+        None => 1,
+    };
+
+    if step == 0 {
+        return Err(refuse(builtin, "step must not be zero"));
+    }
+
+    // Nothing is produced up front, so the range may be as long as it likes
+    Ok(Value::Iterator(IterHandle::new(IterSource::Range { next, end, step })))
+}
+
+fn expect_integer(builtin: &Builtin, value: &Value) -> Result<i64, RuntimeError> {
+    match value {
+        Value::Integer(i) => Ok(*i),
+        other => Err(refuse(builtin, mismatch("integer", other).to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(name: &str, args: Vec<Value>) -> BuiltinResult {
+        let (_, builtin) = lookup(name).unwrap();
+        call(builtin, args)
+    }
+
+    #[test]
+    fn test_lookup() {
+        let (index, builtin) = lookup("len").unwrap();
+        assert_eq!(builtin.id, BuiltinId::Len);
+        assert_eq!(get(index as usize).unwrap().name, "len");
+        assert!(lookup("summon_dragon").is_none());
+    }
+
+    #[test]
+    fn test_range_and_sort() {
+        let r = run("range", vec![Value::Integer(10), Value::Integer(0), Value::Integer(-3)]).unwrap();
+        assert_eq!(r, Value::array(vec![
+            Value::Integer(10), Value::Integer(7), Value::Integer(4), Value::Integer(1),
+        ]));
+
+        let sorted = run("sort", vec![r]).unwrap();
+        assert_eq!(run("min", vec![sorted.clone()]).unwrap(), Value::Integer(1));
+        assert_eq!(run("max", vec![sorted]).unwrap(), Value::Integer(10));
+    }
+
+    #[test]
+    fn test_string_builtins() {
+        let s = Value::String("fire,ice".to_string());
+        let parts = run("split", vec![s, Value::String(",".to_string())]).unwrap();
+        let joined = run("join", vec![parts, Value::String("+".to_string())]).unwrap();
+        assert_eq!(joined, Value::String("fire+ice".to_string()));
+
+        let sub = run("substring", vec![joined, Value::Integer(5)]).unwrap();
+        assert_eq!(run("upper", vec![sub]).unwrap(), Value::String("ICE".to_string()));
+    }
+
+    #[test]
+    fn test_iterator_builtins() {
+        let graphemes = run("iter", vec![Value::String("e\u{301}a".to_string())]).unwrap();
+        let Value::Iterator(handle) = &graphemes else { panic!("expected an iterator") };
+        match &*handle.source().borrow() {
+            IterSource::Items { items, position: 0 } => assert_eq!(items.len(), 2),
+            other => panic!("unexpected source {:?}", other),
+        }
+        assert_eq!(run("iter", vec![graphemes.clone()]).unwrap(), graphemes);
+
+        assert!(run("iter_range", vec![Value::Integer(0), Value::Integer(i64::MAX)]).is_ok());
+        assert!(run("iter_range", vec![Value::Integer(0), Value::Integer(5), Value::Integer(0)]).is_err());
+        assert!(run("next", vec![graphemes]).is_err());
+    }
+}
@@ -66,6 +66,16 @@ pub enum SymbolMeaning {
     TypeArray,
     /// Map type (⌖) - key-value collection
     TypeMap,
+    /// Struct definition (⌻) - record with named fields
+    TypeStruct,
+    /// Enum definition (⎊) - tagged variants with payloads
+    TypeEnum,
+    /// Iterator type (⌭) - values produced on demand; ⌭[v] yields
+    TypeIterator,
+    /// Trait definition (⍟) - named set of method signatures
+    TraitDefine,
+    /// Trait implementation (⍜) - methods of a trait for one type
+    TraitImpl,
 
     // ═══════════════════════════════════════════════════════════════
     // SPECIAL VALUES
@@ -159,6 +169,12 @@ pub enum SymbolMeaning {
     LoopStart,
     /// End of cycle/loop (⊃)
     LoopEnd,
+    /// Loop variable binding in a for-each loop (∈)
+    In,
+    /// Integer range in a for-each loop (‥)
+    Range,
+    /// Range step (∆)
+    Step,
     /// Conditional branch start (⟨)
     IfStart,
     /// Else branch (⟩)
@@ -179,6 +195,18 @@ pub enum SymbolMeaning {
     Return,
     /// Call function (⤷)
     Call,
+    /// Pipe a value into a function (⋈)
+    Pipe,
+    /// Compose two functions (⋉)
+    Compose,
+    /// Bind leading arguments of a function (⋊)
+    PartialApply,
+    /// Map a function over a collection (⧉)
+    MapWith,
+    /// Filter a collection with a predicate (⧊)
+    FilterWith,
+    /// Fold a collection with a function (⧋)
+    ReduceWith,
 
     // ═══════════════════════════════════════════════════════════════
     // PATTERN MATCHING (v0.3.0)
@@ -226,6 +254,15 @@ pub enum SymbolMeaning {
     /// Map entry separator (⋄)
     MapSeparator,
 
+    // ═══════════════════════════════════════════════════════════════
+    // COLLECTION ACCESS
+    // ═══════════════════════════════════════════════════════════════
+
+    /// Index into an array, map or string (⌷)
+    Index,
+    /// Append to an array in place (⊞)
+    Append,
+
     // ═══════════════════════════════════════════════════════════════
     // STACK OPERATIONS
     // ═══════════════════════════════════════════════════════════════
@@ -406,6 +443,16 @@ impl SymbolTable {
             "Array type - declares ordered collection"));
         self.register(Symbol::new("⌖", TypeMap, TypeDeclaration,
             "Map type - declares key-value collection"));
+        self.register(Symbol::new("⌻", TypeStruct, TypeDeclaration,
+            "Struct - defines a record type with named fields"));
+        self.register(Symbol::new("⎊", TypeEnum, TypeDeclaration,
+            "Enum - defines a type of tagged variants"));
+        self.register(Symbol::new("⌭", TypeIterator, TypeDeclaration,
+            "Iterator type - declares an iterator; ⌭[value] yields from a generator"));
+        self.register(Symbol::new("⍟", TraitDefine, TypeDeclaration,
+            "Trait - declares method signatures shared by types"));
+        self.register(Symbol::new("⍜", TraitImpl, TypeDeclaration,
+            "Implement - provides a trait's methods for a type"));
 
         // ═══════════════════════════════════════════════════════════════
         // SPECIAL VALUES
@@ -497,6 +544,12 @@ impl SymbolTable {
             "Loop start - begins repetition block"));
         self.register(Symbol::new("⊃", LoopEnd, ControlFlow,
             "Loop end - ends repetition block"));
+        self.register(Symbol::new("∈", In, ControlFlow,
+            "In - binds each element in a for-each loop"));
+        self.register(Symbol::new("‥", Range, ControlFlow,
+            "Range - integers from start up to (not including) end"));
+        self.register(Symbol::new("∆", Step, ControlFlow,
+            "Step - distance between the integers of a range"));
         self.register(Symbol::new("⟨", IfStart, ControlFlow,
             "If start - begins conditional block"));
         self.register(Symbol::new("⟩", Else, ControlFlow,
@@ -522,6 +575,18 @@ impl SymbolTable {
             "Return - returns value from function"));
         self.register(Symbol::new("⤷", Call, ControlFlow,
             "Call - invokes a function"));
+        self.register(Symbol::new("⋈", Pipe, Operator,
+            "Pipe - hands the value on the left to the function on the right"));
+        self.register(Symbol::new("⋉", Compose, Operator,
+            "Compose - runs the left function, then the right one"));
+        self.register(Symbol::new("⋊", PartialApply, Operator,
+            "Partial application - binds the first arguments of a function"));
+        self.register(Symbol::new("⧉", MapWith, Operator,
+            "Map - applies a function to each element of a collection"));
+        self.register(Symbol::new("⧊", FilterWith, Operator,
+            "Filter - keeps the elements a predicate holds for"));
+        self.register(Symbol::new("⧋", ReduceWith, Operator,
+            "Reduce - folds a collection with a function"));
 
         // ═══════════════════════════════════════════════════════════════
         // PATTERN MATCHING (v0.3.0)
@@ -545,7 +610,7 @@ impl SymbolTable {
         self.register(Symbol::new("⟳", Export, ControlFlow,
             "Export - exports a symbol"));
         self.register(Symbol::new("⊷", ModuleAccess, Operator,
-            "Module access - accesses module member"));
+            "Member access - module members, struct fields and enum variants"));
 
         // ═══════════════════════════════════════════════════════════════
         // EXCEPTION HANDLING (v0.3.0)
@@ -573,6 +638,15 @@ impl SymbolTable {
         self.register(Symbol::new("}", RightBrace, Delimiter,
             "Right brace - closes map literal"));
 
+        // ═══════════════════════════════════════════════════════════════
+        // COLLECTION ACCESS
+        // ═══════════════════════════════════════════════════════════════
+
+        self.register(Symbol::new("⌷", Index, Operator,
+            "Index - element of an array, map or string"));
+        self.register(Symbol::new("⊞", Append, Operator,
+            "Append - adds a value to the end of an array variable"));
+
         // ═══════════════════════════════════════════════════════════════
         // STACK OPERATIONS (for advanced low-level control)
         // ═══════════════════════════════════════════════════════════════
@@ -3,8 +3,11 @@
 //! Manages execution state, variable scopes, and call frames.
 //! The context provides the environment in which code executes.
 
-use crate::bytecode::{Chunk, Value, ValueType};
+use crate::bytecode::{Chunk, Upvalue, UpvalueCell, Value, ValueType};
 use rustc_hash::FxHashMap;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
 use thiserror::Error;
 
 /// Context-related errors
@@ -91,6 +94,15 @@ impl Scope {
     pub fn contains(&self, name: &str) -> bool {
         self.variables.contains_key(name)
     }
+
+    /// Insert or replace a variable, returning the one it replaced
+    pub fn insert(&mut self, name: String, var: Variable) -> Option<Variable> {
+        self.variables.insert(name, var)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Variable> {
+        self.variables.remove(name)
+    }
 }
 
 /// A call frame representing a function invocation
@@ -108,6 +120,8 @@ pub struct CallFrame {
     pub name: String,
     /// Closure environment (if this is a closure call)
     pub closure: Option<crate::bytecode::Closure>,
+    /// Loop depth of the caller, restored when the frame is popped
+    pub loop_depth: usize,
 }
 
 impl CallFrame {
@@ -119,6 +133,7 @@ impl CallFrame {
             scope: Scope::new(),
             name,
             closure: None,
+            loop_depth: 0,
         }
     }
     
@@ -131,22 +146,49 @@ impl CallFrame {
             scope: Scope::new(),
             name,
             closure: Some(closure),
+            loop_depth: 0,
         }
     }
 }
 
+/// A generator's call frame, put aside between a ⌭ and the next resume
+/// along with the state of the loops it has open
+#[derive(Debug)]
+pub struct SuspendedFrame {
+    /// The frame itself, IP just past the ⌭
+    pub frame: CallFrame,
+    /// The frame's segment of the value stack
+    pub stack: Vec<Value>,
+    /// Variables bound by the frame's open loops
+    loop_bindings: Vec<Vec<(String, Option<Variable>)>>,
+    /// Accumulators saved by the frame's open loops, outermost first
+    accumulators: Vec<i64>,
+    /// Accumulator of the innermost open loop
+    accumulator: i64,
+    /// Upvalues of the frame's variables, closed while it is suspended
+    upvalues: Vec<(String, UpvalueCell)>,
+}
+
 /// Maximum call stack depth
 pub const MAX_CALL_DEPTH: usize = 1024;
 
 /// Exception handler for try-catch blocks (v1.0.0)
 #[derive(Debug, Clone)]
 pub struct ExceptionHandler {
-    /// IP to jump to on exception
-    pub handler_ip: usize,
+    /// IP of the catch block, while the handler still catches
+    pub handler_ip: Option<usize>,
     /// Optional finally block IP
     pub finally_ip: Option<usize>,
     /// Stack depth to restore on exception
     pub stack_depth: usize,
+    /// Spell stack depth to restore on exception
+    pub spell_depth: usize,
+    /// Loop depth to restore on exception
+    pub loop_depth: usize,
+    /// Pending completions to keep on exception
+    pub completion_depth: usize,
+    /// The exception being handled, once the handler covers a catch block
+    pub caught: Option<Exception>,
     /// Call frame depth when handler was set
     pub frame_depth: usize,
     /// Chunk index for the handler
@@ -154,11 +196,15 @@ pub struct ExceptionHandler {
 }
 
 impl ExceptionHandler {
-    pub fn new(handler_ip: usize, stack_depth: usize, frame_depth: usize, chunk_index: usize) -> Self {
+    pub fn new(handler_ip: Option<usize>, stack_depth: usize, frame_depth: usize, chunk_index: usize) -> Self {
         Self {
             handler_ip,
             finally_ip: None,
             stack_depth,
+            spell_depth: 0,
+            loop_depth: 0,
+            completion_depth: 0,
+            caught: None,
             frame_depth,
             chunk_index,
         }
@@ -168,6 +214,62 @@ impl ExceptionHandler {
         self.finally_ip = Some(finally_ip);
         self
     }
+
+    pub fn with_spell_depth(mut self, spell_depth: usize) -> Self {
+        self.spell_depth = spell_depth;
+        self
+    }
+
+    pub fn with_loop_depth(mut self, loop_depth: usize) -> Self {
+        self.loop_depth = loop_depth;
+        self
+    }
+
+    pub fn with_completion_depth(mut self, completion_depth: usize) -> Self {
+        self.completion_depth = completion_depth;
+        self
+    }
+}
+
+/// One call in a stack trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    /// Name of the function ("main" for the spell itself)
+    pub function: String,
+    /// Source line the call was at
+    pub line: usize,
+}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (line {})", self.function, self.line)
+    }
+}
+
+/// A thrown value together with where it was thrown from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exception {
+    pub value: Value,
+    /// Active calls at the throw site, innermost first
+    pub trace: Vec<TraceFrame>,
+}
+
+impl Exception {
+    /// Line of the throw site
+    pub fn line(&self) -> usize {
+        self.trace.first().map_or(0, |frame| frame.line)
+    }
+}
+
+/// What a finally block resumes once it has run
+#[derive(Debug, Clone, PartialEq)]
+pub enum Completion {
+    /// Carry on at an IP of the same frame (normal exit, ⊗ or ↺)
+    Resume(usize),
+    /// Rethrow an exception
+    Throw(Exception),
+    /// Finish returning a value
+    Return(Value),
 }
 
 /// Execution context for the VM
@@ -187,6 +289,8 @@ pub struct Context {
     accumulator: i64,
     /// Accumulator stack for nested loops
     accumulator_stack: Vec<i64>,
+    /// Variables bound by each open loop, with the ones they shadowed
+    loop_bindings: Vec<Vec<(String, Option<Variable>)>>,
     /// Execution flags
     pub halted: bool,
     /// Break flag for loop control
@@ -197,8 +301,10 @@ pub struct Context {
     loop_depth: usize,
     /// Exception handler stack (v1.0.0)
     exception_handlers: Vec<ExceptionHandler>,
-    /// Current exception value (if any)
-    pub current_exception: Option<crate::bytecode::Value>,
+    /// Completions interrupted by the finally blocks now running
+    completions: Vec<Completion>,
+    /// Upvalues still referring to a variable of a live frame
+    open_upvalues: Vec<UpvalueCell>,
 }
 
 impl Context {
@@ -210,12 +316,14 @@ impl Context {
             functions: FxHashMap::default(),
             accumulator: 0,
             accumulator_stack: Vec::new(),
+            loop_bindings: Vec::new(),
             halted: false,
             break_flag: false,
             continue_flag: false,
             loop_depth: 0,
             exception_handlers: Vec::new(),
-            current_exception: None,
+            completions: Vec::new(),
+            open_upvalues: Vec::new(),
         }
     }
 
@@ -243,6 +351,44 @@ impl Context {
         !self.exception_handlers.is_empty()
     }
 
+    /// The exception of the innermost catch block being run
+    pub fn caught_exception(&self) -> Option<&Exception> {
+        self.exception_handlers.iter().rev().find_map(|handler| handler.caught.as_ref())
+    }
+
+    /// Pop the innermost handler if it was set up by a frame at or above a call depth
+    pub fn pop_frame_handler(&mut self, frame_depth: usize) -> Option<ExceptionHandler> {
+        match self.exception_handlers.last() {
+            Some(handler) if handler.frame_depth >= frame_depth => self.exception_handlers.pop(),
+            _ => None,
+        }
+    }
+
+    /// Whether a frame at this depth or deeper has a handler installed
+    pub fn has_frame_handler(&self, frame_depth: usize) -> bool {
+        self.exception_handlers.last().is_some_and(|handler| handler.frame_depth >= frame_depth)
+    }
+
+    /// Remember what to do once a finally block ends
+    pub fn push_completion(&mut self, completion: Completion) {
+        self.completions.push(completion);
+    }
+
+    /// Take the completion of the finally block that just ended
+    pub fn pop_completion(&mut self) -> Option<Completion> {
+        self.completions.pop()
+    }
+
+    /// Number of pending completions
+    pub fn completion_depth(&self) -> usize {
+        self.completions.len()
+    }
+
+    /// Forget the completions of finally blocks an exception escaped from
+    pub fn truncate_completions(&mut self, depth: usize) {
+        self.completions.truncate(depth);
+    }
+
     // ═══════════════════════════════════════════════════════════════
     // CHUNK MANAGEMENT
     // ═══════════════════════════════════════════════════════════════
@@ -269,16 +415,23 @@ impl Context {
     // ═══════════════════════════════════════════════════════════════
 
     /// Push a new call frame
-    pub fn push_frame(&mut self, frame: CallFrame) -> Result<(), ContextError> {
+    pub fn push_frame(&mut self, mut frame: CallFrame) -> Result<(), ContextError> {
         if self.frames.len() >= MAX_CALL_DEPTH {
             return Err(ContextError::CallStackOverflow);
         }
+        frame.loop_depth = self.loop_depth;
         self.frames.push(frame);
         Ok(())
     }
 
-    /// Pop the current call frame
+    /// Pop the current call frame, closing any loops it left open and the
+    /// upvalues of its variables
     pub fn pop_frame(&mut self) -> Result<CallFrame, ContextError> {
+        let loop_depth = self.frames.last()
+            .ok_or(ContextError::CallStackUnderflow)?
+            .loop_depth;
+        self.close_upvalues(self.frames.len() - 1, None);
+        self.unwind_loops(loop_depth);
         self.frames.pop().ok_or(ContextError::CallStackUnderflow)
     }
 
@@ -297,6 +450,89 @@ impl Context {
         self.frames.len()
     }
 
+    /// The active calls, innermost first, with the source line each is at
+    pub fn trace(&self) -> Vec<TraceFrame> {
+        self.frames.iter().rev()
+            .map(|frame| {
+                // The IP has moved past at least the opcode being executed
+                let line = self.chunks.get(frame.chunk_index)
+                    .and_then(|chunk| chunk.lines.get(frame.ip.saturating_sub(1)))
+                    .copied()
+                    .unwrap_or(0);
+                TraceFrame { function: frame.name.clone(), line }
+            })
+            .collect()
+    }
+
+    // ═══════════════════════════════════════════════════════════════
+    // GENERATOR FRAMES
+    // ═══════════════════════════════════════════════════════════════
+
+    /// Take the current frame off the call stack to resume it later, along
+    /// with the loops it has open and the upvalues of its variables
+    pub fn suspend_frame(&mut self) -> Result<SuspendedFrame, ContextError> {
+        let depth = self.frames.len().checked_sub(1).ok_or(ContextError::CallStackUnderflow)?;
+        let open_loops = self.loop_depth - self.frames[depth].loop_depth;
+
+        let loop_bindings = self.loop_bindings.split_off(self.loop_bindings.len() - open_loops);
+        let accumulators = self.accumulator_stack.split_off(self.accumulator_stack.len() - open_loops);
+        let accumulator = self.accumulator;
+        if let Some(&outer) = accumulators.first() {
+            self.accumulator = outer;
+        }
+        self.loop_depth -= open_loops;
+
+        // Closures made by the frame keep their cells, which hold the
+        // variables' values until the frame is resumed
+        let mut upvalues = Vec::new();
+        for cell in &self.open_upvalues {
+            if let Upvalue::Open { depth: d, name } = &*cell.borrow() {
+                if *d == depth {
+                    upvalues.push((name.clone(), cell.clone()));
+                }
+            }
+        }
+        self.close_upvalues(depth, None);
+
+        let frame = self.frames.pop().ok_or(ContextError::CallStackUnderflow)?;
+        Ok(SuspendedFrame { frame, stack: Vec::new(), loop_bindings, accumulators, accumulator, upvalues })
+    }
+
+    /// Put a suspended frame back on top of the call stack, its stack
+    /// segment having been pushed by the caller
+    pub fn resume_frame(&mut self, suspended: SuspendedFrame) -> Result<(), ContextError> {
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(ContextError::CallStackOverflow);
+        }
+        let SuspendedFrame { mut frame, loop_bindings, mut accumulators, accumulator, upvalues, .. } = suspended;
+        let depth = self.frames.len();
+
+        frame.loop_depth = self.loop_depth;
+        if let Some(outer) = accumulators.first_mut() {
+            *outer = self.accumulator;
+            self.accumulator = accumulator;
+        }
+        self.accumulator_stack.extend(accumulators);
+        self.loop_depth += loop_bindings.len();
+        self.loop_bindings.extend(loop_bindings);
+
+        // Variables may have been assigned through a closure meanwhile
+        for (name, cell) in upvalues {
+            let value = match &*cell.borrow() {
+                Upvalue::Closed(value) => value.clone(),
+                Upvalue::Open { .. } => continue,
+            };
+            if let Some(var) = frame.scope.get_mut(&name) {
+                var.value = value;
+            }
+            *cell.borrow_mut() = Upvalue::Open { depth, name };
+            self.open_upvalues.push(cell);
+        }
+
+        self.frames.push(frame);
+        Ok(())
+    }
+
     // ═══════════════════════════════════════════════════════════════
     // VARIABLE MANAGEMENT
     // ═══════════════════════════════════════════════════════════════
@@ -311,6 +547,13 @@ impl Context {
     ) -> Result<(), ContextError> {
         let var = Variable::new(value, var_type, optional);
 
+        // Declarations in a loop body belong to the loop
+        let frame_loops = self.frames.last().map_or(0, |frame| frame.loop_depth);
+        if self.loop_depth > frame_loops {
+            self.bind_in_loop(name, var);
+            return Ok(());
+        }
+
         if let Some(frame) = self.frames.last_mut() {
             frame.scope.declare(name, var)
         } else {
@@ -318,6 +561,50 @@ impl Context {
         }
     }
 
+    /// Declare a variable, replacing any variable of the same name in the
+    /// current scope (used for ☊ bindings, which may repeat)
+    pub fn bind_variable(&mut self, name: String, value: Value) {
+        let var = Variable::new(value, ValueType::Null, true);
+
+        let frame_loops = self.frames.last().map_or(0, |frame| frame.loop_depth);
+        if self.loop_depth > frame_loops {
+            self.bind_in_loop(name, var);
+            return;
+        }
+
+        match self.frames.last_mut() {
+            Some(frame) => frame.scope.insert(name, var),
+            None => self.globals.insert(name, var),
+        };
+    }
+
+    /// Bind a for-each loop variable for the innermost loop
+    pub fn bind_loop_variable(&mut self, name: &str, value: Value) {
+        let var_type = value.value_type();
+        self.bind_in_loop(name.to_string(), Variable::new(value, var_type, true));
+    }
+
+    /// Bind a variable until the innermost loop ends, shadowing any variable
+    /// of the same name; binding it again on a later iteration replaces it
+    fn bind_in_loop(&mut self, name: String, var: Variable) {
+        // Closures made on an earlier iteration keep that iteration's variable
+        if let Some(depth) = self.frames.len().checked_sub(1) {
+            self.close_upvalues(depth, Some(&name));
+        }
+
+        let scope = match self.frames.last_mut() {
+            Some(frame) => &mut frame.scope,
+            None => &mut self.globals,
+        };
+        let previous = scope.insert(name.clone(), var);
+
+        if let Some(bindings) = self.loop_bindings.last_mut() {
+            if !bindings.iter().any(|(bound, _)| *bound == name) {
+                bindings.push((name, previous));
+            }
+        }
+    }
+
     /// Get a variable value, searching scopes from innermost to global
     pub fn get_variable(&self, name: &str) -> Result<&Value, ContextError> {
         // Search local scopes first (innermost to outermost)
@@ -335,6 +622,34 @@ impl Context {
         Err(ContextError::UndeclaredVariable { name: name.to_string() })
     }
 
+    /// Get a mutable reference to a variable's value, for in-place updates
+    ///
+    /// The caller is responsible for keeping the value's type unchanged.
+    pub fn get_variable_mut(&mut self, name: &str) -> Result<&mut Value, ContextError> {
+        for frame in self.frames.iter_mut().rev() {
+            if let Some(var) = frame.scope.get_mut(name) {
+                return Ok(&mut var.value);
+            }
+        }
+
+        self.globals
+            .get_mut(name)
+            .map(|var| &mut var.value)
+            .ok_or_else(|| ContextError::UndeclaredVariable { name: name.to_string() })
+    }
+
+    /// Run `f` on a variable's value in place; a variable the running closure
+    /// captured is reached through its upvalue
+    pub fn with_variable_mut<R>(&mut self, name: &str, f: impl FnOnce(&mut Value) -> R) -> Result<R, ContextError> {
+        let local = self.frames.last().is_some_and(|frame| frame.scope.contains(name));
+        if !local {
+            if let Some(cell) = self.closure_upvalue(name) {
+                return self.with_upvalue(&cell, f);
+            }
+        }
+        self.get_variable_mut(name).map(f)
+    }
+
     /// Set a variable value
     pub fn set_variable(&mut self, name: &str, value: Value) -> Result<(), ContextError> {
         // Search local scopes first
@@ -397,6 +712,82 @@ impl Context {
         }
     }
 
+    // ═══════════════════════════════════════════════════════════════
+    // UPVALUES (closures)
+    // ═══════════════════════════════════════════════════════════════
+
+    /// Capture a variable for a new closure
+    ///
+    /// A variable of the current frame gets an open upvalue, shared with any
+    /// closure that captured it before; a variable the running closure
+    /// captured itself passes on that closure's upvalue.
+    pub fn capture_variable(&mut self, name: &str) -> Result<UpvalueCell, ContextError> {
+        let undeclared = || ContextError::UndeclaredVariable { name: name.to_string() };
+        let depth = self.frames.len().checked_sub(1).ok_or_else(undeclared)?;
+
+        if self.frames[depth].scope.contains(name) {
+            let open = self.open_upvalues.iter().find(|cell| {
+                matches!(&*cell.borrow(), Upvalue::Open { depth: d, name: n } if *d == depth && n == name)
+            });
+            if let Some(cell) = open {
+                return Ok(cell.clone());
+            }
+            let cell = Rc::new(RefCell::new(Upvalue::Open { depth, name: name.to_string() }));
+            self.open_upvalues.push(cell.clone());
+            return Ok(cell);
+        }
+
+        self.closure_upvalue(name).ok_or_else(undeclared)
+    }
+
+    /// The running closure's upvalue for a captured variable name
+    pub fn closure_upvalue(&self, name: &str) -> Option<UpvalueCell> {
+        let frame = self.frames.last()?;
+        let closure = frame.closure.as_ref()?;
+        let function = self.chunks.get(frame.chunk_index)?.get_function(closure.function_index)?;
+        let position = function.capture_names.iter().position(|captured| captured == name)?;
+        closure.captures.get(position).cloned()
+    }
+
+    /// Run `f` on the variable an upvalue refers to
+    pub fn with_upvalue<R>(&mut self, cell: &UpvalueCell, f: impl FnOnce(&mut Value) -> R) -> Result<R, ContextError> {
+        match &mut *cell.borrow_mut() {
+            Upvalue::Closed(value) => Ok(f(value)),
+            Upvalue::Open { depth, name } => self.frames.get_mut(*depth)
+                .and_then(|frame| frame.scope.get_mut(name))
+                .map(|var| f(&mut var.value))
+                .ok_or_else(|| ContextError::UndeclaredVariable { name: name.clone() }),
+        }
+    }
+
+    /// Assign a captured variable, keeping its type like `set_variable`
+    pub fn store_upvalue(&mut self, cell: &UpvalueCell, value: Value) -> Result<(), ContextError> {
+        self.with_upvalue(cell, |target| {
+            let (expected, actual) = (target.value_type(), value.value_type());
+            if expected != actual && !matches!(target, Value::Null) && !matches!(value, Value::Null) {
+                return Err(ContextError::TypeMismatch { expected: expected.to_string(), actual: actual.to_string() });
+            }
+            *target = value;
+            Ok(())
+        })?
+    }
+
+    /// Close the open upvalues of the frame at `depth` (only the one for
+    /// `name`, if given), moving the variables' values into them
+    fn close_upvalues(&mut self, depth: usize, name: Option<&str>) {
+        let frames = &self.frames;
+        self.open_upvalues.retain(|cell| {
+            let value = match &*cell.borrow() {
+                Upvalue::Open { depth: d, name: n } if *d == depth && name.is_none_or(|name| name == n) => {
+                    frames[depth].scope.get(n).map_or(Value::Null, |var| var.value.clone())
+                }
+                _ => return true,
+            };
+            *cell.borrow_mut() = Upvalue::Closed(value);
+            false
+        });
+    }
+
     // ═══════════════════════════════════════════════════════════════
     // FUNCTION MANAGEMENT
     // ═══════════════════════════════════════════════════════════════
@@ -451,6 +842,27 @@ impl Context {
         }
     }
 
+    /// Get the accumulator `depth` loops out (0 is the current one)
+    pub fn outer_accumulator(&self, depth: usize) -> Option<i64> {
+        match depth {
+            0 => Some(self.accumulator),
+            _ => self.accumulator_stack.len().checked_sub(depth)
+                .map(|slot| self.accumulator_stack[slot]),
+        }
+    }
+
+    /// Set the accumulator `depth` loops out, returning false if there is none
+    pub fn set_outer_accumulator(&mut self, depth: usize, value: i64) -> bool {
+        match depth {
+            0 => self.accumulator = value,
+            _ => match self.accumulator_stack.len().checked_sub(depth) {
+                Some(slot) => self.accumulator_stack[slot] = value,
+                None => return false,
+            },
+        }
+        true
+    }
+
     // ═══════════════════════════════════════════════════════════════
     // LOOP MANAGEMENT
     // ═══════════════════════════════════════════════════════════════
@@ -458,13 +870,31 @@ impl Context {
     /// Enter a loop
     pub fn enter_loop(&mut self) {
         self.loop_depth += 1;
+        self.loop_bindings.push(Vec::new());
     }
 
-    /// Exit a loop
+    /// Exit a loop, dropping its variables and restoring the ones they shadowed
     pub fn exit_loop(&mut self) {
         if self.loop_depth > 0 {
             self.loop_depth -= 1;
         }
+        if let Some(bindings) = self.loop_bindings.pop() {
+            if let Some(depth) = self.frames.len().checked_sub(1) {
+                for (name, _) in &bindings {
+                    self.close_upvalues(depth, Some(name));
+                }
+            }
+            let scope = match self.frames.last_mut() {
+                Some(frame) => &mut frame.scope,
+                None => &mut self.globals,
+            };
+            for (name, previous) in bindings.into_iter().rev() {
+                match previous {
+                    Some(var) => scope.insert(name, var),
+                    None => scope.remove(&name),
+                };
+            }
+        }
         self.break_flag = false;
         self.continue_flag = false;
     }
@@ -473,6 +903,19 @@ impl Context {
     pub fn in_loop(&self) -> bool {
         self.loop_depth > 0
     }
+
+    /// Number of loops currently open
+    pub fn loop_depth(&self) -> usize {
+        self.loop_depth
+    }
+
+    /// Close loops (and their accumulator frames) until `depth` remain open
+    pub fn unwind_loops(&mut self, depth: usize) {
+        while self.loop_depth > depth {
+            self.pop_accumulator();
+            self.exit_loop();
+        }
+    }
 }
 
 impl Default for Context {
@@ -524,4 +967,25 @@ mod tests {
         ctx.pop_accumulator();
         assert_eq!(ctx.accumulator(), 10);
     }
+
+    #[test]
+    fn test_outer_accumulator() {
+        let mut ctx = Context::new();
+
+        ctx.set_accumulator(1);
+        ctx.enter_loop();
+        ctx.push_accumulator(2);
+        ctx.enter_loop();
+        ctx.push_accumulator(3);
+
+        assert_eq!(ctx.outer_accumulator(0), Some(3));
+        assert_eq!(ctx.outer_accumulator(2), Some(1));
+        assert_eq!(ctx.outer_accumulator(3), None);
+        assert!(ctx.set_outer_accumulator(1, 20));
+        assert!(!ctx.set_outer_accumulator(3, 0));
+
+        ctx.unwind_loops(1);
+        assert_eq!(ctx.accumulator(), 20);
+        assert_eq!(ctx.loop_depth(), 1);
+    }
 }
@@ -0,0 +1,327 @@
+//! # VM Files
+//!
+//! Open file handles for the Obfusku virtual machine.
+//! Spells refer to files through `FileHandle` values, which index into
+//! the runtime's file table. Embedders may restrict which directories
+//! spells are allowed to touch with an allowlist.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// File-related errors
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum FileError {
+    #[error("Path '{path}' is outside the allowed directories")]
+    Forbidden { path: String },
+
+    #[error("Unknown file mode '{mode}' (expected \"r\", \"w\" or \"a\")")]
+    InvalidMode { mode: String },
+
+    #[error("File handle #{handle} is closed or was never opened")]
+    Closed { handle: usize },
+
+    #[error("File handle #{handle} was not opened for {operation}")]
+    WrongMode { handle: usize, operation: String },
+
+    #[error("Cannot access '{path}': {reason}")]
+    Io { path: String, reason: String },
+}
+
+/// How a file is opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileMode {
+    Read,
+    Write,
+    Append,
+}
+
+impl FileMode {
+    /// Parse a mode string ("r", "w" or "a")
+    pub fn parse(mode: &str) -> Result<Self, FileError> {
+        match mode {
+            "r" => Ok(FileMode::Read),
+            "w" => Ok(FileMode::Write),
+            "a" => Ok(FileMode::Append),
+            _ => Err(FileError::InvalidMode { mode: mode.to_string() }),
+        }
+    }
+}
+
+/// Which directories spells may access
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum FilePolicy {
+    /// Any path may be opened
+    #[default]
+    Unrestricted,
+    /// Only paths inside these (canonical) directories may be opened
+    Allowlist(Vec<PathBuf>),
+}
+
+/// An open file
+enum OpenFile {
+    Reader { path: String, reader: BufReader<File> },
+    Writer { path: String, writer: BufWriter<File> },
+}
+
+/// The table of files opened by a running spell
+///
+/// A handle is the index of its slot; closed slots are never reused
+/// so a stale handle can't silently refer to another file.
+#[derive(Default)]
+pub struct FileTable {
+    files: Vec<Option<OpenFile>>,
+    policy: FilePolicy,
+}
+
+impl FileTable {
+    /// Create an unrestricted file table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restrict access to the given directory (and everything below it)
+    ///
+    /// The first call switches the table from unrestricted to allowlist mode.
+    pub fn allow_directory(&mut self, dir: impl AsRef<Path>) -> Result<(), FileError> {
+        let dir = dir.as_ref();
+        let canonical = dir.canonicalize().map_err(|e| io_error(dir, e))?;
+
+        match &mut self.policy {
+            FilePolicy::Unrestricted => self.policy = FilePolicy::Allowlist(vec![canonical]),
+            FilePolicy::Allowlist(dirs) => dirs.push(canonical),
+        }
+        Ok(())
+    }
+
+    /// The active access policy
+    pub fn policy(&self) -> &FilePolicy {
+        &self.policy
+    }
+
+    /// Open a file, returning its handle
+    pub fn open(&mut self, path: &str, mode: FileMode) -> Result<usize, FileError> {
+        let path_ref = Path::new(path);
+        self.check_access(path_ref)?;
+
+        let file = match mode {
+            FileMode::Read => {
+                let file = File::open(path_ref).map_err(|e| io_error(path_ref, e))?;
+                OpenFile::Reader { path: path.to_string(), reader: BufReader::new(file) }
+            }
+            FileMode::Write | FileMode::Append => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(mode == FileMode::Write)
+                    .append(mode == FileMode::Append)
+                    .open(path_ref)
+                    .map_err(|e| io_error(path_ref, e))?;
+                OpenFile::Writer { path: path.to_string(), writer: BufWriter::new(file) }
+            }
+        };
+        // The path may have been swapped for a link between the check and the open
+        self.check_access(path_ref)?;
+
+        self.files.push(Some(file));
+        Ok(self.files.len() - 1)
+    }
+
+    /// Read the next line (without its terminator), or `None` at end of file
+    pub fn read_line(&mut self, handle: usize) -> Result<Option<String>, FileError> {
+        let (path, reader) = self.reader(handle, "reading")?;
+        let mut line = String::new();
+        let read = reader.read_line(&mut line).map_err(|e| io_error(Path::new(path), e))?;
+
+        if read == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    /// Read everything remaining in the file
+    pub fn read_all(&mut self, handle: usize) -> Result<String, FileError> {
+        let (path, reader) = self.reader(handle, "reading")?;
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).map_err(|e| io_error(Path::new(path), e))?;
+        Ok(contents)
+    }
+
+    /// Write text to a file opened with "w" or "a"
+    pub fn write(&mut self, handle: usize, text: &str) -> Result<(), FileError> {
+        match self.slot(handle)? {
+            OpenFile::Writer { path, writer } => writer
+                .write_all(text.as_bytes())
+                .map_err(|e| io_error(Path::new(path), e)),
+            OpenFile::Reader { .. } => Err(FileError::WrongMode {
+                handle,
+                operation: "writing".to_string(),
+            }),
+        }
+    }
+
+    /// Close a handle, flushing pending writes
+    pub fn close(&mut self, handle: usize) -> Result<(), FileError> {
+        let file = self.files.get_mut(handle)
+            .and_then(Option::take)
+            .ok_or(FileError::Closed { handle })?;
+
+        if let OpenFile::Writer { path, mut writer } = file {
+            writer.flush().map_err(|e| io_error(Path::new(&path), e))?;
+        }
+        Ok(())
+    }
+
+    /// Check a path against the allowlist
+    fn check_access(&self, path: &Path) -> Result<(), FileError> {
+        let dirs = match &self.policy {
+            FilePolicy::Unrestricted => return Ok(()),
+            FilePolicy::Allowlist(dirs) => dirs,
+        };
+
+        // A file being created doesn't exist yet, so resolve its parent
+        // instead; a dangling symlink is refused, as creating the file
+        // would follow it wherever it points
+        let resolved = match path.canonicalize() {
+            Ok(p) => Some(p),
+            Err(_) if path.symlink_metadata().is_ok() => None,
+            Err(_) => {
+                let parent = match path.parent() {
+                    Some(p) if !p.as_os_str().is_empty() => p,
+                    _ => Path::new("."),
+                };
+                match (parent.canonicalize(), path.file_name()) {
+                    (Ok(p), Some(name)) => Some(p.join(name)),
+                    _ => None,
+                }
+            }
+        };
+
+        match resolved {
+            Some(p) if dirs.iter().any(|dir| p.starts_with(dir)) => Ok(()),
+            _ => Err(FileError::Forbidden { path: path.display().to_string() }),
+        }
+    }
+
+    fn slot(&mut self, handle: usize) -> Result<&mut OpenFile, FileError> {
+        self.files.get_mut(handle)
+            .and_then(Option::as_mut)
+            .ok_or(FileError::Closed { handle })
+    }
+
+    fn reader(&mut self, handle: usize, operation: &str) -> Result<(&str, &mut BufReader<File>), FileError> {
+        match self.slot(handle)? {
+            OpenFile::Reader { path, reader } => Ok((path.as_str(), reader)),
+            OpenFile::Writer { .. } => Err(FileError::WrongMode {
+                handle,
+                operation: operation.to_string(),
+            }),
+        }
+    }
+}
+
+impl Drop for FileTable {
+    fn drop(&mut self) {
+        // Flush writers a spell forgot to close
+        for file in self.files.iter_mut().flatten() {
+            if let OpenFile::Writer { writer, .. } = file {
+                let _ = writer.flush();
+            }
+        }
+    }
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> FileError {
+    FileError::Io { path: path.display().to_string(), reason: error.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("obfusku-files-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_write_then_read() {
+        let dir = scratch_dir("rw");
+        let path = dir.join("scroll.txt");
+        let path = path.to_str().unwrap();
+        let mut files = FileTable::new();
+
+        let h = files.open(path, FileMode::Write).unwrap();
+        files.write(h, "first\nsecond\n").unwrap();
+        files.close(h).unwrap();
+
+        let h = files.open(path, FileMode::Append).unwrap();
+        files.write(h, "third").unwrap();
+        files.close(h).unwrap();
+
+        let h = files.open(path, FileMode::Read).unwrap();
+        assert_eq!(files.read_line(h).unwrap(), Some("first".to_string()));
+        assert_eq!(files.read_all(h).unwrap(), "second\nthird");
+        assert_eq!(files.read_line(h).unwrap(), None);
+        assert!(matches!(files.write(h, "x"), Err(FileError::WrongMode { .. })));
+        files.close(h).unwrap();
+        assert_eq!(files.close(h), Err(FileError::Closed { handle: h }));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let allowed = scratch_dir("allowed");
+        let other = scratch_dir("other");
+        let mut files = FileTable::new();
+        files.allow_directory(&allowed).unwrap();
+
+        let inside = allowed.join("ok.txt");
+        assert!(files.open(inside.to_str().unwrap(), FileMode::Write).is_ok());
+
+        let outside = other.join("nope.txt");
+        assert!(matches!(
+            files.open(outside.to_str().unwrap(), FileMode::Write),
+            Err(FileError::Forbidden { .. })
+        ));
+
+        let escape = allowed.join("..").join("escape.txt");
+        assert!(matches!(
+            files.open(escape.to_str().unwrap(), FileMode::Write),
+            Err(FileError::Forbidden { .. })
+        ));
+
+        std::fs::remove_dir_all(allowed).unwrap();
+        std::fs::remove_dir_all(other).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_allowlist_refuses_dangling_symlinks() {
+        let allowed = scratch_dir("sandbox");
+        let other = scratch_dir("outside");
+        let mut files = FileTable::new();
+        files.allow_directory(&allowed).unwrap();
+
+        let target = other.join("pwned.txt");
+        let link = allowed.join("link");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        for mode in [FileMode::Write, FileMode::Append, FileMode::Read] {
+            assert!(matches!(files.open(link.to_str().unwrap(), mode), Err(FileError::Forbidden { .. })));
+        }
+        assert!(!target.exists());
+
+        std::fs::remove_dir_all(allowed).unwrap();
+        std::fs::remove_dir_all(other).unwrap();
+    }
+}
@@ -5,7 +5,9 @@
 pub mod stack;
 pub mod context;
 pub mod runtime;
+pub mod files;
 
 pub use stack::{Stack, StackError};
-pub use context::{Context, ContextError, CallFrame, Variable, Scope, ExceptionHandler};
+pub use context::{Context, ContextError, CallFrame, Variable, Scope, ExceptionHandler, Completion, Exception, TraceFrame};
+pub use files::{FileError, FileMode, FilePolicy, FileTable};
 pub use runtime::{Runtime, RuntimeError, RuntimeResult};
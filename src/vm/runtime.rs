@@ -11,10 +11,15 @@
 //! - Support extensibility through the context system
 //! - Maintain the ritualistic feel of Obfusku
 
-use crate::bytecode::{Chunk, OpCode, Value, ValueType};
-use crate::vm::context::{CallFrame, Context, ContextError};
+use crate::bytecode::{Chunk, Closure, Composition, Field, FieldType, FunctionInfo, GeneratorState, IterHandle, IterSource, OpCode, OrderedMap, StructValue, TypeDef, UnhashableKey, UpvalueCell, Value, ValueType, VariantValue};
+use crate::interpolate;
+use crate::stdlib::{self, Builtin, BuiltinId};
+use crate::vm::context::{CallFrame, Completion, Context, ContextError, Exception, ExceptionHandler, TraceFrame};
+use crate::vm::files::{FileError, FileMode, FileTable};
 use crate::vm::stack::{Stack, StackError};
 use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::rc::Rc;
 use thiserror::Error;
 
 /// Runtime errors - magical errors for magical code
@@ -47,6 +52,12 @@ pub enum RuntimeError {
     #[error("🚫 Continue used outside of a cycle - there is no next iteration")]
     ContinueOutsideLoop,
 
+    #[error("🔁 A range cannot step by 0 - the cycle would never end")]
+    ZeroStep,
+
+    #[error("✹ ✹[{depth}] reaches past the {loops} enclosing cycle(s)")]
+    AccumulatorOutOfReach { depth: usize, loops: usize },
+
     #[error("⚠️ Universe corruption - spell ended without ❧")]
     UniverseCorruption,
 
@@ -61,11 +72,118 @@ pub enum RuntimeError {
 
     #[error("📊 Array index {index} out of bounds (length {length})")]
     IndexOutOfBounds { index: i64, length: usize },
+
+    #[error("📚 Builtin ⤷{name} refused the ritual: {reason}")]
+    BuiltinError { name: String, reason: String },
+
+    #[error("📂 File ritual failed: {0}")]
+    FileError(#[from] FileError),
+
+    #[error("🔄 Cannot transmute {value} ({from}) into {target}")]
+    ConversionFailed { value: String, from: ValueType, target: ValueType },
+
+    #[error("🧩 {type_name} has no field '{field}'")]
+    UnknownField { type_name: String, field: String },
+
+    #[error("🪞 {type_name} implements no method '{method}'")]
+    NoMethod { type_name: String, method: String },
+
+    #[error("🫙 ⧋ found nothing to fold - an empty collection needs a seed")]
+    EmptyReduce,
+
+    #[error("🎯 {name} takes {expected} argument(s), but {got} were given")]
+    WrongArity { name: String, expected: usize, got: usize },
+
+    #[error("🎯 {name} has no parameter '{param}'")]
+    UnknownArgument { name: String, param: String },
+
+    #[error("🎯 '{param}' is given twice in the call to {name}")]
+    DuplicateArgument { name: String, param: String },
+
+    #[error("⌭ This generator is already running — it cannot be resumed from inside itself")]
+    RunningGenerator,
+
+    #[error("☄️ Uncaught exception: {} — thrown at line {line}, and no ☊ contained it", describe_exception(.value))]
+    UncaughtException { value: Value, line: usize, trace: Vec<TraceFrame> },
+}
+
+impl RuntimeError {
+    /// Whether this error is a fault of the spell that it may ☊ catch,
+    /// rather than a sign of corrupted bytecode or a broken VM
+    pub fn is_catchable(&self) -> bool {
+        match self {
+            RuntimeError::ContextError(error) => !matches!(error, ContextError::CallStackUnderflow),
+            RuntimeError::TypeMismatch { .. }
+            | RuntimeError::DivisionByZero
+            | RuntimeError::ArithmeticOverflow
+            | RuntimeError::ZeroStep
+            | RuntimeError::IoError(_)
+            | RuntimeError::InvalidInput(_)
+            | RuntimeError::FunctionNotFound { .. }
+            | RuntimeError::IndexOutOfBounds { .. }
+            | RuntimeError::BuiltinError { .. }
+            | RuntimeError::FileError(_)
+            | RuntimeError::ConversionFailed { .. }
+            | RuntimeError::UnknownField { .. }
+            | RuntimeError::NoMethod { .. }
+            | RuntimeError::EmptyReduce
+            | RuntimeError::WrongArity { .. }
+            | RuntimeError::UnknownArgument { .. }
+            | RuntimeError::DuplicateArgument { .. }
+            | RuntimeError::RunningGenerator => true,
+            _ => false,
+        }
+    }
+
+    /// The kind a caught error carries, which `☊[e ∈ Kind]` filters on
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RuntimeError::StackError(_) => "StackError",
+            RuntimeError::ContextError(error) => match error {
+                ContextError::UndeclaredVariable { .. } => "UndeclaredVariable",
+                ContextError::DuplicateVariable { .. } => "DuplicateVariable",
+                ContextError::TypeMismatch { .. } => "TypeMismatch",
+                ContextError::NullAssignment { .. } => "NullAssignment",
+                ContextError::CallStackOverflow => "CallStackOverflow",
+                ContextError::CallStackUnderflow => "CallStackUnderflow",
+                ContextError::FunctionNotFound { .. } => "FunctionNotFound",
+            },
+            RuntimeError::TypeMismatch { .. } => "TypeMismatch",
+            RuntimeError::DivisionByZero => "DivisionByZero",
+            RuntimeError::ArithmeticOverflow => "ArithmeticOverflow",
+            RuntimeError::UnknownOpcode(_) => "UnknownOpcode",
+            RuntimeError::InvalidBytecode { .. } => "InvalidBytecode",
+            RuntimeError::BreakOutsideLoop => "BreakOutsideLoop",
+            RuntimeError::ContinueOutsideLoop => "ContinueOutsideLoop",
+            RuntimeError::ZeroStep => "ZeroStep",
+            RuntimeError::AccumulatorOutOfReach { .. } => "AccumulatorOutOfReach",
+            RuntimeError::UniverseCorruption => "UniverseCorruption",
+            RuntimeError::IoError(_) => "IoError",
+            RuntimeError::InvalidInput(_) => "InvalidInput",
+            RuntimeError::FunctionNotFound { .. } => "FunctionNotFound",
+            RuntimeError::IndexOutOfBounds { .. } => "IndexOutOfBounds",
+            RuntimeError::BuiltinError { .. } => "BuiltinError",
+            RuntimeError::FileError(_) => "FileError",
+            RuntimeError::ConversionFailed { .. } => "ConversionFailed",
+            RuntimeError::UnknownField { .. } => "UnknownField",
+            RuntimeError::NoMethod { .. } => "NoMethod",
+            RuntimeError::EmptyReduce => "EmptyReduce",
+            RuntimeError::WrongArity { .. } => "WrongArity",
+            RuntimeError::UnknownArgument { .. } => "UnknownArgument",
+            RuntimeError::DuplicateArgument { .. } => "DuplicateArgument",
+            RuntimeError::RunningGenerator => "RunningGenerator",
+            RuntimeError::UncaughtException { .. } => "UncaughtException",
+        }
+    }
 }
 
 /// Result type for runtime operations
 pub type RuntimeResult<T> = Result<T, RuntimeError>;
 
+/// Maximum number of execution loops nested for callbacks and generators,
+/// each of which runs on the native stack
+const MAX_REENTRIES: usize = 64;
+
 /// The Obfusku Virtual Machine
 ///
 /// Executes bytecode in a stack-based manner with symbolic semantics.
@@ -74,8 +192,25 @@ pub struct Runtime {
     context: Context,
     /// The execution stack
     stack: Stack,
+    /// Values the spell moved aside with ⇑, kept apart from expression temporaries
+    spell_stack: Stack,
     /// Whether to show debug output
     debug_mode: bool,
+    /// Files opened by the spell
+    files: FileTable,
+    /// Command-line arguments handed to the spell
+    args: Vec<String>,
+    /// Status the spell ended with (`❧[status]`)
+    exit_code: i32,
+    /// Call depth of the instruction waiting on the callback being run
+    /// (0 when no instruction is)
+    reentry_depth: usize,
+    /// Execution loops running inside another one for a callback or a
+    /// generator
+    reentries: usize,
+    /// Generators being resumed, innermost last, with the call depth
+    /// their frame runs at
+    generators: Vec<(IterHandle, usize)>,
 }
 
 impl Runtime {
@@ -84,7 +219,14 @@ impl Runtime {
         Self {
             context: Context::new(),
             stack: Stack::new(),
+            spell_stack: Stack::new(),
             debug_mode: false,
+            files: FileTable::new(),
+            args: Vec::new(),
+            exit_code: 0,
+            reentry_depth: 0,
+            reentries: 0,
+            generators: Vec::new(),
         }
     }
 
@@ -93,20 +235,39 @@ impl Runtime {
         self.debug_mode = debug;
     }
 
+    /// Set the arguments returned by `⤷args[]`
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    /// The exit status the spell ended with (0 unless `❧[status]` said otherwise)
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    /// Restrict file access to a directory (and everything below it)
+    ///
+    /// Until the first directory is allowed, spells may open any path.
+    pub fn allow_directory(&mut self, dir: impl AsRef<Path>) -> RuntimeResult<()> {
+        self.files.allow_directory(dir)?;
+        Ok(())
+    }
+
     /// Execute a chunk of bytecode
     pub fn execute(&mut self, chunk: Chunk) -> RuntimeResult<()> {
         let chunk_index = self.context.add_chunk(chunk);
         let frame = CallFrame::new(chunk_index, self.stack.len(), "main".to_string());
         self.context.push_frame(frame)?;
 
-        self.run()
+        self.run_until(0)
     }
 
-    /// Main execution loop
-    fn run(&mut self) -> RuntimeResult<()> {
+    /// Main execution loop, running until the call depth falls back to
+    /// `stop_depth`
+    fn run_until(&mut self, stop_depth: usize) -> RuntimeResult<()> {
         loop {
             // Check halt flag
-            if self.context.halted {
+            if self.context.halted || self.context.call_depth() <= stop_depth {
                 return Ok(());
             }
 
@@ -144,7 +305,18 @@ impl Runtime {
                 frame.ip += 1;
             }
 
-            self.execute_instruction(opcode, chunk_index, ip)?;
+            // A fault of the spell becomes an exception it may catch
+            match self.execute_instruction(opcode, chunk_index, ip) {
+                Err(error) if error.is_catchable() && self.context.has_exception_handler() => {
+                    let exception = self.error_value(error.kind(), &error.to_string());
+                    self.throw(exception)?;
+                }
+                // An exception that escaped a callback, rethrown at the instruction that ran it
+                Err(RuntimeError::UncaughtException { value, trace, .. }) if self.context.has_exception_handler() => {
+                    self.throw_exception(Exception { value, trace })?;
+                }
+                result => result?,
+            }
         }
     }
 
@@ -194,6 +366,32 @@ impl Runtime {
                 self.stack.rotate()?;
             }
 
+            // ═══════════════════════════════════════════════════════════
+            // SPELL STACK OPERATIONS
+            // ═══════════════════════════════════════════════════════════
+
+            OpCode::SpellPush => {
+                let value = self.stack.pop()?;
+                self.spell_stack.push(value)?;
+            }
+
+            OpCode::SpellPop => {
+                let value = self.spell_stack.pop()?;
+                self.stack.push(value)?;
+            }
+
+            OpCode::SpellDup => {
+                self.spell_stack.dup()?;
+            }
+
+            OpCode::SpellSwap => {
+                self.spell_stack.swap()?;
+            }
+
+            OpCode::SpellRot => {
+                self.spell_stack.rotate()?;
+            }
+
             // ═══════════════════════════════════════════════════════════
             // VARIABLE OPERATIONS
             // ═══════════════════════════════════════════════════════════
@@ -447,6 +645,115 @@ impl Runtime {
                 }
             }
 
+            // ═══════════════════════════════════════════════════════════
+            // ITERATION
+            // ═══════════════════════════════════════════════════════════
+
+            OpCode::IterBegin => {
+                // State: the collection being iterated, then the next position
+                let source = match self.stack.pop()? {
+                    source @ (Value::Array(_) | Value::Map(_) | Value::Iterator(_)) => source,
+                    Value::String(s) => Value::array(s.chars().map(Value::Rune).collect()),
+                    other => return Err(RuntimeError::TypeMismatch {
+                        expected: "array, map, string or iterator to iterate".to_string(),
+                        actual: other.value_type().to_string(),
+                    }),
+                };
+                self.stack.push(source)?;
+                self.stack.push(Value::Integer(0))?;
+            }
+
+            OpCode::IterNext => {
+                let bindings = self.read_byte(chunk_index)?;
+                let offset = self.read_u16(chunk_index)?;
+
+                let position = match self.stack.peek()? {
+                    Value::Integer(i) => *i as usize,
+                    _ => return Err(RuntimeError::InvalidBytecode { offset: base_ip }),
+                };
+                let next = match self.stack.peek_at(1)? {
+                    Value::Array(items) => items.get(position)
+                        .map(|item| (Value::Integer(position as i64), item.clone(), false)),
+                    Value::Map(map) => map.get_index(position)
+                        .map(|(key, value)| (key.clone(), value.clone(), true)),
+                    // An iterator's values are counted like an array's elements
+                    Value::Iterator(handle) => {
+                        let handle = handle.clone();
+                        self.iter_next(chunk_index, &handle, base_ip)?
+                            .map(|value| (Value::Integer(position as i64), value, false))
+                    }
+                    _ => return Err(RuntimeError::InvalidBytecode { offset: base_ip }),
+                };
+
+                match next {
+                    Some((key, value, is_map)) => {
+                        self.stack.set_at(0, Value::Integer(position as i64 + 1))?;
+                        // A single variable gets a map's keys and an array's elements
+                        if bindings == 2 || is_map {
+                            self.stack.push(key)?;
+                        }
+                        if bindings == 2 || !is_map {
+                            self.stack.push(value)?;
+                        }
+                    }
+                    None => {
+                        if let Some(frame) = self.context.current_frame_mut() {
+                            frame.ip += offset as usize;
+                        }
+                    }
+                }
+            }
+
+            OpCode::RangeBegin => {
+                // State: end, step, then the next integer
+                let step = self.stack.pop()?;
+                let end = self.stack.pop()?;
+                let start = self.stack.pop()?;
+                let integer = |value: Value| match value {
+                    Value::Integer(i) => Ok(i),
+                    other => Err(RuntimeError::TypeMismatch {
+                        expected: "integer range bound".to_string(),
+                        actual: other.value_type().to_string(),
+                    }),
+                };
+                let (start, end, step) = (integer(start)?, integer(end)?, integer(step)?);
+                if step == 0 {
+                    return Err(RuntimeError::ZeroStep);
+                }
+                self.stack.push(Value::Integer(end))?;
+                self.stack.push(Value::Integer(step))?;
+                self.stack.push(Value::Integer(start))?;
+            }
+
+            OpCode::RangeNext => {
+                let offset = self.read_u16(chunk_index)?;
+
+                let state = (self.stack.peek_at(2)?, self.stack.peek_at(1)?, self.stack.peek()?);
+                let (end, step, current) = match state {
+                    (Value::Integer(end), Value::Integer(step), Value::Integer(current)) => (*end, *step, *current),
+                    _ => return Err(RuntimeError::InvalidBytecode { offset: base_ip }),
+                };
+
+                if (step > 0 && current < end) || (step < 0 && current > end) {
+                    self.stack.set_at(0, Value::Integer(current.saturating_add(step)))?;
+                    self.stack.push(Value::Integer(current))?;
+                } else if let Some(frame) = self.context.current_frame_mut() {
+                    frame.ip += offset as usize;
+                }
+            }
+
+            OpCode::BindLoopVar => {
+                let name_idx = self.read_u16(chunk_index)?;
+                let name = {
+                    let chunk = self.context.get_chunk(chunk_index).unwrap();
+                    chunk.strings.get(name_idx as usize)
+                        .cloned()
+                        .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?
+                };
+                let value = self.stack.pop()?;
+                self.context.bind_loop_variable(&name, value);
+            }
+
             OpCode::Break => {
                 if !self.context.in_loop() {
                     return Err(RuntimeError::BreakOutsideLoop);
@@ -468,40 +775,37 @@ impl Runtime {
             OpCode::Call => {
                 let func_idx = self.read_u16(chunk_index)?;
                 let arity = self.read_byte(chunk_index)?;
+                self.call_function(chunk_index, func_idx as usize, arity, base_ip)?;
+            }
 
-                // Get function info
+            OpCode::Invoke => {
+                let name_idx = self.read_u16(chunk_index)?;
+                let arity = self.read_byte(chunk_index)?;
                 let chunk = self.context.get_chunk(chunk_index).unwrap();
-                let func_info = chunk.get_function(func_idx as usize)
-                    .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?
-                    .clone();
-
-                // Collect arguments from stack
-                let mut args = Vec::with_capacity(arity as usize);
-                for _ in 0..arity {
-                    args.push(self.stack.pop()?);
-                }
-                args.reverse();
-
-                // Create new frame
-                let frame = CallFrame::new(chunk_index, self.stack.len(), func_info.name.clone());
-                self.context.push_frame(frame)?;
+                let method = chunk.strings.get(name_idx as usize)
+                    .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?;
 
-                // Bind parameters to arguments
-                for (i, (param_name, param_type)) in func_info.params.iter().enumerate() {
-                    let value = args.get(i).cloned().unwrap_or(Value::Null);
-                    self.context.declare_variable(param_name.clone(), value, *param_type, false)?;
-                }
+                // The receiver's own type first, then the builtin type it falls under
+                let receiver = self.stack.peek_at(arity as usize)?;
+                let user_def = user_fields(receiver).ok().map(|(def, _, _)| def);
+                let own_type = user_def
+                    .and_then(|def| chunk.types.iter().position(|t| Rc::ptr_eq(t, def)))
+                    .map(|i| FieldType::User(i as u16));
+                let func_idx = own_type.into_iter()
+                    .chain(std::iter::once(FieldType::Value(receiver.value_type())))
+                    .find_map(|target| chunk.method_table(target).and_then(|table| table.method(method)))
+                    .ok_or_else(|| RuntimeError::NoMethod {
+                        type_name: user_def.map_or_else(|| receiver.value_type().to_string(), |def| def.name.clone()),
+                        method: method.clone(),
+                    })?;
 
-                // Jump to function start
-                if let Some(frame) = self.context.current_frame_mut() {
-                    frame.ip = func_info.start;
-                }
+                // The receiver is the method's first argument
+                self.call_function(chunk_index, func_idx as usize, arity + 1, base_ip)?;
             }
 
             OpCode::Return => {
                 let result = self.stack.pop().unwrap_or(Value::Null);
-                self.context.pop_frame()?;
-                self.stack.push(result)?;
+                self.return_value(result)?;
             }
 
             OpCode::DefineFunc => {
@@ -518,77 +822,206 @@ impl Runtime {
 
             OpCode::CallClosure => {
                 let arity = self.read_byte(chunk_index)?;
-                
-                // Pop the closure from stack
-                let closure_val = self.stack.pop()?;
-                
-                match closure_val {
-                    Value::ClosureVal(closure) => {
-                        // Get function info from closure
-                        let chunk = self.context.get_chunk(chunk_index).unwrap();
-                        let func_info = chunk.get_function(closure.function_index)
-                            .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?
-                            .clone();
-                        
-                        // Collect arguments from stack
-                        let mut args = Vec::with_capacity(arity as usize);
-                        for _ in 0..arity {
-                            args.push(self.stack.pop()?);
-                        }
-                        args.reverse();
-                        
-                        // Create new frame with closure environment
-                        let frame = CallFrame::with_closure(
-                            chunk_index, 
-                            self.stack.len(), 
-                            func_info.name.clone(),
-                            (*closure).clone()
-                        );
-                        self.context.push_frame(frame)?;
-                        
-                        // Bind parameters to arguments
-                        for (i, (param_name, param_type)) in func_info.params.iter().enumerate() {
-                            let value = args.get(i).cloned().unwrap_or(Value::Null);
-                            self.context.declare_variable(param_name.clone(), value, *param_type, false)?;
-                        }
-                        
-                        // Jump to function start
-                        if let Some(frame) = self.context.current_frame_mut() {
-                            frame.ip = func_info.start;
+                let callee = self.stack.pop()?;
+                let args = self.stack.pop_n(arity as usize)?;
+                self.call_callable(chunk_index, callee, args, None, base_ip)?;
+            }
+
+            OpCode::CallNamed => {
+                let func_idx = self.read_u16(chunk_index)?;
+                let arity = self.read_byte(chunk_index)?;
+                let named = self.pop_named_arguments()?;
+                let args = self.stack.pop_n(arity as usize)?;
+                self.enter_function(chunk_index, func_idx as usize, None, args, Some(&named), base_ip)?;
+            }
+
+            OpCode::CallClosureNamed => {
+                let arity = self.read_byte(chunk_index)?;
+                let callee = self.stack.pop()?;
+                let named = self.pop_named_arguments()?;
+                let args = self.stack.pop_n(arity as usize)?;
+                self.call_callable(chunk_index, callee, args, Some(&named), base_ip)?;
+            }
+
+            OpCode::TailCall => {
+                let func_idx = self.read_u16(chunk_index)?;
+                let arity = self.read_byte(chunk_index)?;
+                let args = self.stack.pop_n(arity as usize)?;
+                self.leave_for_tail_call()?;
+                self.enter_function(chunk_index, func_idx as usize, None, args, None, base_ip)?;
+            }
+
+            OpCode::TailCallClosure => {
+                let arity = self.read_byte(chunk_index)?;
+                let callee = self.stack.pop()?;
+                let args = self.stack.pop_n(arity as usize)?;
+                self.leave_for_tail_call()?;
+                self.call_callable(chunk_index, callee, args, None, base_ip)?;
+            }
+
+            OpCode::Yield => {
+                let value = self.stack.pop()?;
+                let depth = self.context.call_depth();
+                let handle = match self.generators.last() {
+                    Some((handle, running)) if *running == depth => handle.clone(),
+                    _ => return Err(RuntimeError::InvalidBytecode { offset: base_ip }),
+                };
+
+                // The frame's stack segment goes with it, loop state and all
+                let base_pointer = self.context.current_frame()
+                    .ok_or(RuntimeError::UniverseCorruption)?
+                    .base_pointer;
+                let segment = self.stack.pop_n(self.stack.len() - base_pointer)?;
+                let mut suspended = self.context.suspend_frame()?;
+                suspended.stack = segment;
+                *handle.source().borrow_mut() = IterSource::Generator(GeneratorState::Suspended(Box::new(suspended)));
+                self.stack.push(value)?;
+            }
+
+            OpCode::CallNative => {
+                let builtin_idx = self.read_u16(chunk_index)?;
+                let arity = self.read_byte(chunk_index)?;
+
+                let builtin = stdlib::get(builtin_idx as usize)
+                    .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?;
+                if !builtin.accepts(arity) {
+                    return Err(RuntimeError::InvalidBytecode { offset: base_ip });
+                }
+
+                let args = self.stack.pop_n(arity as usize)?;
+                let result = self.call_builtin(chunk_index, builtin, args, base_ip)?;
+                self.stack.push(result)?;
+            }
+
+            // ═══════════════════════════════════════════════════════════
+            // HIGHER-ORDER OPERATIONS
+            // ═══════════════════════════════════════════════════════════
+
+            OpCode::MapWith => {
+                let callee = self.stack.pop()?;
+                let collection = self.stack.pop()?;
+
+                // An iterator is mapped lazily, a value at a time
+                let mapped = match collection {
+                    Value::Iterator(source) => Value::Iterator(IterHandle::new(IterSource::Mapped { source, callee })),
+                    collection => {
+                        let mut elements = Vec::new();
+                        let mut entries = OrderedMap::new();
+                        self.each_element(chunk_index, &collection, &callee, 2, base_ip, |_, key, result| {
+                            match key {
+                                Some(key) => { entries.insert(key, result).map_err(unhashable)?; }
+                                None => elements.push(result),
+                            }
+                            Ok(())
+                        })?;
+
+                        match collection {
+                            Value::Map(_) => Value::map(entries),
+                            _ => Value::array(elements),
                         }
                     }
-                    Value::Function(func_idx) => {
-                        // Also support calling regular functions
-                        let chunk = self.context.get_chunk(chunk_index).unwrap();
-                        let func_info = chunk.get_function(func_idx)
-                            .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?
-                            .clone();
-                        
-                        let mut args = Vec::with_capacity(arity as usize);
-                        for _ in 0..arity {
-                            args.push(self.stack.pop()?);
-                        }
-                        args.reverse();
-                        
-                        let frame = CallFrame::new(chunk_index, self.stack.len(), func_info.name.clone());
-                        self.context.push_frame(frame)?;
-                        
-                        for (i, (param_name, param_type)) in func_info.params.iter().enumerate() {
-                            let value = args.get(i).cloned().unwrap_or(Value::Null);
-                            self.context.declare_variable(param_name.clone(), value, *param_type, false)?;
-                        }
-                        
-                        if let Some(frame) = self.context.current_frame_mut() {
-                            frame.ip = func_info.start;
+                };
+                self.stack.push(mapped)?;
+            }
+
+            OpCode::FilterWith => {
+                let callee = self.stack.pop()?;
+                let collection = self.stack.pop()?;
+
+                let filtered = match collection {
+                    Value::Iterator(source) => Value::Iterator(IterHandle::new(IterSource::Filtered { source, callee })),
+                    collection => {
+                        let mut elements = Vec::new();
+                        let mut entries = OrderedMap::new();
+                        self.each_element(chunk_index, &collection, &callee, 2, base_ip, |element, key, keep| {
+                            if keep.is_truthy() {
+                                match key {
+                                    Some(key) => { entries.insert(key, element).map_err(unhashable)?; }
+                                    None => elements.push(element),
+                                }
+                            }
+                            Ok(())
+                        })?;
+
+                        match collection {
+                            Value::Map(_) => Value::map(entries),
+                            _ => Value::array(elements),
                         }
                     }
-                    other => {
+                };
+                self.stack.push(filtered)?;
+            }
+
+            OpCode::ReduceWith => {
+                let seeded = self.read_byte(chunk_index)? != 0;
+                let callee = self.stack.pop()?;
+                let seed = if seeded { Some(self.stack.pop()?) } else { None };
+                let collection = self.stack.pop()?;
+
+                // An iterator is folded as it goes rather than drained first
+                if let Value::Iterator(handle) = &collection {
+                    let acc = self.fold_iterator(chunk_index, handle, &callee, seed, base_ip)?;
+                    self.stack.push(acc)?;
+                    return Ok(());
+                }
+
+                // Over a map the callback gets the key too when it takes three parameters
+                let items: Vec<(Option<Value>, Value)> = match &collection {
+                    Value::Array(elements) => elements.iter().map(|element| (None, element.clone())).collect(),
+                    Value::Map(entries) => {
+                        let with_key = self.callable_arity(chunk_index, &callee) == Some(3);
+                        entries.iter()
+                            .map(|(key, value)| (with_key.then(|| key.clone()), value.clone()))
+                            .collect()
+                    }
+                    other => return Err(RuntimeError::TypeMismatch {
+                        expected: "array or map".to_string(),
+                        actual: other.value_type().to_string(),
+                    }),
+                };
+
+                // Without a seed the first element starts the fold
+                let mut items = items.into_iter();
+                let mut acc = match seed {
+                    Some(seed) => seed,
+                    None => items.next().map(|(_, value)| value).ok_or(RuntimeError::EmptyReduce)?,
+                };
+                for (key, value) in items {
+                    let args = match key {
+                        Some(key) => vec![acc, key, value],
+                        None => vec![acc, value],
+                    };
+                    acc = self.call_value(chunk_index, &callee, args, base_ip)?;
+                }
+                self.stack.push(acc)?;
+            }
+
+            OpCode::Pipe => {
+                let callee = self.stack.pop()?;
+                let value = self.stack.pop()?;
+                self.call_callable(chunk_index, callee, vec![value], None, base_ip)?;
+            }
+
+            OpCode::Compose => {
+                let then = self.stack.pop()?;
+                let first = self.stack.pop()?;
+                for callee in [&first, &then] {
+                    if self.callable_arity(chunk_index, callee).is_none() {
                         return Err(RuntimeError::TypeMismatch {
                             expected: "closure or function".to_string(),
-                            actual: other.value_type().to_string(),
+                            actual: callee.value_type().to_string(),
                         });
                     }
                 }
+                self.stack.push(Value::Composed(Rc::new(Composition { first, then })))?;
+            }
+
+            OpCode::BindArgs => {
+                let count = self.read_byte(chunk_index)?;
+                let args = self.stack.pop_n(count as usize)?;
+                let callee = self.stack.pop()?;
+                let bound = self.bind_arguments(chunk_index, callee, args)?;
+                self.stack.push(bound)?;
             }
 
             // ═══════════════════════════════════════════════════════════
@@ -597,39 +1030,23 @@ impl Runtime {
 
             OpCode::MakeArray => {
                 let count = self.read_u16(chunk_index)?;
-                let mut elements = Vec::with_capacity(count as usize);
-                for _ in 0..count {
-                    elements.push(self.stack.pop()?);
-                }
-                elements.reverse();
-                self.stack.push(Value::Array(elements))?;
+                let elements = self.stack.pop_n(count as usize)?;
+                self.stack.push(Value::array(elements))?;
             }
 
             OpCode::ArrayGet => {
                 let index = self.stack.pop()?;
                 let array = self.stack.pop()?;
 
-                match (array, index) {
+                match (&array, &index) {
                     (Value::Array(arr), Value::Integer(idx)) => {
-                        let idx = if idx < 0 {
-                            arr.len() as i64 + idx
-                        } else {
-                            idx
-                        };
-
-                        if idx < 0 || idx >= arr.len() as i64 {
-                            return Err(RuntimeError::IndexOutOfBounds {
-                                index: idx,
-                                length: arr.len()
-                            });
-                        }
-
-                        self.stack.push(arr[idx as usize].clone())?;
+                        let idx = resolve_index(*idx, arr.len())?;
+                        self.stack.push(arr[idx].clone())?;
                     }
-                    (arr, idx) => {
+                    _ => {
                         return Err(RuntimeError::TypeMismatch {
                             expected: "array and integer index".to_string(),
-                            actual: format!("{} and {}", arr.value_type(), idx.value_type()),
+                            actual: format!("{} and {}", array.value_type(), index.value_type()),
                         });
                     }
                 }
@@ -638,33 +1055,16 @@ impl Runtime {
             OpCode::ArraySet => {
                 let value = self.stack.pop()?;
                 let index = self.stack.pop()?;
-                let array = self.stack.pop()?;
-
-                match (array, index) {
-                    (Value::Array(mut arr), Value::Integer(idx)) => {
-                        let idx = if idx < 0 {
-                            arr.len() as i64 + idx
-                        } else {
-                            idx
-                        };
-
-                        if idx < 0 || idx >= arr.len() as i64 {
-                            return Err(RuntimeError::IndexOutOfBounds {
-                                index: idx,
-                                length: arr.len()
-                            });
-                        }
+                let mut array = self.stack.pop()?;
 
-                        arr[idx as usize] = value;
-                        self.stack.push(Value::Array(arr))?;
-                    }
-                    (arr, idx) => {
-                        return Err(RuntimeError::TypeMismatch {
-                            expected: "array and integer index".to_string(),
-                            actual: format!("{} and {}", arr.value_type(), idx.value_type()),
-                        });
-                    }
+                if !matches!(array, Value::Array(_)) {
+                    return Err(RuntimeError::TypeMismatch {
+                        expected: "array".to_string(),
+                        actual: array.value_type().to_string(),
+                    });
                 }
+                set_element(&mut array, index, value)?;
+                self.stack.push(array)?;
             }
 
             OpCode::ArrayLen => {
@@ -687,20 +1087,9 @@ impl Runtime {
 
             OpCode::ArrayPush => {
                 let value = self.stack.pop()?;
-                let array = self.stack.pop()?;
-
-                match array {
-                    Value::Array(mut arr) => {
-                        arr.push(value);
-                        self.stack.push(Value::Array(arr))?;
-                    }
-                    other => {
-                        return Err(RuntimeError::TypeMismatch {
-                            expected: "array".to_string(),
-                            actual: other.value_type().to_string(),
-                        });
-                    }
-                }
+                let mut array = self.stack.pop()?;
+                append_element(&mut array, value)?;
+                self.stack.push(array)?;
             }
 
             // ═══════════════════════════════════════════════════════════
@@ -709,29 +1098,25 @@ impl Runtime {
 
             OpCode::MakeMap => {
                 let pair_count = self.read_u16(chunk_index)? as usize;
-                let mut entries = Vec::with_capacity(pair_count);
-                
-                // Pop key-value pairs from stack
-                for _ in 0..pair_count {
-                    let value = self.stack.pop()?;
-                    let key = self.stack.pop()?;
-                    entries.push((key, value));
+                let pairs = self.stack.pop_n(pair_count * 2)?;
+
+                // Later duplicates of a key overwrite earlier ones
+                let mut entries = OrderedMap::with_capacity(pair_count);
+                let mut pairs = pairs.into_iter();
+                while let (Some(key), Some(value)) = (pairs.next(), pairs.next()) {
+                    entries.insert(key, value).map_err(unhashable)?;
                 }
-                entries.reverse();
-                
-                self.stack.push(Value::Map(entries))?;
+
+                self.stack.push(Value::map(entries))?;
             }
 
             OpCode::MapGet => {
                 let key = self.stack.pop()?;
                 let map = self.stack.pop()?;
-                
+
                 match map {
                     Value::Map(entries) => {
-                        let value = entries.iter()
-                            .find(|(k, _)| k == &key)
-                            .map(|(_, v)| v.clone())
-                            .unwrap_or(Value::Null);
+                        let value = entries.get(&key).cloned().unwrap_or(Value::Null);
                         self.stack.push(value)?;
                     }
                     other => {
@@ -746,34 +1131,26 @@ impl Runtime {
             OpCode::MapSet => {
                 let value = self.stack.pop()?;
                 let key = self.stack.pop()?;
-                let map = self.stack.pop()?;
-                
-                match map {
-                    Value::Map(mut entries) => {
-                        // Update existing or add new
-                        if let Some(pos) = entries.iter().position(|(k, _)| k == &key) {
-                            entries[pos] = (key, value);
-                        } else {
-                            entries.push((key, value));
-                        }
-                        self.stack.push(Value::Map(entries))?;
-                    }
-                    other => {
-                        return Err(RuntimeError::TypeMismatch {
-                            expected: "map".to_string(),
-                            actual: other.value_type().to_string(),
-                        });
-                    }
+                let mut map = self.stack.pop()?;
+
+                if !matches!(map, Value::Map(_)) {
+                    return Err(RuntimeError::TypeMismatch {
+                        expected: "map".to_string(),
+                        actual: map.value_type().to_string(),
+                    });
                 }
+                // Update existing or add new
+                set_element(&mut map, key, value)?;
+                self.stack.push(map)?;
             }
 
             OpCode::MapHas => {
                 let key = self.stack.pop()?;
                 let map = self.stack.pop()?;
-                
+
                 match map {
                     Value::Map(entries) => {
-                        let has = entries.iter().any(|(k, _)| k == &key);
+                        let has = entries.contains_key(&key);
                         self.stack.push(Value::Boolean(has))?;
                     }
                     other => {
@@ -788,10 +1165,10 @@ impl Runtime {
             OpCode::MapRemove => {
                 let key = self.stack.pop()?;
                 let map = self.stack.pop()?;
-                
+
                 match map {
                     Value::Map(mut entries) => {
-                        entries.retain(|(k, _)| k != &key);
+                        Rc::make_mut(&mut entries).remove(&key);
                         self.stack.push(Value::Map(entries))?;
                     }
                     other => {
@@ -805,11 +1182,11 @@ impl Runtime {
 
             OpCode::MapKeys => {
                 let map = self.stack.pop()?;
-                
+
                 match map {
                     Value::Map(entries) => {
-                        let keys: Vec<Value> = entries.into_iter().map(|(k, _)| k).collect();
-                        self.stack.push(Value::Array(keys))?;
+                        let keys: Vec<Value> = entries.keys().cloned().collect();
+                        self.stack.push(Value::array(keys))?;
                     }
                     other => {
                         return Err(RuntimeError::TypeMismatch {
@@ -822,11 +1199,11 @@ impl Runtime {
 
             OpCode::MapValues => {
                 let map = self.stack.pop()?;
-                
+
                 match map {
                     Value::Map(entries) => {
-                        let values: Vec<Value> = entries.into_iter().map(|(_, v)| v).collect();
-                        self.stack.push(Value::Array(values))?;
+                        let values: Vec<Value> = entries.values().cloned().collect();
+                        self.stack.push(Value::array(values))?;
                     }
                     other => {
                         return Err(RuntimeError::TypeMismatch {
@@ -837,6 +1214,34 @@ impl Runtime {
                 }
             }
 
+            // ═══════════════════════════════════════════════════════════
+            // IN-PLACE COLLECTION OPERATIONS
+            // ═══════════════════════════════════════════════════════════
+
+            OpCode::Index => {
+                let index = self.stack.pop()?;
+                let container = self.stack.pop()?;
+                let element = index_element(&container, &index)?;
+                self.stack.push(element)?;
+            }
+
+            OpCode::AppendVar => {
+                let name = self.read_string_operand(chunk_index, base_ip)?;
+                let value = self.stack.pop()?;
+
+                // The variable is usually the collection's only owner, so
+                // make_mut appends without copying
+                self.context.with_variable_mut(&name, |target| append_element(target, value))??;
+            }
+
+            OpCode::SetIndexVar => {
+                let name = self.read_string_operand(chunk_index, base_ip)?;
+                let value = self.stack.pop()?;
+                let index = self.stack.pop()?;
+
+                self.context.with_variable_mut(&name, |target| set_element(target, index, value))??;
+            }
+
             // ═══════════════════════════════════════════════════════════
             // CLOSURE OPERATIONS (v1.0.0)
             // ═══════════════════════════════════════════════════════════
@@ -844,61 +1249,33 @@ impl Runtime {
             OpCode::MakeClosure => {
                 let func_idx = self.read_u16(chunk_index)? as usize;
                 let capture_count = self.read_byte(chunk_index)? as usize;
-                
-                // Pop captured values from stack (in order)
-                let mut captures = Vec::with_capacity(capture_count);
-                for _ in 0..capture_count {
-                    captures.push(self.stack.pop()?);
-                }
-                captures.reverse();
-                
+
+                // Capture the variables named by the function, by reference
+                let chunk = self.context.get_chunk(chunk_index).unwrap();
+                let names = chunk.get_function(func_idx)
+                    .filter(|func| func.capture_names.len() == capture_count)
+                    .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?
+                    .capture_names.clone();
+                let captures = names.iter()
+                    .map(|name| self.context.capture_variable(name))
+                    .collect::<Result<Vec<_>, _>>()?;
+
                 let closure = crate::bytecode::Closure::new(func_idx, captures);
                 self.stack.push(Value::ClosureVal(Box::new(closure)))?;
             }
 
             OpCode::LoadCapture => {
                 let capture_idx = self.read_u16(chunk_index)? as usize;
-                
-                // Get current closure from call frame
-                if let Some(frame) = self.context.current_frame() {
-                    if let Some(closure) = &frame.closure {
-                        if let Some(value) = closure.captures.get(capture_idx) {
-                            self.stack.push(value.clone())?;
-                        } else {
-                            return Err(RuntimeError::InvalidBytecode { offset: base_ip });
-                        }
-                    } else {
-                        return Err(RuntimeError::TypeMismatch {
-                            expected: "closure context".to_string(),
-                            actual: "no closure in frame".to_string(),
-                        });
-                    }
-                } else {
-                    return Err(RuntimeError::UniverseCorruption);
-                }
+                let cell = self.capture_cell(capture_idx, base_ip)?;
+                let value = self.context.with_upvalue(&cell, |value| value.clone())?;
+                self.stack.push(value)?;
             }
 
             OpCode::StoreCapture => {
                 let capture_idx = self.read_u16(chunk_index)? as usize;
                 let value = self.stack.pop()?;
-                
-                // Store to current closure's captures
-                if let Some(frame) = self.context.current_frame_mut() {
-                    if let Some(ref mut closure) = frame.closure {
-                        if capture_idx < closure.captures.len() {
-                            closure.captures[capture_idx] = value;
-                        } else {
-                            return Err(RuntimeError::InvalidBytecode { offset: base_ip });
-                        }
-                    } else {
-                        return Err(RuntimeError::TypeMismatch {
-                            expected: "closure context".to_string(),
-                            actual: "no closure in frame".to_string(),
-                        });
-                    }
-                } else {
-                    return Err(RuntimeError::UniverseCorruption);
-                }
+                let cell = self.capture_cell(capture_idx, base_ip)?;
+                self.context.store_upvalue(&cell, value)?;
             }
 
             // ═══════════════════════════════════════════════════════════
@@ -941,9 +1318,133 @@ impl Runtime {
                         .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?
                 };
                 
-                // Bind matched value to variable
-                let value = self.stack.peek()?.clone();
-                self.context.declare_variable(name, value, ValueType::Null, true)?;
+                // Bind matched value to variable, replacing any earlier binding
+                let value = self.stack.pop()?;
+                self.context.bind_variable(name, value);
+            }
+
+            OpCode::MatchType => {
+                let type_byte = self.read_byte(chunk_index)?;
+                let matches = self.stack.peek()?.value_type() as u8 == type_byte;
+                self.stack.push(Value::Boolean(matches))?;
+            }
+
+            OpCode::MatchRange => {
+                let high = self.stack.pop()?;
+                let low = self.stack.pop()?;
+                let value = self.stack.pop()?;
+                // Like ranges in ⊂ loops, a pattern range holds only integers
+                let matches = matches!(value, Value::Integer(_))
+                    && !self.compare_less(value.clone(), low)?
+                    && self.compare_less(value, high)?;
+                self.stack.push(Value::Boolean(matches))?;
+            }
+
+            OpCode::MatchArray => {
+                let length = self.read_byte(chunk_index)? as usize;
+                let has_rest = self.read_byte(chunk_index)? != 0;
+                let matches = match self.stack.peek()? {
+                    Value::Array(arr) if has_rest => arr.len() >= length,
+                    Value::Array(arr) => arr.len() == length,
+                    _ => false,
+                };
+                self.stack.push(Value::Boolean(matches))?;
+            }
+
+            OpCode::MatchEntry => {
+                let key = self.stack.pop()?;
+                let entry = match self.stack.pop()? {
+                    Value::Map(entries) => entries.get(&key).cloned(),
+                    _ => None,
+                };
+                let found = entry.is_some();
+                self.stack.push(entry.unwrap_or(Value::Null))?;
+                self.stack.push(Value::Boolean(found))?;
+            }
+
+            OpCode::MatchRest => {
+                let start = self.read_byte(chunk_index)? as usize;
+                let rest = match self.stack.pop()? {
+                    Value::Array(arr) => arr.get(start..).unwrap_or_default().to_vec(),
+                    other => return Err(RuntimeError::TypeMismatch {
+                        expected: "array".to_string(),
+                        actual: other.value_type().to_string(),
+                    }),
+                };
+                self.stack.push(Value::Array(Rc::new(rest)))?;
+            }
+
+            // ═══════════════════════════════════════════════════════════
+            // USER TYPES
+            // ═══════════════════════════════════════════════════════════
+
+            OpCode::MakeStruct => {
+                let type_idx = self.read_u16(chunk_index)? as usize;
+                let def = self.user_type(chunk_index, type_idx, base_ip)?;
+                let fields = self.pop_fields(chunk_index, &def, 0)?;
+                self.stack.push(Value::Struct(Rc::new(StructValue { def, fields })))?;
+            }
+
+            OpCode::MakeVariant => {
+                let type_idx = self.read_u16(chunk_index)? as usize;
+                let tag = self.read_byte(chunk_index)? as usize;
+                let def = self.user_type(chunk_index, type_idx, base_ip)?;
+                let fields = self.pop_fields(chunk_index, &def, tag)?;
+                self.stack.push(Value::Variant(Rc::new(VariantValue { def, tag, fields })))?;
+            }
+
+            OpCode::GetField => {
+                let name = self.read_string_operand(chunk_index, base_ip)?;
+                let value = self.stack.pop()?;
+                let (def, tag, fields) = user_fields(&value)?;
+                let position = field_position(def, tag, &name)?;
+                let field = fields[position].clone();
+                self.stack.push(field)?;
+            }
+
+            OpCode::SetFieldVar => {
+                let var = self.read_string_operand(chunk_index, base_ip)?;
+                let name = self.read_string_operand(chunk_index, base_ip)?;
+                let value = self.stack.pop()?;
+
+                // Check against the declared type before touching the variable
+                let (def, tag) = {
+                    let current = self.context.with_variable_mut(&var, |target| target.clone())?;
+                    let (def, tag, _) = user_fields(&current)?;
+                    (Rc::clone(def), tag)
+                };
+                let position = field_position(&def, tag, &name)?;
+                self.check_field(chunk_index, &def, &def.fields(tag)[position], &value)?;
+
+                self.context.with_variable_mut(&var, |target| match target {
+                    Value::Struct(s) => Rc::make_mut(s).fields[position] = value,
+                    Value::Variant(v) => Rc::make_mut(v).fields[position] = value,
+                    _ => unreachable!("checked to be a struct or variant"),
+                })?;
+            }
+
+            OpCode::MatchUserType => {
+                let type_idx = self.read_u16(chunk_index)? as usize;
+                let tag = self.read_byte(chunk_index)?;
+                let expected = self.user_type(chunk_index, type_idx, base_ip)?;
+                let matches = match self.stack.peek()? {
+                    Value::Struct(s) => Rc::ptr_eq(&s.def, &expected),
+                    Value::Variant(v) => Rc::ptr_eq(&v.def, &expected)
+                        && (tag == u8::MAX || v.tag == tag as usize),
+                    _ => false,
+                };
+                self.stack.push(Value::Boolean(matches))?;
+            }
+
+            OpCode::FieldAt => {
+                let position = self.read_byte(chunk_index)? as usize;
+                let value = self.stack.pop()?;
+                let (_, _, fields) = user_fields(&value)?;
+                let field = fields.get(position).cloned().ok_or(RuntimeError::IndexOutOfBounds {
+                    index: position as i64,
+                    length: fields.len(),
+                })?;
+                self.stack.push(field)?;
             }
 
             // ═══════════════════════════════════════════════════════════
@@ -972,61 +1473,44 @@ impl Runtime {
             // ═══════════════════════════════════════════════════════════
 
             OpCode::TryBegin => {
-                let handler_offset = self.read_u16(chunk_index)?;
-                
-                // Calculate handler IP
-                let handler_ip = {
-                    let frame = self.context.current_frame()
-                        .ok_or(RuntimeError::UniverseCorruption)?;
-                    frame.ip + handler_offset as usize
-                };
-                
-                // Push exception handler
-                let handler = crate::vm::context::ExceptionHandler::new(
+                // Each offset is relative to the end of its own operand,
+                // and 0 means the block is absent
+                let catch_offset = self.read_u16(chunk_index)?;
+                let catch_base = self.current_ip()?;
+                let finally_offset = self.read_u16(chunk_index)?;
+                let finally_base = self.current_ip()?;
+
+                let handler_ip = (catch_offset != 0).then(|| catch_base + catch_offset as usize);
+                let mut handler = ExceptionHandler::new(
                     handler_ip,
                     self.stack.len(),
                     self.context.call_depth(),
                     chunk_index,
-                );
+                )
+                .with_spell_depth(self.spell_stack.len())
+                .with_loop_depth(self.context.loop_depth())
+                .with_completion_depth(self.context.completion_depth());
+                if finally_offset != 0 {
+                    handler = handler.with_finally(finally_base + finally_offset as usize);
+                }
                 self.context.push_exception_handler(handler);
             }
 
             OpCode::TryEnd => {
-                // Pop exception handler on normal exit
-                self.context.pop_exception_handler();
+                // Leaving the try region - normally, or with ⊗/↺ - runs its
+                // finally block first, which then resumes here
+                let handler = self.context.pop_exception_handler()
+                    .ok_or(RuntimeError::UniverseCorruption)?;
+                if let Some(finally_ip) = handler.finally_ip {
+                    let resume_ip = self.current_ip()?;
+                    self.context.push_completion(Completion::Resume(resume_ip));
+                    self.jump_to(finally_ip)?;
+                }
             }
 
             OpCode::Throw => {
                 let exception = self.stack.pop()?;
-                
-                // Look for exception handler
-                if self.context.has_exception_handler() {
-                    let handler = self.context.pop_exception_handler().unwrap();
-                    
-                    // Restore stack to handler's stack depth
-                    while self.stack.len() > handler.stack_depth {
-                        self.stack.pop()?;
-                    }
-                    
-                    // Unwind call frames if needed
-                    while self.context.call_depth() > handler.frame_depth {
-                        self.context.pop_frame()?;
-                    }
-                    
-                    // Store exception for catch block
-                    self.context.current_exception = Some(exception);
-                    
-                    // Jump to handler
-                    if let Some(frame) = self.context.current_frame_mut() {
-                        frame.ip = handler.handler_ip;
-                    }
-                } else {
-                    // No handler - propagate as runtime error
-                    return Err(RuntimeError::TypeMismatch {
-                        expected: "exception handler".to_string(),
-                        actual: format!("unhandled exception: {}", exception),
-                    });
-                }
+                self.throw(exception)?;
             }
 
             OpCode::Catch => {
@@ -1037,16 +1521,44 @@ impl Runtime {
                         .cloned()
                         .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?
                 };
-                
-                // Bind exception to variable
-                if let Some(exception) = self.context.current_exception.take() {
-                    self.context.declare_variable(name, exception, ValueType::Null, true)?;
-                }
+
+                // Bind exception to variable; each ☊ may reuse the name
+                let exception = self.context.caught_exception()
+                    .map(|exception| exception.value.clone())
+                    .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?;
+                self.context.bind_variable(name, exception);
+            }
+
+            OpCode::LoadException => {
+                let exception = self.context.caught_exception()
+                    .map(|exception| exception.value.clone())
+                    .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?;
+                self.stack.push(exception)?;
+            }
+
+            OpCode::Rethrow => {
+                let exception = self.context.caught_exception()
+                    .cloned()
+                    .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?;
+                self.throw_exception(exception)?;
+            }
+
+            OpCode::ExceptionKind => {
+                let exception = self.stack.pop()?;
+                self.stack.push(exception_kind(&exception))?;
             }
 
             OpCode::Finally => {
-                // Finally blocks execute regardless - handled by compiler jump logic
-                // No runtime action needed
+                // Marks the start of a finally block; it is entered by
+                // TryEnd, Throw or Return, which record how to resume
+            }
+
+            OpCode::EndFinally => {
+                match self.context.pop_completion().ok_or(RuntimeError::UniverseCorruption)? {
+                    Completion::Resume(ip) => self.jump_to(ip)?,
+                    Completion::Throw(exception) => self.throw_exception(exception)?,
+                    Completion::Return(value) => self.return_value(value)?,
+                }
             }
 
             // ═══════════════════════════════════════════════════════════
@@ -1116,6 +1628,42 @@ impl Runtime {
                 self.context.decrement_accumulator();
             }
 
+            OpCode::EnterLoop => {
+                let seed = self.context.accumulator();
+                self.context.enter_loop();
+                self.context.push_accumulator(seed);
+            }
+
+            OpCode::ExitLoop => {
+                let depth = self.context.loop_depth();
+                self.context.unwind_loops(depth.saturating_sub(1));
+            }
+
+            OpCode::LoadOuterAcc => {
+                let depth = self.read_byte(chunk_index)? as usize;
+                let acc = self.context.outer_accumulator(depth)
+                    .ok_or(RuntimeError::AccumulatorOutOfReach {
+                        depth,
+                        loops: self.context.loop_depth(),
+                    })?;
+                self.stack.push(Value::Integer(acc))?;
+            }
+
+            OpCode::StoreOuterAcc => {
+                let depth = self.read_byte(chunk_index)? as usize;
+                let value = self.stack.pop()?;
+                let i = value.to_integer().ok_or_else(|| RuntimeError::TypeMismatch {
+                    expected: "integer".to_string(),
+                    actual: value.value_type().to_string(),
+                })?;
+                if !self.context.set_outer_accumulator(depth, i) {
+                    return Err(RuntimeError::AccumulatorOutOfReach {
+                        depth,
+                        loops: self.context.loop_depth(),
+                    });
+                }
+            }
+
             OpCode::Concat => {
                 let b = self.stack.pop()?;
                 let a = self.stack.pop()?;
@@ -1125,6 +1673,9 @@ impl Runtime {
 
             OpCode::Convert => {
                 let type_byte = self.read_byte(chunk_index)?;
+                if type_byte > ValueType::FileHandle as u8 {
+                    return Err(RuntimeError::InvalidBytecode { offset: base_ip });
+                }
                 let target_type: ValueType = unsafe { std::mem::transmute(type_byte) };
                 let value = self.stack.pop()?;
                 let converted = self.convert_value(value, target_type)?;
@@ -1147,12 +1698,10 @@ impl Runtime {
                 }
                 values.reverse();
 
-                // Perform interpolation: replace {0}, {1}, etc. with values
-                let mut result = template;
-                for (i, value) in values.iter().enumerate() {
-                    let placeholder = format!("{{{}}}", i);
-                    result = result.replace(&placeholder, &value.to_string_value());
-                }
+                // Render {0}, {1:>8.2}, etc. - templates come from the compiler,
+                // so a malformed one means the bytecode is corrupted
+                let result = interpolate::render(&template, &values)
+                    .map_err(|_| RuntimeError::InvalidBytecode { offset: base_ip })?;
 
                 self.stack.push(Value::String(result))?;
             }
@@ -1165,6 +1714,19 @@ impl Runtime {
                 self.context.halted = true;
             }
 
+            OpCode::Exit => {
+                let status = self.stack.pop()?;
+                self.exit_code = match status {
+                    Value::Integer(code) => i32::try_from(code)
+                        .map_err(|_| RuntimeError::ArithmeticOverflow)?,
+                    other => return Err(RuntimeError::TypeMismatch {
+                        expected: ValueType::Integer.to_string(),
+                        actual: other.value_type().to_string(),
+                    }),
+                };
+                self.context.halted = true;
+            }
+
             #[allow(unreachable_patterns)]
             _ => {
                 return Err(RuntimeError::UnknownOpcode(opcode));
@@ -1207,6 +1769,419 @@ impl Runtime {
         Ok((high << 8) | low)
     }
 
+    /// Read a 16-bit string pool index and resolve it
+    fn read_string_operand(&mut self, chunk_index: usize, base_ip: usize) -> RuntimeResult<String> {
+        let idx = self.read_u16(chunk_index)?;
+        let chunk = self.context.get_chunk(chunk_index)
+            .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?;
+        chunk.strings.get(idx as usize)
+            .cloned()
+            .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })
+    }
+
+    /// Upvalue of the running closure at a capture index
+    fn capture_cell(&self, capture_idx: usize, base_ip: usize) -> RuntimeResult<UpvalueCell> {
+        let frame = self.context.current_frame().ok_or(RuntimeError::UniverseCorruption)?;
+        let closure = frame.closure.as_ref().ok_or_else(|| RuntimeError::TypeMismatch {
+            expected: "closure context".to_string(),
+            actual: "no closure in frame".to_string(),
+        })?;
+        closure.captures.get(capture_idx).cloned().ok_or(RuntimeError::InvalidBytecode { offset: base_ip })
+    }
+
+    /// Call a function of the chunk with its arguments on the stack
+    fn call_function(&mut self, chunk_index: usize, func_idx: usize, arity: u8, base_ip: usize) -> RuntimeResult<()> {
+        let args = self.stack.pop_n(arity as usize)?;
+        self.enter_function(chunk_index, func_idx, None, args, None, base_ip)
+    }
+
+    /// The ⌖ of named arguments a call left on the stack
+    fn pop_named_arguments(&mut self) -> RuntimeResult<OrderedMap> {
+        match self.stack.pop()? {
+            Value::Map(named) => Ok(Rc::unwrap_or_clone(named)),
+            other => Err(RuntimeError::TypeMismatch {
+                expected: "named arguments".to_string(),
+                actual: other.value_type().to_string(),
+            }),
+        }
+    }
+
+    /// Push the frame of a function, binding its parameters to the arguments
+    fn enter_function(
+        &mut self,
+        chunk_index: usize,
+        func_idx: usize,
+        closure: Option<Closure>,
+        args: Vec<Value>,
+        named: Option<&OrderedMap>,
+        base_ip: usize,
+    ) -> RuntimeResult<()> {
+        let chunk = self.context.get_chunk(chunk_index).unwrap();
+        let func_info = chunk.get_function(func_idx)
+            .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })?
+            .clone();
+        let values = bind_parameters(&func_info, args, named)?;
+
+        let name = func_info.name.clone();
+        let frame = match closure {
+            Some(closure) => CallFrame::with_closure(chunk_index, self.stack.len(), name, closure),
+            None => CallFrame::new(chunk_index, self.stack.len(), name),
+        };
+        self.context.push_frame(frame)?;
+
+        for ((param_name, param_type), value) in func_info.params.iter().zip(values) {
+            self.context.declare_variable(param_name.clone(), value, *param_type, false)?;
+        }
+        self.jump_to(func_info.start)?;
+
+        // A generator's body waits for the first value to be asked of it
+        if func_info.generator {
+            let suspended = self.context.suspend_frame()?;
+            let state = GeneratorState::Suspended(Box::new(suspended));
+            self.stack.push(Value::Iterator(IterHandle::new(IterSource::Generator(state))))?;
+        }
+        Ok(())
+    }
+
+    /// Call a function value: functions and closures get a frame of their
+    /// own, while composed callables run to completion before this returns
+    fn call_callable(
+        &mut self,
+        chunk_index: usize,
+        callee: Value,
+        args: Vec<Value>,
+        named: Option<&OrderedMap>,
+        base_ip: usize,
+    ) -> RuntimeResult<()> {
+        match callee {
+            Value::Function(func_idx) => self.enter_function(chunk_index, func_idx, None, args, named, base_ip),
+            Value::ClosureVal(mut closure) => {
+                let args = match closure.bound.is_empty() {
+                    true => args,
+                    false => std::mem::take(&mut closure.bound).into_iter().chain(args).collect(),
+                };
+                self.enter_function(chunk_index, closure.function_index, Some(*closure), args, named, base_ip)
+            }
+            Value::Composed(_) => {
+                let result = self.call_value_named(chunk_index, &callee, args, named, base_ip)?;
+                self.stack.push(result)?;
+                Ok(())
+            }
+            other => Err(RuntimeError::TypeMismatch {
+                expected: "closure or function".to_string(),
+                actual: other.value_type().to_string(),
+            }),
+        }
+    }
+
+    /// Call a function value from inside an instruction and wait for its result
+    ///
+    /// The callee runs in a nested execution loop. Handlers outside it are
+    /// left alone: an exception it does not catch comes back as an error,
+    /// which the loop running this instruction throws again.
+    fn call_value(&mut self, chunk_index: usize, callee: &Value, args: Vec<Value>, base_ip: usize) -> RuntimeResult<Value> {
+        self.call_value_named(chunk_index, callee, args, None, base_ip)
+    }
+
+    /// `call_value` with named arguments, which go to the function that runs first
+    fn call_value_named(
+        &mut self,
+        chunk_index: usize,
+        callee: &Value,
+        args: Vec<Value>,
+        named: Option<&OrderedMap>,
+        base_ip: usize,
+    ) -> RuntimeResult<Value> {
+        if self.context.halted {
+            return Ok(Value::Null);
+        }
+        if let Value::Composed(composition) = callee {
+            let middle = self.call_value_named(chunk_index, &composition.first, args, named, base_ip)?;
+            return self.call_value(chunk_index, &composition.then, vec![middle], base_ip);
+        }
+
+        if self.reentries >= MAX_REENTRIES {
+            return Err(ContextError::CallStackOverflow.into());
+        }
+
+        let depth = self.context.call_depth();
+        self.call_callable(chunk_index, callee.clone(), args, named, base_ip)?;
+        self.run_nested(depth)?;
+
+        if self.context.halted {
+            return Ok(Value::Null);
+        }
+        Ok(self.stack.pop()?)
+    }
+
+    /// Run the frames above `depth` in a nested execution loop, on behalf
+    /// of the instruction waiting at `depth`
+    fn run_nested(&mut self, depth: usize) -> RuntimeResult<()> {
+        let outer_depth = std::mem::replace(&mut self.reentry_depth, depth);
+        self.reentries += 1;
+        let result = self.run_until(depth);
+        self.reentries -= 1;
+        self.reentry_depth = outer_depth;
+        result
+    }
+
+    /// The function a function value calls first, with the number of
+    /// arguments already bound to it
+    fn callable_function(&self, chunk_index: usize, callee: &Value) -> Option<(&FunctionInfo, usize)> {
+        let (func_idx, bound) = match callee {
+            Value::Function(func_idx) => (*func_idx, 0),
+            Value::ClosureVal(closure) => (closure.function_index, closure.bound.len()),
+            Value::Composed(composition) => return self.callable_function(chunk_index, &composition.first),
+            _ => return None,
+        };
+        self.context.get_chunk(chunk_index)
+            .and_then(|chunk| chunk.get_function(func_idx))
+            .map(|func| (func, bound))
+    }
+
+    /// Number of arguments a function value still needs, if it is one
+    fn callable_arity(&self, chunk_index: usize, callee: &Value) -> Option<usize> {
+        self.callable_function(chunk_index, callee)
+            .map(|(func, bound)| func.required_params().saturating_sub(bound))
+    }
+
+    /// Bind leading arguments to a function value (⋊)
+    fn bind_arguments(&self, chunk_index: usize, callee: Value, args: Vec<Value>) -> RuntimeResult<Value> {
+        let (func, bound) = self.callable_function(chunk_index, &callee).ok_or_else(|| RuntimeError::TypeMismatch {
+            expected: "closure or function".to_string(),
+            actual: callee.value_type().to_string(),
+        })?;
+        let takes = func.fixed_params().saturating_sub(bound);
+        if args.len() > takes && !func.variadic {
+            return Err(RuntimeError::WrongArity { name: func.name.clone(), expected: takes, got: args.len() });
+        }
+
+        Ok(match callee {
+            Value::Function(func_idx) => {
+                let mut closure = Closure::new(func_idx, Vec::new());
+                closure.bound = args;
+                Value::ClosureVal(Box::new(closure))
+            }
+            Value::ClosureVal(mut closure) => {
+                closure.bound.extend(args);
+                Value::ClosureVal(closure)
+            }
+            // Arguments go to the function that runs first
+            Value::Composed(composition) => {
+                let first = self.bind_arguments(chunk_index, composition.first.clone(), args)?;
+                Value::Composed(Rc::new(Composition { first, then: composition.then.clone() }))
+            }
+            _ => unreachable!("callable_arity accepted a value that cannot be called"),
+        })
+    }
+
+    /// Apply a callback to each element of a collection. Over a map the
+    /// callback gets the key before the value when it takes `keyed` parameters
+    fn each_element(
+        &mut self,
+        chunk_index: usize,
+        collection: &Value,
+        callee: &Value,
+        keyed: usize,
+        base_ip: usize,
+        mut visit: impl FnMut(Value, Option<Value>, Value) -> RuntimeResult<()>,
+    ) -> RuntimeResult<()> {
+        match collection {
+            Value::Array(elements) => {
+                for element in elements.iter() {
+                    let result = self.call_value(chunk_index, callee, vec![element.clone()], base_ip)?;
+                    visit(element.clone(), None, result)?;
+                }
+            }
+            Value::Map(entries) => {
+                let with_key = self.callable_arity(chunk_index, callee) == Some(keyed);
+                for (key, value) in entries.iter() {
+                    let args = if with_key { vec![key.clone(), value.clone()] } else { vec![value.clone()] };
+                    let result = self.call_value(chunk_index, callee, args, base_ip)?;
+                    visit(value.clone(), Some(key.clone()), result)?;
+                }
+            }
+            other => return Err(RuntimeError::TypeMismatch {
+                expected: "array or map".to_string(),
+                actual: other.value_type().to_string(),
+            }),
+        }
+        Ok(())
+    }
+
+    /// The next value of an iterator, or None once it is exhausted
+    fn iter_next(&mut self, chunk_index: usize, handle: &IterHandle, base_ip: usize) -> RuntimeResult<Option<Value>> {
+        // Sources that call back into the VM are advanced without the borrow held
+        let chained = match &mut *handle.source().borrow_mut() {
+            IterSource::Items { items, position } => {
+                let item = items.get(*position).cloned();
+                if item.is_some() {
+                    *position += 1;
+                }
+                return Ok(item);
+            }
+            IterSource::Range { next, end, step } => {
+                let current = *next;
+                if (*step > 0 && current < *end) || (*step < 0 && current > *end) {
+                    *next = current.saturating_add(*step);
+                    return Ok(Some(Value::Integer(current)));
+                }
+                return Ok(None);
+            }
+            IterSource::Generator(_) => None,
+            IterSource::Mapped { source, callee } => Some((source.clone(), callee.clone(), true)),
+            IterSource::Filtered { source, callee } => Some((source.clone(), callee.clone(), false)),
+        };
+
+        let Some((source, callee, mapped)) = chained else {
+            return self.resume_generator(handle);
+        };
+        while let Some(value) = self.iter_next(chunk_index, &source, base_ip)? {
+            let result = self.call_value(chunk_index, &callee, vec![value.clone()], base_ip)?;
+            if mapped {
+                return Ok(Some(result));
+            }
+            if result.is_truthy() {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Run a generator up to its next ⌭, returning the value it yields, or
+    /// None once it has returned
+    ///
+    /// The generator's frame goes back on top of the call stack with its
+    /// stack segment and runs in a nested loop, like a callback: an
+    /// exception it does not catch finishes it and comes back as an error.
+    fn resume_generator(&mut self, handle: &IterHandle) -> RuntimeResult<Option<Value>> {
+        if self.reentries >= MAX_REENTRIES {
+            return Err(ContextError::CallStackOverflow.into());
+        }
+
+        let mut suspended = {
+            let mut source = handle.source().borrow_mut();
+            let IterSource::Generator(state) = &mut *source else {
+                unreachable!("only generators are resumed");
+            };
+            match std::mem::replace(state, GeneratorState::Running) {
+                GeneratorState::Suspended(suspended) => suspended,
+                GeneratorState::Running => return Err(RuntimeError::RunningGenerator),
+                GeneratorState::Done => {
+                    *state = GeneratorState::Done;
+                    return Ok(None);
+                }
+            }
+        };
+        let finish = || *handle.source().borrow_mut() = IterSource::Generator(GeneratorState::Done);
+
+        let depth = self.context.call_depth();
+        let base_pointer = self.stack.len();
+        suspended.frame.base_pointer = base_pointer;
+        for value in std::mem::take(&mut suspended.stack) {
+            self.stack.push(value)?;
+        }
+        if let Err(error) = self.context.resume_frame(*suspended) {
+            self.stack.truncate(base_pointer);
+            finish();
+            return Err(error.into());
+        }
+
+        self.generators.push((handle.clone(), depth + 1));
+        let result = self.run_nested(depth);
+        self.generators.pop();
+
+        if let Err(error) = result {
+            finish();
+            return Err(error);
+        }
+        if self.context.halted {
+            return Ok(None);
+        }
+
+        // ⌭ left the generator suspended; ⤶ left it running to the end
+        let value = self.stack.pop()?;
+        let returned = matches!(&*handle.source().borrow(), IterSource::Generator(GeneratorState::Running));
+        if returned {
+            finish();
+            return Ok(None);
+        }
+        Ok(Some(value))
+    }
+
+    /// Fold an iterator with ⧋, pulling one value per call
+    fn fold_iterator(
+        &mut self,
+        chunk_index: usize,
+        handle: &IterHandle,
+        callee: &Value,
+        seed: Option<Value>,
+        base_ip: usize,
+    ) -> RuntimeResult<Value> {
+        let mut acc = match seed {
+            Some(seed) => seed,
+            None => self.iter_next(chunk_index, handle, base_ip)?.ok_or(RuntimeError::EmptyReduce)?,
+        };
+        while let Some(value) = self.iter_next(chunk_index, handle, base_ip)? {
+            acc = self.call_value(chunk_index, callee, vec![acc, value], base_ip)?;
+        }
+        Ok(acc)
+    }
+
+    /// A struct or enum definition from a chunk's type table
+    fn user_type(&self, chunk_index: usize, type_idx: usize, base_ip: usize) -> RuntimeResult<Rc<TypeDef>> {
+        self.context.get_chunk(chunk_index)
+            .and_then(|chunk| chunk.types.get(type_idx))
+            .cloned()
+            .ok_or(RuntimeError::InvalidBytecode { offset: base_ip })
+    }
+
+    /// Pop the fields of a struct or variant, checking each against its declared type
+    fn pop_fields(&mut self, chunk_index: usize, def: &TypeDef, tag: usize) -> RuntimeResult<Vec<Value>> {
+        let declared = def.fields(tag);
+        let mut fields = Vec::with_capacity(declared.len());
+        for _ in declared {
+            fields.push(self.stack.pop()?);
+        }
+        fields.reverse();
+
+        for (field, value) in declared.iter().zip(&fields) {
+            self.check_field(chunk_index, def, field, value)?;
+        }
+        Ok(fields)
+    }
+
+    /// Check a value against the declared type of a field
+    fn check_field(&self, chunk_index: usize, def: &TypeDef, field: &Field, value: &Value) -> RuntimeResult<()> {
+        let (matches, expected) = match field.ty {
+            FieldType::Value(ty) => (value.value_type() == ty, ty.to_string()),
+            FieldType::User(index) => {
+                let Some(expected) = self.context.get_chunk(chunk_index).and_then(|c| c.types.get(index as usize)) else {
+                    return Err(RuntimeError::InvalidBytecode { offset: self.current_ip()? });
+                };
+                let matches = match value {
+                    Value::Struct(s) => Rc::ptr_eq(&s.def, expected),
+                    Value::Variant(v) => Rc::ptr_eq(&v.def, expected),
+                    _ => false,
+                };
+                (matches, expected.name.clone())
+            }
+        };
+
+        if matches {
+            return Ok(());
+        }
+        let actual = match value {
+            Value::Struct(s) => s.def.name.clone(),
+            Value::Variant(v) => v.def.name.clone(),
+            other => other.value_type().to_string(),
+        };
+        Err(RuntimeError::TypeMismatch {
+            expected: format!("{} for field '{}' of {}", expected, field.name, def.name),
+            actual,
+        })
+    }
+
     /// Add two values
     fn add(&self, a: Value, b: Value) -> RuntimeResult<Value> {
         match (a, b) {
@@ -1406,29 +2381,424 @@ impl Runtime {
         }
     }
 
-    /// Convert a value to another type
-    fn convert_value(&self, value: Value, target: ValueType) -> RuntimeResult<Value> {
-        match target {
-            ValueType::Integer => value.to_integer()
-                .map(Value::Integer)
-                .ok_or_else(|| RuntimeError::TypeMismatch {
-                    expected: "integer".to_string(),
-                    actual: value.value_type().to_string(),
-                }),
-            ValueType::Real => value.to_real()
-                .map(Value::Real)
-                .ok_or_else(|| RuntimeError::TypeMismatch {
-                    expected: "real".to_string(),
-                    actual: value.value_type().to_string(),
+    /// Throw a value from the current instruction
+    fn throw(&mut self, value: Value) -> RuntimeResult<()> {
+        let trace = self.context.trace();
+        self.throw_exception(Exception { value, trace })
+    }
+
+    /// Hand an exception to the innermost handler that takes it, or fail
+    /// with the place it was thrown from
+    fn throw_exception(&mut self, exception: Exception) -> RuntimeResult<()> {
+        loop {
+            // Handlers below a running callback wait for the instruction that called it
+            let handler = match self.context.current_exception_handler() {
+                Some(handler) if handler.frame_depth > self.reentry_depth => self.context.pop_exception_handler(),
+                _ => None,
+            };
+            let handler = match handler {
+                Some(handler) => handler,
+                None => return Err(RuntimeError::UncaughtException {
+                    line: exception.line(),
+                    value: exception.value,
+                    trace: exception.trace,
                 }),
-            ValueType::String => Ok(Value::String(value.to_string_value())),
-            ValueType::Boolean => Ok(Value::Boolean(value.is_truthy())),
-            _ => Err(RuntimeError::TypeMismatch {
-                expected: target.to_string(),
-                actual: value.value_type().to_string(),
+            };
+
+            if let Some(handler_ip) = handler.handler_ip {
+                self.unwind_to(&handler)?;
+                // The catch block stays covered by the finally block, and
+                // its closing TryEnd pops this handler again
+                let finally_only = ExceptionHandler {
+                    handler_ip: None,
+                    caught: Some(exception),
+                    ..handler
+                };
+                self.context.push_exception_handler(finally_only);
+                return self.jump_to(handler_ip);
+            }
+            if let Some(finally_ip) = handler.finally_ip {
+                self.unwind_to(&handler)?;
+                self.context.push_completion(Completion::Throw(exception));
+                return self.jump_to(finally_ip);
+            }
+            // A catch block without a finally block: keep looking
+        }
+    }
+
+    /// Build the structured exception value an error is thrown as
+    fn error_value(&self, kind: &str, message: &str) -> Value {
+        let trace = self.context.trace();
+        let line = trace.first().map_or(0, |frame| frame.line);
+
+        let mut entries = OrderedMap::with_capacity(4);
+        let fields = [
+            ("kind", Value::String(kind.to_string())),
+            ("message", Value::String(message.to_string())),
+            ("line", Value::Integer(line as i64)),
+            ("trace", Value::array(trace.iter()
+                .map(|frame| Value::String(frame.to_string()))
+                .collect())),
+        ];
+        for (key, value) in fields {
+            // String keys always hash
+            let _ = entries.insert(Value::String(key.to_string()), value);
+        }
+        Value::map(entries)
+    }
+
+    /// Restore the state saved by a handler, dropping everything set up
+    /// inside its try region
+    fn unwind_to(&mut self, handler: &ExceptionHandler) -> RuntimeResult<()> {
+        // Unwind call frames if needed
+        while self.context.call_depth() > handler.frame_depth {
+            self.context.pop_frame()?;
+        }
+
+        self.stack.truncate(handler.stack_depth);
+        self.spell_stack.truncate(handler.spell_depth);
+        self.context.unwind_loops(handler.loop_depth);
+        self.context.truncate_completions(handler.completion_depth);
+        Ok(())
+    }
+
+    /// Return from the current function, first running the finally blocks
+    /// of the try regions it returns from
+    fn return_value(&mut self, result: Value) -> RuntimeResult<()> {
+        let depth = self.context.call_depth();
+        while let Some(handler) = self.context.pop_frame_handler(depth) {
+            if let Some(finally_ip) = handler.finally_ip {
+                self.unwind_to(&handler)?;
+                self.context.push_completion(Completion::Return(result));
+                return self.jump_to(finally_ip);
+            }
+        }
+
+        self.leave_frame()?;
+        self.stack.push(result)?;
+        Ok(())
+    }
+
+    /// Pop the current frame
+    fn leave_frame(&mut self) -> RuntimeResult<()> {
+        let frame = self.context.pop_frame()?;
+        // Drop whatever the function left behind, such as the state
+        // of loops it returned from
+        self.stack.truncate(frame.base_pointer);
+        Ok(())
+    }
+
+    /// Pop the current frame before a tail call, so the callee returns
+    /// straight to the caller; a frame with a handler installed stays, and
+    /// the ⤶ after the call returns from it as usual
+    fn leave_for_tail_call(&mut self) -> RuntimeResult<()> {
+        if self.context.has_frame_handler(self.context.call_depth()) {
+            return Ok(());
+        }
+        self.leave_frame()
+    }
+
+    /// IP of the current frame
+    fn current_ip(&self) -> RuntimeResult<usize> {
+        self.context.current_frame()
+            .map(|frame| frame.ip)
+            .ok_or(RuntimeError::UniverseCorruption)
+    }
+
+    /// Move the current frame to an IP
+    fn jump_to(&mut self, ip: usize) -> RuntimeResult<()> {
+        let frame = self.context.current_frame_mut()
+            .ok_or(RuntimeError::UniverseCorruption)?;
+        frame.ip = ip;
+        Ok(())
+    }
+
+    /// Execute a builtin, routing conversions through `convert_value`,
+    /// file access through the file table and iterators through `iter_next`
+    fn call_builtin(&mut self, chunk_index: usize, builtin: &Builtin, mut args: Vec<Value>, base_ip: usize) -> RuntimeResult<Value> {
+        let target = match builtin.id {
+            BuiltinId::ToString => ValueType::String,
+            BuiltinId::ToInt => ValueType::Integer,
+            BuiltinId::ToReal => ValueType::Real,
+            BuiltinId::Open | BuiltinId::ReadLine | BuiltinId::ReadAll
+            | BuiltinId::Write | BuiltinId::Close => return self.call_file_builtin(builtin, args),
+            BuiltinId::Args => {
+                return Ok(Value::array(self.args.iter().cloned().map(Value::String).collect()));
+            }
+            BuiltinId::Env => {
+                let name = expect_string(&args[0])?;
+                return Ok(std::env::var(name).map(Value::String).unwrap_or(Value::Null));
+            }
+            BuiltinId::Error => {
+                let kind = expect_string(&args[0])?;
+                let message = match args.get(1) {
+                    Some(message) => expect_string(message)?,
+                    None => kind,
+                };
+                return Ok(self.error_value(kind, message));
+            }
+            BuiltinId::Next => {
+                let handle = expect_iterator(&args[0])?;
+                return Ok(self.iter_next(chunk_index, &handle, base_ip)?.unwrap_or(Value::Null));
+            }
+            BuiltinId::Collect => {
+                let handle = expect_iterator(&args[0])?;
+                let mut values = Vec::new();
+                while let Some(value) = self.iter_next(chunk_index, &handle, base_ip)? {
+                    values.push(value);
+                }
+                return Ok(Value::array(values));
+            }
+            _ => return stdlib::call(builtin, args),
+        };
+
+        let value = args.pop().unwrap_or(Value::Null);
+        self.convert_value(value, target)
+    }
+
+    /// Execute one of the file builtins
+    fn call_file_builtin(&mut self, builtin: &Builtin, args: Vec<Value>) -> RuntimeResult<Value> {
+        if builtin.id == BuiltinId::Open {
+            let path = expect_string(&args[0])?;
+            let mode = match args.get(1) {
+                Some(mode) => FileMode::parse(expect_string(mode)?)?,
+                None => FileMode::Read,
+            };
+            return Ok(Value::FileHandle(self.files.open(path, mode)?));
+        }
+
+        let handle = match &args[0] {
+            Value::FileHandle(handle) => *handle,
+            other => return Err(RuntimeError::TypeMismatch {
+                expected: ValueType::FileHandle.to_string(),
+                actual: other.value_type().to_string(),
             }),
+        };
+
+        match builtin.id {
+            BuiltinId::ReadLine => Ok(self.files.read_line(handle)?
+                .map(Value::String)
+                .unwrap_or(Value::Null)),
+            BuiltinId::ReadAll => Ok(Value::String(self.files.read_all(handle)?)),
+            BuiltinId::Write => {
+                self.files.write(handle, &args[1].to_string_value())?;
+                Ok(Value::Null)
+            }
+            _ => {
+                self.files.close(handle)?;
+                Ok(Value::Null)
+            }
         }
     }
+
+    /// Convert a value to another type
+    fn convert_value(&self, value: Value, target: ValueType) -> RuntimeResult<Value> {
+        let converted = match (target, &value) {
+            (ValueType::Integer, Value::Rune(c)) => Some(Value::Integer(*c as i64)),
+            (ValueType::Integer, _) => value.to_integer().map(Value::Integer),
+            (ValueType::Real, Value::Rune(c)) => Some(Value::Real(*c as u32 as f64)),
+            (ValueType::Real, _) => value.to_real().map(Value::Real),
+            (ValueType::String, _) => Some(Value::String(value.to_string_value())),
+            (ValueType::Boolean, _) => Some(Value::Boolean(value.is_truthy())),
+            (ValueType::Rune, Value::Rune(c)) => Some(Value::Rune(*c)),
+            (ValueType::Rune, Value::Integer(i)) => u32::try_from(*i).ok()
+                .and_then(char::from_u32)
+                .map(Value::Rune),
+            (ValueType::Rune, Value::String(s)) => {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(Value::Rune(c)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        converted.ok_or_else(|| RuntimeError::ConversionFailed {
+            value: match &value {
+                Value::String(s) => format!("{:?}", s),
+                other => other.to_string_value(),
+            },
+            from: value.value_type(),
+            target,
+        })
+    }
+}
+
+/// Turn a possibly negative index into a position, checking bounds
+fn resolve_index(index: i64, length: usize) -> RuntimeResult<usize> {
+    let resolved = if index < 0 { length as i64 + index } else { index };
+
+    if resolved < 0 || resolved >= length as i64 {
+        return Err(RuntimeError::IndexOutOfBounds { index, length });
+    }
+    Ok(resolved as usize)
+}
+
+/// Read an element of an array (by position), map (by key) or string (rune by position)
+fn index_element(container: &Value, index: &Value) -> RuntimeResult<Value> {
+    match (container, index) {
+        (Value::Array(arr), Value::Integer(idx)) => Ok(arr[resolve_index(*idx, arr.len())?].clone()),
+        (Value::Map(entries), key) => Ok(entries.get(key).cloned().unwrap_or(Value::Null)),
+        (Value::String(s), Value::Integer(idx)) => {
+            let length = s.chars().count();
+            let position = resolve_index(*idx, length)?;
+            Ok(Value::Rune(s.chars().nth(position).unwrap_or_default()))
+        }
+        _ => Err(RuntimeError::TypeMismatch {
+            expected: "array or string with integer index, or map".to_string(),
+            actual: format!("{} and {}", container.value_type(), index.value_type()),
+        }),
+    }
+}
+
+/// Definition, variant tag and field values of a struct or variant
+fn user_fields(value: &Value) -> RuntimeResult<(&Rc<TypeDef>, usize, &[Value])> {
+    match value {
+        Value::Struct(s) => Ok((&s.def, 0, &s.fields)),
+        Value::Variant(v) => Ok((&v.def, v.tag, &v.fields)),
+        other => Err(RuntimeError::TypeMismatch {
+            expected: "⌻ struct or ⎊ variant".to_string(),
+            actual: other.value_type().to_string(),
+        }),
+    }
+}
+
+/// Position of a named field of a struct or variant
+fn field_position(def: &TypeDef, tag: usize, name: &str) -> RuntimeResult<usize> {
+    def.fields(tag).iter().position(|field| field.name == name).ok_or_else(|| {
+        let type_name = match def.variant_name(tag) {
+            "" => def.name.clone(),
+            variant => format!("{}⊷{}", def.name, variant),
+        };
+        RuntimeError::UnknownField { type_name, field: name.to_string() }
+    })
+}
+
+/// Store into an array position or map key, copying the collection only if it is shared
+fn set_element(container: &mut Value, index: Value, value: Value) -> RuntimeResult<()> {
+    match (container, index) {
+        (Value::Array(arr), Value::Integer(idx)) => {
+            let position = resolve_index(idx, arr.len())?;
+            Rc::make_mut(arr)[position] = value;
+            Ok(())
+        }
+        (Value::Map(entries), key) => {
+            Rc::make_mut(entries).insert(key, value).map_err(unhashable)?;
+            Ok(())
+        }
+        (container, index) => Err(RuntimeError::TypeMismatch {
+            expected: "array with integer index, or map".to_string(),
+            actual: format!("{} and {}", container.value_type(), index.value_type()),
+        }),
+    }
+}
+
+/// Append to an array, copying it only if it is shared
+fn append_element(container: &mut Value, value: Value) -> RuntimeResult<()> {
+    match container {
+        Value::Array(arr) => {
+            Rc::make_mut(arr).push(value);
+            Ok(())
+        }
+        other => Err(RuntimeError::TypeMismatch {
+            expected: "array".to_string(),
+            actual: other.value_type().to_string(),
+        }),
+    }
+}
+
+/// Reject a map key that cannot be hashed
+fn unhashable(UnhashableKey(key): UnhashableKey) -> RuntimeError {
+    RuntimeError::TypeMismatch {
+        expected: "hashable map key".to_string(),
+        actual: key.value_type().to_string(),
+    }
+}
+
+/// Borrow a string argument
+fn expect_string(value: &Value) -> RuntimeResult<&str> {
+    match value {
+        Value::String(s) => Ok(s),
+        other => Err(RuntimeError::TypeMismatch {
+            expected: ValueType::String.to_string(),
+            actual: other.value_type().to_string(),
+        }),
+    }
+}
+
+/// Share an iterator argument
+fn expect_iterator(value: &Value) -> RuntimeResult<IterHandle> {
+    match value {
+        Value::Iterator(handle) => Ok(handle.clone()),
+        other => Err(RuntimeError::TypeMismatch {
+            expected: ValueType::Iterator.to_string(),
+            actual: other.value_type().to_string(),
+        }),
+    }
+}
+
+/// The kind of a structured exception, or null for any other thrown value
+fn exception_kind(exception: &Value) -> Value {
+    match exception {
+        Value::Map(entries) => match entries.get(&Value::String("kind".to_string())) {
+            Some(Value::String(kind)) => Value::String(kind.clone()),
+            _ => Value::Null,
+        },
+        _ => Value::Null,
+    }
+}
+
+/// A thrown value as an error message: `Kind: message` for a structured
+/// exception, the value itself for anything else
+fn describe_exception(exception: &Value) -> String {
+    let Value::Map(entries) = exception else {
+        return exception.to_string();
+    };
+    let kind = entries.get(&Value::String("kind".to_string()));
+    let message = entries.get(&Value::String("message".to_string()));
+    match (kind, message) {
+        (Some(kind), Some(message)) => format!("{}: {}", kind, message),
+        _ => exception.to_string(),
+    }
+}
+
+/// Match the arguments of a call to a function's parameters: named
+/// arguments fill the parameters they name, defaults the ones left out,
+/// and a variadic parameter collects the arguments past the others
+fn bind_parameters(func: &FunctionInfo, mut args: Vec<Value>, named: Option<&OrderedMap>) -> RuntimeResult<Vec<Value>> {
+    let fixed = func.fixed_params();
+    let given = args.len() + named.map_or(0, |named| named.len());
+    let rest = if func.variadic && args.len() > fixed { args.split_off(fixed) } else { Vec::new() };
+    if args.len() > fixed {
+        return Err(RuntimeError::WrongArity { name: func.name.clone(), expected: fixed, got: given });
+    }
+
+    let mut slots: Vec<Option<Value>> = args.into_iter().map(Some).collect();
+    slots.resize(fixed, None);
+    for (key, value) in named.into_iter().flat_map(|named| named.iter()) {
+        let param = expect_string(key)?;
+        let position = func.params[..fixed].iter().position(|(name, _)| name == param)
+            .ok_or_else(|| RuntimeError::UnknownArgument { name: func.name.clone(), param: param.to_string() })?;
+        if slots[position].replace(value.clone()).is_some() {
+            return Err(RuntimeError::DuplicateArgument { name: func.name.clone(), param: param.to_string() });
+        }
+    }
+
+    let required = func.required_params();
+    let mut values = Vec::with_capacity(func.params.len());
+    for (position, slot) in slots.into_iter().enumerate() {
+        let value = match slot {
+            Some(value) => value,
+            None if position >= required => func.defaults[position - required].clone(),
+            None => return Err(RuntimeError::WrongArity { name: func.name.clone(), expected: required, got: given }),
+        };
+        values.push(value);
+    }
+    if func.variadic {
+        values.push(Value::array(rest));
+    }
+    Ok(values)
 }
 
 impl Default for Runtime {
@@ -1440,6 +2810,40 @@ impl Default for Runtime {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::compiler::Compiler;
+    use crate::symbols::SymbolTable;
+
+    /// Compile and run a spell, returning the runtime for inspection
+    fn run_spell(source: &str) -> Runtime {
+        let symbol_table = SymbolTable::new();
+        let chunk = Compiler::new(&symbol_table).compile(source).unwrap();
+        let mut runtime = Runtime::new();
+        runtime.execute(chunk).unwrap();
+        runtime
+    }
+
+    /// Compile and run a spell, returning the execution result
+    fn try_spell(source: &str) -> Result<(), RuntimeError> {
+        let symbol_table = SymbolTable::new();
+        let chunk = Compiler::new(&symbol_table).compile(source).unwrap();
+        Runtime::new().execute(chunk).map(|_| ())
+    }
+
+    /// `try_spell` on declarations followed by a body, closed with ❧
+    fn try_spell_with(header: &str, body: &str) -> Result<(), RuntimeError> {
+        try_spell(&format!("{}{}\n❧", header, body))
+    }
+
+    /// `try_spell` on a thread with as much stack as the main one, for
+    /// spells nesting execution loops deeply; gives the kind of any error
+    fn try_spell_nested(source: String) -> Result<(), &'static str> {
+        std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(move || try_spell(&source).map_err(|error| error.kind()))
+            .unwrap()
+            .join()
+            .unwrap()
+    }
 
     #[test]
     fn test_arithmetic() {
@@ -1462,4 +2866,631 @@ mod tests {
         let result = runtime.divide(Value::Integer(5), Value::Integer(0));
         assert!(matches!(result, Err(RuntimeError::DivisionByZero)));
     }
+
+    #[test]
+    fn test_builtin_calls() {
+        let runtime = run_spell(
+            "⌬xs=[5 3 9]\n⌬s=⤷sort[xs]\n⟁n=⤷len[s] ✚ ⤷max[1, 7]\n⌘t=⤷to_string[n]\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("n").unwrap(), &Value::Integer(10));
+        assert_eq!(runtime.context.get_variable("t").unwrap(), &Value::String("10".to_string()));
+        assert_eq!(
+            runtime.context.get_variable("s").unwrap(),
+            &Value::array(vec![Value::Integer(3), Value::Integer(5), Value::Integer(9)])
+        );
+    }
+
+    #[test]
+    fn test_file_error_is_catchable() {
+        let runtime = run_spell(
+            "⌘msg=\"\"\n☄\n⟁h=⤷open[\"/nonexistent/obfusku/scroll.txt\"]\n☊[e ∈ FileError]\nmsg=e⌷\"message\"\n⟣\n❧",
+        );
+
+        match runtime.context.get_variable("msg").unwrap() {
+            Value::String(msg) => assert!(msg.contains("scroll.txt")),
+            other => panic!("expected message, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_args_and_exit_code() {
+        let symbol_table = SymbolTable::new();
+        let chunk = Compiler::new(&symbol_table)
+            .compile("⌬a=⤷args[]\n❧[⤷len[a] ✚ 40]")
+            .unwrap();
+        let mut runtime = Runtime::new();
+        runtime.set_args(vec!["fire".to_string(), "ice".to_string()]);
+        runtime.execute(chunk).unwrap();
+
+        assert_eq!(runtime.exit_code(), 42);
+        assert_eq!(
+            runtime.context.get_variable("a").unwrap(),
+            &Value::array(vec![Value::String("fire".to_string()), Value::String("ice".to_string())])
+        );
+        assert_eq!(run_spell("❧").exit_code(), 0);
+    }
+
+    #[test]
+    fn test_conversions() {
+        let runtime = run_spell(
+            "⟁n=⟁[\"41\"] ✚ 1\nᚱr=ᚱ[65]\n⌘msg=\"\"\n☄\n⟁bad=⟁[\"abc\"]\n☊[e]\nmsg=e⌷\"message\"\n⟣\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("n").unwrap(), &Value::Integer(42));
+        assert_eq!(runtime.context.get_variable("r").unwrap(), &Value::Rune('A'));
+        match runtime.context.get_variable("msg").unwrap() {
+            Value::String(msg) => assert!(msg.contains("abc")),
+            other => panic!("expected message, got {:?}", other),
+        }
+
+        let result = Runtime::new().convert_value(Value::String("xy".to_string()), ValueType::Rune);
+        assert!(matches!(result, Err(RuntimeError::ConversionFailed { .. })));
+    }
+
+    #[test]
+    fn test_map_literal_keeps_order() {
+        let runtime = run_spell("⌖m={\"b\"⇒1⋄2.5⇒2⋄\"b\"⇒3}\n❧");
+
+        match runtime.context.get_variable("m").unwrap() {
+            Value::Map(map) => {
+                let keys: Vec<&Value> = map.keys().collect();
+                assert_eq!(keys, vec![&Value::String("b".to_string()), &Value::Real(2.5)]);
+                assert_eq!(map.get(&Value::String("b".to_string())), Some(&Value::Integer(3)));
+            }
+            other => panic!("expected map, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_in_place_collections() {
+        let runtime = run_spell(
+            "⌬xs=[1 2]\nxs⊞3\n⌬ys=xs\nys⌷0=9\n⌖m={\"a\"⇒1}\nm⌷\"b\"=xs⌷-1\n❧",
+        );
+
+        // ys was copied on write, leaving xs untouched
+        assert_eq!(
+            runtime.context.get_variable("xs").unwrap(),
+            &Value::array(vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)])
+        );
+        assert_eq!(
+            runtime.context.get_variable("ys").unwrap(),
+            &Value::array(vec![Value::Integer(9), Value::Integer(2), Value::Integer(3)])
+        );
+        match runtime.context.get_variable("m").unwrap() {
+            Value::Map(map) => assert_eq!(map.get(&Value::String("b".to_string())), Some(&Value::Integer(3))),
+            other => panic!("expected map, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_append_does_not_copy() {
+        let mut runtime = run_spell("⌬xs=[1]\n❧");
+        let before = match runtime.context.get_variable("xs").unwrap() {
+            Value::Array(arr) => Rc::as_ptr(arr),
+            other => panic!("expected array, got {:?}", other),
+        };
+
+        let target = runtime.context.get_variable_mut("xs").unwrap();
+        append_element(target, Value::Integer(2)).unwrap();
+
+        match runtime.context.get_variable("xs").unwrap() {
+            Value::Array(arr) => assert_eq!(Rc::as_ptr(arr), before),
+            other => panic!("expected array, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_spell_stack() {
+        let runtime = run_spell(
+            "⇑[10]\n⇑[3]\n⇆\n⟁d=⇓ ☠︎ ⇓\n⇑[1] ⇑[2] ⇑[3]\n↻\n⟁top=⇓\n⇕\n⟁twice=⇓ ✚ ⇓\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("d").unwrap(), &Value::Integer(7));
+        assert_eq!(runtime.context.get_variable("top").unwrap(), &Value::Integer(2));
+        assert_eq!(runtime.context.get_variable("twice").unwrap(), &Value::Integer(2));
+        assert_eq!(runtime.spell_stack.len(), 1);
+    }
+
+    #[test]
+    fn test_throw_rewinds_spell_stack() {
+        let runtime = run_spell("⇑[1]\n☄\n⇑[2]\n⚠[\"boom\"]\n⇓\n☊[e]\n⟣\n⟁x=⇓\n❧");
+
+        assert_eq!(runtime.context.get_variable("x").unwrap(), &Value::Integer(1));
+        assert!(runtime.spell_stack.is_empty());
+    }
+
+    #[test]
+    fn test_loop_accumulator_frames() {
+        let runtime = run_spell(
+            "⟁inner=0\n✹=3\n⊂[✹ ▷ 0]\n⊂[✹ ▷ 0]\n✹[2]⊕\n✹⊖\n⊃\n✹⊖\n⊃\n⟁after=✹\n\
+             ✹=0\n⊂[◉]\n✹⊕\n☄\n⊂[◉]\n⚠[\"out\"]\n⊃\n☊\n⟣\n⟨✹ ⩵ 2] ⊗ ⟫\n⊃\n⟁caught=✹\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("after").unwrap(), &Value::Integer(9));
+        assert_eq!(runtime.context.get_variable("caught").unwrap(), &Value::Integer(0));
+        assert_eq!(runtime.context.loop_depth(), 0);
+    }
+
+    #[test]
+    fn test_for_each() {
+        let runtime = run_spell(
+            "⌬xs=[3, 1, 4]\n⟁total=0\n⊂[x ∈ xs]\ntotal=total✚x\n⊃\n\
+             ⌖m={\"a\"⇒1, \"b\"⇒2}\n⌘keys=\"\"\n⊂[k ⇒ v ∈ m]\n⟨v ⩵ 1] ↺ ⟫\nkeys=keys✚k\n⊃\n\
+             ⌘runes=\"\"\n⊂[c ∈ \"héllo\"]\n⟨c ⩵ ᚱ[\"l\"]] ⊗ ⟫\nrunes=runes✚⌘[c]\n⊃\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("total").unwrap(), &Value::Integer(8));
+        assert_eq!(runtime.context.get_variable("keys").unwrap(), &Value::String("b".to_string()));
+        assert_eq!(runtime.context.get_variable("runes").unwrap(), &Value::String("hé".to_string()));
+    }
+
+    #[test]
+    fn test_range_loop_scoping() {
+        let runtime = run_spell(
+            "⟁i=99\n⟁sum=0\n⊂[i ∈ 10‥0∆-3]\n⟁sq=i✱i\nsum=sum✚sq\n⊃\n\
+             λfind[⌬a]\n⊂[e ∈ a]\n⟨e ▷ 2] ⤶[e] ⟫\n⊃\n⤶[0]\nΛ\n⌬xs=[1, 5]\n⟁found=0\n\
+             ⊂[n ∈ 0‥3]\nfound=found✚⤷find[xs]\n⊃\n❧",
+        );
+
+        // 10, 7, 4, 1
+        assert_eq!(runtime.context.get_variable("sum").unwrap(), &Value::Integer(166));
+        assert_eq!(runtime.context.get_variable("i").unwrap(), &Value::Integer(99));
+        assert!(runtime.context.get_variable("sq").is_err());
+        assert_eq!(runtime.context.get_variable("found").unwrap(), &Value::Integer(15));
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_labeled_break_and_continue() {
+        let runtime = run_spell(
+            "⌬grid=[1, 2, 3]\n⟁hits=0\n⊂outer[a ∈ grid]\n⊂[b ∈ grid]\n⟡b]\n⟢2]\n↺[outer]\n⟢◇]\nhits=hits✚1\n⟣\n⊃\n⊃\n\
+             ⟁n=0\n⊂rows[◉]\n☄\n⊂[c ∈ 0‥5]\nn=n✚1\n⟨c ⩵ 2] ⊗[rows] ⟫\n⊃\n☊\n⟣\n⊃\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("hits").unwrap(), &Value::Integer(3));
+        assert_eq!(runtime.context.get_variable("n").unwrap(), &Value::Integer(3));
+        assert!(runtime.stack.is_empty());
+        assert!(!runtime.context.has_exception_handler());
+    }
+
+    #[test]
+    fn test_finally_runs_on_every_exit() {
+        let runtime = run_spell(
+            "⌘log=\"\"\n☄\nlog=log✚\"t\"\n☋\nlog=log✚\"f\"\n⟣\n\
+             ☄\n⚠[\"x\"]\n☊\nlog=log✚\"c\"\n☋\nlog=log✚\"f\"\n⟣\n\
+             λr[⟁n]\n☄\n⤶[n✚1]\n☋\nlog=log✚\"r\"\n⟣\n⤶[0]\nΛ\n⟁v=⤷r[4]\n\
+             ⟁i=0\n⊂outer[◉]\n⊂[◉]\ni=i✚1\n☄\n⟨i ⩵ 2] ↺[outer] ⟫\n⟨i ⩵ 3] ⊗[outer] ⟫\n☋\nlog=log✚\"l\"\n⟣\n⊃\n⊃\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("log").unwrap(), &Value::String("tfcfrlll".to_string()));
+        assert_eq!(runtime.context.get_variable("v").unwrap(), &Value::Integer(5));
+        assert_eq!(runtime.context.get_variable("i").unwrap(), &Value::Integer(3));
+        assert!(runtime.stack.is_empty());
+        assert!(!runtime.context.has_exception_handler());
+        assert_eq!(runtime.context.completion_depth(), 0);
+    }
+
+    #[test]
+    fn test_finally_rethrows() {
+        let runtime = run_spell(
+            "⌘log=\"\"\n☄\n☄\n⚠[\"a\"]\n☊\n⚠[\"b\"]\n☋\nlog=log✚\"f\"\n⟣\n☊[e]\nlog=log✚e\n⟣\n❧",
+        );
+        assert_eq!(runtime.context.get_variable("log").unwrap(), &Value::String("fb".to_string()));
+
+        let symbol_table = SymbolTable::new();
+        let chunk = Compiler::new(&symbol_table)
+            .compile("⟁n=0\n☄\n⚠[\"lost\"]\n☋\nn=1\n⟣\n❧")
+            .unwrap();
+        let mut runtime = Runtime::new();
+        assert!(runtime.execute(chunk).is_err());
+        assert_eq!(runtime.context.get_variable("n").unwrap(), &Value::Integer(1));
+    }
+
+    #[test]
+    fn test_runtime_errors_are_catchable() {
+        let runtime = run_spell(
+            "⟁z=0\n⌘kind=\"\"\n⟁line=0\n☄\n⟁bad=1÷z\n☊[e ∈ IndexOutOfBounds]\nkind=\"wrong\"\n\
+             ☊[e ∈ ArithmeticOverflow, DivisionByZero]\nkind=e⌷\"kind\"\nline=e⌷\"line\"\n⟣\n\
+             ⌘hit=\"\"\n☄\n⚠[\"boom\"]\n☊[e ⟢ \"bang\"]\nhit=\"bang\"\n☊[e ⟢ \"boom\"]\nhit=e\n⟣\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("kind").unwrap(), &Value::String("DivisionByZero".to_string()));
+        assert_eq!(runtime.context.get_variable("line").unwrap(), &Value::Integer(5));
+        assert_eq!(runtime.context.get_variable("hit").unwrap(), &Value::String("boom".to_string()));
+        assert!(!runtime.context.has_exception_handler());
+    }
+
+    #[test]
+    fn test_rethrow_and_unmatched_filters() {
+        let runtime = run_spell(
+            "λdeep[⟁n]\n⌬xs=[1]\n⤶[xs⌷n]\nΛ\n⌘inner=\"\"\n⟁frames=0\n\
+             ☄\n☄\n⟁v=⤷deep[5]\n☊[e]\ninner=e⌷\"kind\"\n⚠\n⟣\n☊[e]\nframes=⤷len[e⌷\"trace\"]\n⟣\n\
+             ⌘log=\"\"\n☄\n☄\n⚠[⤷error[\"NotFound\"]]\n☊[◇ ∈ DivisionByZero]\nlog=\"wrong\"\n☋\nlog=log✚\"f\"\n⟣\n\
+             ☊[e ∈ NotFound]\nlog=log✚e⌷\"message\"\n⟣\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("inner").unwrap(), &Value::String("IndexOutOfBounds".to_string()));
+        assert_eq!(runtime.context.get_variable("frames").unwrap(), &Value::Integer(2));
+        assert_eq!(runtime.context.get_variable("log").unwrap(), &Value::String("fNotFound".to_string()));
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_uncaught_exception_origin() {
+        let symbol_table = SymbolTable::new();
+        let chunk = Compiler::new(&symbol_table)
+            .compile("λdeep[]\n⚠[\"lost\"]\n⤶[0]\nΛ\n☄\n⟁x=⤷deep[]\n☊[e ⟢ \"other\"]\n⟣\n❧")
+            .unwrap();
+        let mut runtime = Runtime::new();
+
+        // Passing through a ☊ that rejects it keeps the original throw site
+        match runtime.execute(chunk) {
+            Err(RuntimeError::UncaughtException { value, line, trace }) => {
+                assert_eq!(value, Value::String("lost".to_string()));
+                assert_eq!(line, 2);
+                let calls: Vec<String> = trace.iter().map(|frame| frame.to_string()).collect();
+                assert_eq!(calls, vec!["deep (line 2)", "main (line 6)"]);
+            }
+            other => panic!("expected an uncaught exception, got {:?}", other),
+        }
+
+        // A structured exception reads as its kind and message
+        let error = try_spell("⌖e={\"kind\"⇒\"NotFound\", \"message\"⇒\"no scroll\"}\n⚠[e]\n❧").unwrap_err();
+        assert!(error.to_string().contains("Uncaught exception: NotFound: no scroll —"));
+        let error = try_spell("⚠[\"lost\"]\n❧").unwrap_err();
+        assert!(error.to_string().contains("Uncaught exception: lost —"));
+    }
+
+    #[test]
+    fn test_match_patterns() {
+        let runtime = run_spell(
+            "⌘log=\"\"\n⌬inner=[6, 0]\n⌬xs=[4, inner, 7, 8]\n\
+             ⌬vals=[0, 5, 12, \"x\", 2.5]\n⊂[i ∈ vals]\n⟡i]\n⟢0 ∨ 12] log=log✚\"a\"\n⟢1‥10] log=log✚\"b\"\n\
+             ⟢⌘s] log=log✚s\n⟢◇] log=log✚\"?\"\n⟣\n⊃\n\
+             ⟡xs]\n⟢[≔a] ⟨a ▷ 0] log=log✚\"one\"\n⟢[≔a, [≔b, ◇], ‥≔rest]] ⟁tail=⤷len[rest]\n⟢◇] log=log✚\"none\"\n⟣\n\
+             ⌖m={\"kind\"⇒\"point\"⋄\"x\"⇒3}\n⟡m]\n⟢{\"kind\" ⇒ \"circle\"}] log=log✚\"c\"\n\
+             ⟢{\"kind\" ⇒ \"point\", \"x\" ⇒ ⟁px}] log=log✚\"p\"\n⟢◇]\n⟣\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("log").unwrap(), &Value::String("abax?p".to_string()));
+        assert_eq!(runtime.context.get_variable("a").unwrap(), &Value::Integer(4));
+        assert_eq!(runtime.context.get_variable("b").unwrap(), &Value::Integer(6));
+        assert_eq!(runtime.context.get_variable("tail").unwrap(), &Value::Integer(2));
+        assert_eq!(runtime.context.get_variable("px").unwrap(), &Value::Integer(3));
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_guards_fall_through_and_filters_bind() {
+        let runtime = run_spell(
+            "⟁n=7\n⌘size=\"\"\n⟡n]\n⟢⟁k ⟨k ◁ 5] size=\"small\"\n⟢⟁k ⟨k ◁ 10] size=\"medium\"\n⟢◇] size=\"large\"\n⟣\n\
+             ⟁code=0\n☄\n⚠[⤷error[\"Custom\", \"boom\"]]\n☊[◇ ⟢ {\"kind\" ⇒ \"Custom\", \"message\" ⇒ ≔why}]\ncode=⤷len[why]\n⟣\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("size").unwrap(), &Value::String("medium".to_string()));
+        assert_eq!(runtime.context.get_variable("code").unwrap(), &Value::Integer(4));
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_match_and_conditional_values() {
+        let runtime = run_spell(
+            "⟁code=1\n⟁label = ⟡code] ⟢1] \"one\" ⟢◇] \"other\" ⟣\n\
+             ⌘parity=⟨7 ⌗ 2 ⩵ 0] \"even\" ⟩ \"odd\" ⟫\n⟁total=0\n\
+             ⊂[i ∈ 0‥4]\ntotal=total✚⟡i] ⟢0 ∨ 1] i ⟢⟁k ⟨k ▷ 2] k ✱ 100 ⟢◇] ⟨i ⩵ 2] 20 ⟩ 21 ⟫ ⟣\n⊃\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("label").unwrap(), &Value::String("one".to_string()));
+        assert_eq!(runtime.context.get_variable("parity").unwrap(), &Value::String("odd".to_string()));
+        assert_eq!(runtime.context.get_variable("total").unwrap(), &Value::Integer(321));
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_structs_and_enums() {
+        let runtime = run_spell(
+            "⌻Point[⟁x, ⟁y]\n⎊Shape[Circle[⧆r], Dot[Point at], Empty]\n\
+             ⌻p=Point[1, 2]\n⌻q=p\nq⊷x=10\n⟁sum=p⊷x ✚ q⊷x ✚ q⊷y\n\
+             ⎊s=Shape⊷Dot[q]\n⟁hit = ⟡s] ⟢Shape⊷Circle[◇]] 0 ⟢Shape⊷Dot[Point[≔x, 2]]] x ⟢Shape] 1 ⟢◇] 2 ⟣\n\
+             ⌘shown=⤷to_string[s]\n⟁missing=0\n☄\n⚡[p⊷z]\n☊[◇ ∈ UnknownField]\nmissing=1\n⟣\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("sum").unwrap(), &Value::Integer(13));
+        assert_eq!(runtime.context.get_variable("hit").unwrap(), &Value::Integer(10));
+        assert_eq!(runtime.context.get_variable("shown").unwrap(), &Value::String("⎊Shape⊷Dot[⌻Point[10, 2]]".to_string()));
+        assert_eq!(runtime.context.get_variable("missing").unwrap(), &Value::Integer(1));
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_field_types_are_checked() {
+        let result = try_spell("⌻Point[⟁x, ⟁y]\n⌻p=Point[1, \"two\"]\n❧");
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch { .. })));
+
+        let result = try_spell("⌻Point[⟁x, ⟁y]\n⌻p=Point[1, 2]\np⊷y=◉\n❧");
+        assert!(matches!(result, Err(RuntimeError::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_trait_methods_dispatch_on_receiver_type() {
+        let runtime = run_spell(
+            "⌻Point[⟁x, ⟁y]\n⍟Norm[norm[], plus[⟁k]]\n\
+             ⍜Norm[Point]\nλnorm[⌻self]\n⤶[self⊷x ✚ self⊷y]\nΛ\nλplus[⌻self, ⟁k]\n⤶[Point[self⊷x ✚ k, self⊷y]]\nΛ\n⟣\n\
+             ⍜Norm[⟁]\nλnorm[⟁self]\n⤶[self ✱ self]\nΛ\nλplus[⟁self, ⟁k]\n⤶[self ✚ k]\nΛ\n⟣\n\
+             ⟁total=0\n⌬xs=[Point[1, 2], 4]\n⊂[x ∈ xs]\ntotal=total ✚ x⊷plus[10]⊷norm[]\n⊃\n\
+             ⌘kind=\"\"\n☄\n⚡[\"s\"⊷norm[]]\n☊[e]\nkind=e⌷\"kind\"\n⟣\n❧",
+        );
+
+        // (11 + 2) + 14 * 14
+        assert_eq!(runtime.context.get_variable("total").unwrap(), &Value::Integer(209));
+        assert_eq!(runtime.context.get_variable("kind").unwrap(), &Value::String("NoMethod".to_string()));
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_closures_share_upvalues() {
+        let runtime = run_spell(
+            "λmake_counter[]\n⟁n=0\n⤶[λ[] n=n✚1 ⤶[n] Λ]\nΛ\n\
+             ⟁c=⤷make_counter[]\n⟁d=⤷make_counter[]\n⤷c[]\n⤷c[]\n⤷d[]\n⟁counted=⤷c[]\n\
+             λpair[]\n⟁total=0\n⟁add=λ[⟁k] total=total✚k Λ\n⟁get=λ[] ⤶[total] Λ\n⤷add[5]\ntotal=total✚100\n⤷add[2]\n⤶[get]\nΛ\n\
+             ⟁get=⤷pair[]\n⟁shared=⤷get[]\n❧",
+        );
+
+        // Each counter has its own n; the function and its closures see each other's updates
+        assert_eq!(runtime.context.get_variable("counted").unwrap(), &Value::Integer(3));
+        assert_eq!(runtime.context.get_variable("shared").unwrap(), &Value::Integer(107));
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_lambdas_capture_loop_and_outer_variables() {
+        let runtime = run_spell(
+            "λcallbacks[]\n⌬fs=[]\n⊂[i ∈ 0‥3]\nfs⊞λ[] ⤶[i ✱ 10] Λ\n⊃\n⤶[fs]\nΛ\n\
+             ⌬fs=⤷callbacks[]\n⟁sum=0\n⊂[f ∈ fs]\nsum=sum✚⤷f[]✱⤷f[]\n⊃\n\
+             λouter[]\n⟁x=1\nλmid[]\n⤶[λ[] x=x✱2 Λ]\nΛ\n⟁h=⤷mid[]\n⤷h[]\n⤷h[]\n⤶[x]\nΛ\n⟁doubled=⤷outer[]\n\
+             λbag[]\n⌬items=[]\n⤶[λ[⟁v] items⊞v ⤶[⤷len[items]] Λ]\nΛ\n⟁put=⤷bag[]\n⤷put[1]\n⟁held=⤷put[2]\n❧",
+        );
+
+        // Each iteration's closure keeps its own i: 0 + 100 + 400
+        assert_eq!(runtime.context.get_variable("sum").unwrap(), &Value::Integer(500));
+        assert_eq!(runtime.context.get_variable("doubled").unwrap(), &Value::Integer(4));
+        assert_eq!(runtime.context.get_variable("held").unwrap(), &Value::Integer(2));
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_higher_order_operators() {
+        let runtime = run_spell(
+            "λdouble[⟁x]\n⤶[x✱2]\nΛ\nλadd[⟁a, ⟁b]\n⤶[a✚b]\nΛ\n⌬xs=[1, 2, 3, 4]\n\
+             ⟁evens=xs ⧊ λ[⟁x] ⤶[x⌗2⩵0] Λ ⧉ double ⧋ add\n⟁seeded=xs ⧋[100] add\n\
+             ⟁piped=3 ⋈ double ⋈ double\n⟁inc=double ⋉ λ[⟁x] ⤶[x✚1] Λ\n⟁composed=⤷inc[5]\n\
+             ⌖m={\"a\"⇒1, \"b\"⇒2}\n⌖tens=m ⧉ λ[⌘k, ⟁v] ⤶[v✱10] Λ\n⟁b=tens⌷\"b\"\n\
+             ⟁values=m ⧋[0] add\n⟁total=0\n⌬same=xs ⧉ λ[⟁x] total=total✚x ⤶[x] Λ\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("evens").unwrap(), &Value::Integer(12));
+        assert_eq!(runtime.context.get_variable("seeded").unwrap(), &Value::Integer(110));
+        assert_eq!(runtime.context.get_variable("piped").unwrap(), &Value::Integer(12));
+        assert_eq!(runtime.context.get_variable("composed").unwrap(), &Value::Integer(11));
+        assert_eq!(runtime.context.get_variable("b").unwrap(), &Value::Integer(20));
+        assert_eq!(runtime.context.get_variable("values").unwrap(), &Value::Integer(3));
+        assert_eq!(runtime.context.get_variable("total").unwrap(), &Value::Integer(10));
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_callback_exceptions_reach_the_caller() {
+        let runtime = run_spell(
+            "λinv[⟁x]\n⤶[10÷x]\nΛ\n⌬xs=[1, 0]\n⟁kind=\"\"\n\
+             ☄\n⌬ys=xs ⧉ inv\n☊[e ∈ DivisionByZero]\nkind=e⌷\"kind\"\n⟣\n\
+             λsafe[⟁x]\n☄\n⤶[10÷x]\n☊[e]\n⤶[-1]\n⟣\nΛ\n⌬zs=xs ⧉ safe\n⟁last=zs⌷1\n\
+             ⟁empty=\"\"\n⌬none=[]\n☄\n⟁n=none ⧋ inv\n☊[e ∈ EmptyReduce]\nempty=e⌷\"kind\"\n⟣\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("kind").unwrap(), &Value::String("DivisionByZero".to_string()));
+        assert_eq!(runtime.context.get_variable("last").unwrap(), &Value::Integer(-1));
+        assert_eq!(runtime.context.get_variable("empty").unwrap(), &Value::String("EmptyReduce".to_string()));
+        assert!(runtime.stack.is_empty());
+
+        let error = try_spell("⌬xs=[1]\n⌬ys=xs ⧉ 3\n❧").unwrap_err();
+        assert_eq!(error.kind(), "TypeMismatch");
+    }
+
+    #[test]
+    fn test_partial_application() {
+        let runtime = run_spell(
+            "λadd3[⟁a, ⟁b, ⟁c]\n⤶[a✚b✱c]\nΛ\n⟁f=add3⋊[1]\n⟁g=f⋊[2]\n⟁bound=⤷g[10]\n⟁rest=⤷f[2, 3]\n\
+             ⟁plus=λ[⟁a, ⟁b] ⤶[a✚b] Λ\n⌬xs=[1, 2, 3]\n⌬ys=xs ⧉ plus⋊[100]\n⟁last=ys⌷2\n\
+             ⟁twice=plus ⋉ λ[⟁x] ⤶[x✱2] Λ\n⟁once=twice⋊[1]\n⟁composed=⤷once[2]\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("bound").unwrap(), &Value::Integer(21));
+        assert_eq!(runtime.context.get_variable("rest").unwrap(), &Value::Integer(7));
+        assert_eq!(runtime.context.get_variable("last").unwrap(), &Value::Integer(103));
+        assert_eq!(runtime.context.get_variable("composed").unwrap(), &Value::Integer(6));
+
+        // Only a function value's own arity is known when it is bound
+        let error = try_spell("⟁plus=λ[⟁a, ⟁b] ⤶[a✚b] Λ\n⟁f=plus⋊[1, 2, 3]\n❧").unwrap_err();
+        assert!(matches!(error, RuntimeError::WrongArity { expected: 2, got: 3, .. }));
+        assert_eq!(try_spell("⟁f=3⋊[1]\n❧").unwrap_err().kind(), "TypeMismatch");
+    }
+
+    #[test]
+    fn test_defaults_variadics_and_named_arguments() {
+        let runtime = run_spell(
+            "λscale[⟁x, ⟁by=2, ⟁plus=0]\n⤶[x✱by✚plus]\nΛ\n\
+             ⟁plain=⤷scale[5]\n⟁given=⤷scale[5, 3]\n⟁named=⤷scale[5, plus=1]\n⟁reordered=⤷scale[plus=1, x=5, by=10]\n\
+             λsum[⟁first, ‥⌬rest]\n⤶[rest ⧋[first] λ[⟁a, ⟁b] ⤶[a✚b] Λ]\nΛ\n⟁alone=⤷sum[1]\n⟁many=⤷sum[1, 2, 3]\n\
+             ⟁f=scale\n⟁indirect=⤷f[4, by=5]\n⟁s=sum⋊[10]\n⟁bound=⤷s[5]\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("plain").unwrap(), &Value::Integer(10));
+        assert_eq!(runtime.context.get_variable("given").unwrap(), &Value::Integer(15));
+        assert_eq!(runtime.context.get_variable("named").unwrap(), &Value::Integer(11));
+        assert_eq!(runtime.context.get_variable("reordered").unwrap(), &Value::Integer(51));
+        assert_eq!(runtime.context.get_variable("alone").unwrap(), &Value::Integer(1));
+        assert_eq!(runtime.context.get_variable("many").unwrap(), &Value::Integer(6));
+        assert_eq!(runtime.context.get_variable("indirect").unwrap(), &Value::Integer(20));
+        assert_eq!(runtime.context.get_variable("bound").unwrap(), &Value::Integer(15));
+
+        // Calls through a value are checked when they run
+        let header = "λpair[⟁a, ⟁b]\n⤶[a]\nΛ\n⟁p=pair\n";
+        assert_eq!(try_spell_with(header, "⤷p[1]").unwrap_err().kind(), "WrongArity");
+        assert_eq!(try_spell_with(header, "⤷p[1, 2, 3]").unwrap_err().kind(), "WrongArity");
+        assert_eq!(try_spell_with(header, "⤷p[1, c=2]").unwrap_err().kind(), "UnknownArgument");
+        assert_eq!(try_spell_with(header, "⤷p[1, a=2]").unwrap_err().kind(), "DuplicateArgument");
+    }
+
+    #[test]
+    fn test_forward_references_and_mutual_recursion() {
+        let runtime = run_spell(
+            "⟁early=⤷is_even[10]\n⟁odd=⤷is_odd[7]\n⌬xs=[1, 2, 3]\n⟁total=xs ⧉ double ⧋ λ[⟁a, ⟁b] ⤶[a✚b] Λ\n\
+             λis_even[⟁n]\n⟨n ⩵ 0]\n⤶[◉]\n⟫\n⤶[⤷is_odd[n ☠︎ 1]]\nΛ\n\
+             λis_odd[⟁n]\n⟨n ⩵ 0]\n⤶[◎]\n⟫\n⤶[⤷is_even[n ☠︎ 1]]\nΛ\n\
+             λdouble[⟁x]\n⤶[x✱2]\nΛ\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("early").unwrap(), &Value::Boolean(true));
+        assert_eq!(runtime.context.get_variable("odd").unwrap(), &Value::Boolean(true));
+        assert_eq!(runtime.context.get_variable("total").unwrap(), &Value::Integer(12));
+    }
+
+    #[test]
+    fn test_variables_shadow_functions_declared_later() {
+        let runtime = run_spell(
+            "λf[⟁count]\n⤶[count ✚ 1]\nΛ\n⟁first=⤷f[41]\n\
+             ⟁total=5\n⟁second=total ✚ 1\n⟁zero=⤷count[] ✚ ⤷total[]\n\
+             λcount[]\n⤶[0]\nΛ\nλtotal[]\n⤶[0]\nΛ\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("first").unwrap(), &Value::Integer(42));
+        assert_eq!(runtime.context.get_variable("second").unwrap(), &Value::Integer(6));
+        assert_eq!(runtime.context.get_variable("zero").unwrap(), &Value::Integer(0));
+    }
+
+    #[test]
+    fn test_tail_calls_reuse_the_frame() {
+        let runtime = run_spell(
+            "λcount[⟁n, ⟁total]\n⟨n ⩵ 0]\n⤶[total]\n⟫\n⤶[⤷count[n ☠︎ 1, total ✚ n]]\nΛ\n\
+             λping[⟁n]\n⟨n ⩵ 0]\n⤶[\"ping\"]\n⟫\n⤶[⤷pong[n ☠︎ 1]]\nΛ\n\
+             λpong[⟁n]\n⟨n ⩵ 0]\n⤶[\"pong\"]\n⟫\n⤶[⤷ping[n ☠︎ 1]]\nΛ\n\
+             λloop_with[⟁step, ⟁n]\n⟨n ⩵ 0]\n⤶[0]\n⟫\n⤶[⤷step[step, n ☠︎ 1]]\nΛ\n\
+             ⟁sum=⤷count[5000, 0]\n⌘last=⤷ping[5001]\n⟁done=⤷loop_with[loop_with, 5000]\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("sum").unwrap(), &Value::Integer(12502500));
+        assert_eq!(runtime.context.get_variable("last").unwrap(), &Value::String("pong".to_string()));
+        assert_eq!(runtime.context.get_variable("done").unwrap(), &Value::Integer(0));
+        assert_eq!(runtime.context.call_depth(), 1);
+
+        // Without a tail call the frames still run out
+        let deep = "λgrow[⟁n]\n⟨n ⩵ 0]\n⤶[0]\n⟫\n⤶[⤷grow[n ☠︎ 1] ✚ 1]\nΛ\n⟁n=⤷grow[5000]\n❧";
+        assert!(matches!(try_spell(deep), Err(RuntimeError::ContextError(ContextError::CallStackOverflow))));
+
+        // A handler further out still catches what a tail-called function throws
+        let runtime = run_spell(
+            "λboom[⟁n]\n⟨n ⩵ 0]\n⚠[\"deep\"]\n⟫\n⤶[⤷boom[n ☠︎ 1]]\nΛ\n⌘caught=\"\"\n\
+             ☄\n⟁x=⤷boom[3000]\n☊[e]\ncaught=e\n⟣\n❧",
+        );
+        assert_eq!(runtime.context.get_variable("caught").unwrap(), &Value::String("deep".to_string()));
+    }
+
+    #[test]
+    fn test_returned_calls_in_loops_keep_the_accumulator() {
+        let runtime = run_spell(
+            "λinner[⟁n]\n⤶[✹ ✚ n]\nΛ\n\
+             λtail[⟁n]\n⊂[◉]\n✹=100\n⤶[⤷inner[n]]\n⊃\n⤶[0]\nΛ\n\
+             λkept[⟁n]\n⊂[◉]\n✹=100\n⟁r=⤷inner[n]\n⤶[r]\n⊃\n⤶[0]\nΛ\n\
+             ⟁a=⤷tail[1]\n⟁b=⤷kept[1]\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("a").unwrap(), &Value::Integer(101));
+        assert_eq!(runtime.context.get_variable("b").unwrap(), &Value::Integer(101));
+    }
+
+    #[test]
+    fn test_generators_suspend_and_resume() {
+        let runtime = run_spell(
+            "λcount_up[⟁from, ⟁to]\n⊂[i ∈ from‥to]\n⌬one=[i]\n⊂[x ∈ ⤷iter[one]]\n⌭[x]\n⊃\n⊃\nΛ\n\
+             λticks[]\n⟁n=0\n⟁bump=λ[] n=n✚10 Λ\n⌭[bump]\nn=n✚1\n⌭[n]\nΛ\n\
+             ⟁i=99\n⟁sum=0\n⊂[x ∈ ⤷count_up[1, 5]]\n⊂[y ∈ ⤷count_up[0, x]]\nsum=sum✚y\n⊃\n⊃\n\
+             ⌭g=⤷count_up[10, 13]\n⟁first=⤷next[g]\n⌬rest=⤷collect[g]\n⟁after=⤷next[g]\n\
+             ⌭t=⤷ticks[]\n⟁bump=⤷next[t]\n⤷bump[]\n⟁ticked=⤷next[t]\n❧",
+        );
+
+        // 0 + (0+1) + (0+1+2) + (0+1+2+3)
+        assert_eq!(runtime.context.get_variable("sum").unwrap(), &Value::Integer(10));
+        assert_eq!(runtime.context.get_variable("i").unwrap(), &Value::Integer(99));
+        assert_eq!(runtime.context.get_variable("first").unwrap(), &Value::Integer(10));
+        assert_eq!(runtime.context.get_variable("rest").unwrap(), &Value::array(vec![Value::Integer(11), Value::Integer(12)]));
+        assert_eq!(runtime.context.get_variable("after").unwrap(), &Value::Null);
+        // The closure's update made while the generator was suspended is kept
+        assert_eq!(runtime.context.get_variable("ticked").unwrap(), &Value::Integer(11));
+        assert_eq!(runtime.context.loop_depth(), 0);
+        assert_eq!(runtime.context.call_depth(), 1);
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_lazy_iterators() {
+        let runtime = run_spell(
+            "λnaturals[]\n⟁n=0\n⊂[◉]\n⌭[n]\nn=n✚1\n⊃\nΛ\n\
+             ⌭squares=⤷naturals[] ⧊ λ[⟁x] ⤶[x⌗2⩵0] Λ ⧉ λ[⟁x] ⤶[x✱x] Λ\n\
+             ⌬seen=[]\n⊂[v ∈ squares]\n⟨v ▷ 50] ⊗ ⟫\nseen⊞v\n⊃\n⟁resumed=⤷next[squares]\n\
+             ⟁total=⤷iter_range[1, 101] ⧋ λ[⟁a, ⟁b] ⤶[a✚b] Λ\n\
+             ⟁huge=⤷next[⤷iter_range[0, 9223372036854775807]]\n\
+             ⌖m={\"a\"⇒1, \"b\"⇒2}\n⌬keys=⤷collect[⤷iter[m]]\n⌬marks=⤷collect[⤷iter[\"e\u{301}a\"]]\n\
+             ⌘counted=\"\"\n⊂[k ⇒ c ∈ ⤷iter[\"xy\"]]\ncounted=counted✚⌘[k]✚c\n⊃\n❧",
+        );
+
+        let ints = |values: &[i64]| Value::array(values.iter().map(|&v| Value::Integer(v)).collect());
+        assert_eq!(runtime.context.get_variable("seen").unwrap(), &ints(&[0, 4, 16, 36]));
+        // Only as much was taken as the loop asked for
+        assert_eq!(runtime.context.get_variable("resumed").unwrap(), &Value::Integer(100));
+        assert_eq!(runtime.context.get_variable("total").unwrap(), &Value::Integer(5050));
+        assert_eq!(runtime.context.get_variable("huge").unwrap(), &Value::Integer(0));
+        let strings = |values: &[&str]| Value::array(values.iter().map(|v| Value::String(v.to_string())).collect());
+        assert_eq!(runtime.context.get_variable("keys").unwrap(), &strings(&["a", "b"]));
+        assert_eq!(runtime.context.get_variable("marks").unwrap(), &strings(&["e\u{301}", "a"]));
+        assert_eq!(runtime.context.get_variable("counted").unwrap(), &Value::String("0x1y".to_string()));
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_generator_exceptions() {
+        let runtime = run_spell(
+            "λboom[]\n⌭[1]\n⚠[\"kaput\"]\nΛ\n⌭b=⤷boom[]\n⟁got=0\n⌘caught=\"\"\n\
+             ☄\n⊂[x ∈ b]\ngot=got✚x\n⊃\n☊[e]\ncaught=e\n⟣\n⟁after=⤷next[b]\n\
+             λselfish[]\n⌭[⤷next[me]]\nΛ\n⌭me=⤷selfish[]\n⌘kind=\"\"\n\
+             ☄\n⤷next[me]\n☊[e]\nkind=e⌷\"kind\"\n⟣\n\
+             λguarded[]\n☄\n⚠[\"inside\"]\n☊[e]\n⟣\n⌭[2]\nΛ\n⟁kept=⤷next[⤷guarded[]]\n❧",
+        );
+
+        assert_eq!(runtime.context.get_variable("got").unwrap(), &Value::Integer(1));
+        assert_eq!(runtime.context.get_variable("caught").unwrap(), &Value::String("kaput".to_string()));
+        // A generator that failed is finished
+        assert_eq!(runtime.context.get_variable("after").unwrap(), &Value::Null);
+        assert_eq!(runtime.context.get_variable("kind").unwrap(), &Value::String("RunningGenerator".to_string()));
+        assert_eq!(runtime.context.get_variable("kept").unwrap(), &Value::Integer(2));
+        assert_eq!(runtime.context.call_depth(), 1);
+        assert!(runtime.stack.is_empty());
+    }
+
+    #[test]
+    fn test_nested_execution_loops_run_out_cleanly() {
+        let spells = [
+            // Recursion through a ⧉ callback
+            "λdeep[⟁n]\n⟨n ⩵ 0]\n⤶[0]\n⟫\n⌬one=[n]\n⌬out=one ⧉ λ[⟁x] ⤶[⤷deep[x ☠︎ 1] ✚ 1] Λ\n⤶[out⌷0]\nΛ\n\
+             ⟁d=⤷deep[DEPTH]\n❧",
+            // Recursion through generators, each resuming the next
+            "λcount[⟁n]\n⟨n ▷ 0]\n⊂[x ∈ ⤷count[n ☠︎ 1]]\n⌭[x]\n⊃\n⟫\n⌭[n]\nΛ\n\
+             ⟁last=0\n⊂[x ∈ ⤷count[DEPTH]]\nlast=x\n⊃\n❧",
+        ];
+
+        for spell in spells {
+            assert_eq!(try_spell_nested(spell.replace("DEPTH", "60")), Ok(()));
+            assert_eq!(try_spell_nested(spell.replace("DEPTH", "500")), Err("CallStackOverflow"));
+        }
+    }
 }
@@ -121,6 +121,11 @@ impl Stack {
         self.values.clear();
     }
 
+    /// Drop values until at most `len` remain
+    pub fn truncate(&mut self, len: usize) {
+        self.values.truncate(len);
+    }
+
     /// Get all values (for debugging)
     pub fn values(&self) -> &[Value] {
         &self.values